@@ -0,0 +1,344 @@
+//! Auditing of build scripts for hermeticity.
+//!
+//! Build scripts may read files and environment variables which aren't inputs
+//! of the Bazel action, or write absolute paths and timestamps to `OUT_DIR`,
+//! all of which make their outputs unreproducible. Scripts declare what they
+//! read with `rerun-if-changed` and `rerun-if-env-changed`, which are checked
+//! against the action's inputs and environment.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::BuildScriptOutput;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// A hermeticity issue found in a build script.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Finding {
+    /// A `rerun-if-changed` path which isn't an input of the action.
+    UndeclaredInput(String),
+    /// A `rerun-if-env-changed` variable which isn't set for the action.
+    UndeclaredEnv(String),
+    /// A file in `OUT_DIR` containing an absolute exec root or sandbox path.
+    LeakedPath { file: String, path: String },
+    /// A file in `OUT_DIR` containing the time at which the script ran.
+    Timestamp { file: String, timestamp: String },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Finding::UndeclaredInput(path) => write!(
+                f,
+                "undeclared input `{path}` (from `cargo::rerun-if-changed`)"
+            ),
+            Finding::UndeclaredEnv(name) => write!(
+                f,
+                "undeclared environment variable `{name}` (from `cargo::rerun-if-env-changed`)"
+            ),
+            Finding::LeakedPath { file, path } => {
+                write!(f, "`{file}` contains the absolute path `{path}`")
+            }
+            Finding::Timestamp { file, timestamp } => {
+                write!(f, "`{file}` contains the timestamp `{timestamp}`")
+            }
+        }
+    }
+}
+
+/// What the action declared to Bazel, and when the build script ran.
+pub struct Audit<'a> {
+    /// The absolute path of the exec root.
+    pub exec_root: &'a Path,
+    /// The absolute path of `CARGO_MANIFEST_DIR`, which relative
+    /// `rerun-if-changed` paths are resolved against.
+    pub manifest_dir: &'a Path,
+    /// The exec root relative paths of the action's inputs. Directories
+    /// include everything within them.
+    pub inputs: &'a BTreeSet<PathBuf>,
+    /// The names of the environment variables set for the build script.
+    pub env: &'a BTreeSet<String>,
+    /// When the build script started.
+    pub start: SystemTime,
+    /// When the build script finished.
+    pub end: SystemTime,
+}
+
+impl Audit<'_> {
+    /// Returns the findings for the outputs of a build script and the files
+    /// it wrote to `out_dir`.
+    pub fn run(
+        &self,
+        outputs: &[BuildScriptOutput],
+        out_dir: &Path,
+    ) -> Result<Vec<Finding>, String> {
+        let mut findings = BTreeSet::new();
+        for output in outputs {
+            match output {
+                BuildScriptOutput::RerunIfChanged(path) if !self.is_declared_input(path) => {
+                    findings.insert(Finding::UndeclaredInput(path.clone()));
+                }
+                BuildScriptOutput::RerunIfEnvChanged(name) if !self.env.contains(name) => {
+                    findings.insert(Finding::UndeclaredEnv(name.clone()));
+                }
+                _ => {}
+            }
+        }
+
+        let leaked_paths = self.leaked_paths();
+        let timestamps = timestamps(self.start, self.end);
+        let mut files = Vec::new();
+        list_files(out_dir, &mut files)
+            .map_err(|e| format!("Failed to list {}: {e}", out_dir.display()))?;
+        for file in files {
+            let Ok(contents) = fs::read(&file) else {
+                continue;
+            };
+            let name = file
+                .strip_prefix(self.exec_root)
+                .unwrap_or(&file)
+                .display()
+                .to_string();
+            if let Some(path) = leaked_paths.iter().find(|p| contains(&contents, p)) {
+                findings.insert(Finding::LeakedPath {
+                    file: name.clone(),
+                    path: path.clone(),
+                });
+            }
+            if let Some(timestamp) = timestamps.iter().find(|t| contains(&contents, t)) {
+                findings.insert(Finding::Timestamp {
+                    file: name.clone(),
+                    timestamp: timestamp.clone(),
+                });
+            } else if let Some(timestamp) = find_unix_time(&contents, self.start, self.end) {
+                findings.insert(Finding::Timestamp {
+                    file: name,
+                    timestamp,
+                });
+            }
+        }
+        Ok(findings.into_iter().collect())
+    }
+
+    /// Whether `path`, as written by the build script, is within an input of
+    /// the action.
+    fn is_declared_input(&self, path: &str) -> bool {
+        let Some(path) = normalize(&self.manifest_dir.join(path)) else {
+            return false;
+        };
+        let Ok(relative) = path.strip_prefix(self.exec_root) else {
+            // Absolute paths outside of the exec root are never inputs.
+            return false;
+        };
+        // A directory is considered declared if it contains declared inputs.
+        if self
+            .inputs
+            .iter()
+            .any(|input| relative.starts_with(input) || input.starts_with(relative))
+        {
+            return true;
+        }
+        // Runfiles directories only contain the declared data of the script.
+        let in_runfiles = self.manifest_dir.components().any(|c| {
+            c.as_os_str()
+                .to_str()
+                .is_some_and(|c| c.ends_with(".runfiles"))
+        });
+        in_runfiles && path.starts_with(self.manifest_dir) && path.exists()
+    }
+
+    /// The absolute paths which shouldn't be found in outputs, the exec root
+    /// and what it resolves to, which is a sandbox when sandboxing is used.
+    fn leaked_paths(&self) -> Vec<String> {
+        let mut paths = vec![self.exec_root.display().to_string()];
+        if let Ok(canonical) = self.exec_root.canonicalize() {
+            if canonical != self.exec_root {
+                paths.push(canonical.display().to_string());
+            }
+        }
+        paths
+    }
+}
+
+/// Reads the list of inputs written by Bazel, one exec root relative path per
+/// line.
+pub fn read_inputs(path: &Path) -> Result<BTreeSet<PathBuf>, String> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read audit inputs {}: {e}", path.display()))?;
+    Ok(contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Lexically resolves the `.` and `..` components of an absolute path.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return None;
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    Some(normalized)
+}
+
+fn list_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            list_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    let needle = needle.as_bytes();
+    !needle.is_empty() && haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Converts days since the unix epoch to a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, usize, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as usize;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// The dates on which the script ran, in the formats commonly embedded by
+/// build scripts. A day is added on both sides to account for time zones.
+fn timestamps(start: SystemTime, end: SystemTime) -> Vec<String> {
+    let first = unix_seconds(start) / SECONDS_PER_DAY;
+    let last = unix_seconds(end) / SECONDS_PER_DAY;
+    let mut timestamps = Vec::new();
+    for days in first.saturating_sub(1)..=last + 1 {
+        let (year, month, day) = civil_from_days(days as i64);
+        // ISO 8601, and C's `__DATE__`.
+        timestamps.push(format!("{year:04}-{month:02}-{day:02}"));
+        timestamps.push(format!("{} {day:>2} {year}", MONTHS[month - 1]));
+    }
+    timestamps
+}
+
+/// Finds a number of seconds since the unix epoch between `start` and `end`.
+fn find_unix_time(contents: &[u8], start: SystemTime, end: SystemTime) -> Option<String> {
+    let (start, end) = (unix_seconds(start), unix_seconds(end));
+    contents
+        .split(|b| !b.is_ascii_digit())
+        .filter(|digits| digits.len() == 10)
+        .filter_map(|digits| std::str::from_utf8(digits).ok())
+        .find(|digits| digits.parse().is_ok_and(|t: u64| start <= t && t <= end))
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::time::Duration;
+
+    #[test]
+    fn test_civil_from_days() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(20_744), (2026, 10, 18));
+    }
+
+    #[test]
+    fn test_audit() {
+        let exec_root = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap()).join("audit");
+        let manifest_dir = exec_root.join("pkg");
+        let out_dir = exec_root.join("out_dir");
+        fs::create_dir_all(out_dir.join("nested")).unwrap();
+        fs::create_dir_all(&manifest_dir).unwrap();
+
+        let start = UNIX_EPOCH + Duration::from_secs(1_792_300_000);
+        let end = start + Duration::from_secs(10);
+        fs::write(out_dir.join("clean.rs"), "pub const X: u32 = 1;").unwrap();
+        fs::write(
+            out_dir.join("nested/path.rs"),
+            format!("include!(\"{}/gen.rs\");", exec_root.display()),
+        )
+        .unwrap();
+        fs::write(
+            out_dir.join("date.rs"),
+            "pub const DATE: &str = \"Oct 18 2026\";",
+        )
+        .unwrap();
+        fs::write(out_dir.join("time.rs"), "pub const TIME: u64 = 1792300005;").unwrap();
+
+        let inputs = vec![PathBuf::from("pkg/src"), PathBuf::from("pkg/build.rs")]
+            .into_iter()
+            .collect();
+        let env = vec!["TARGET".to_owned()].into_iter().collect();
+        let audit = Audit {
+            exec_root: &exec_root,
+            manifest_dir: &manifest_dir,
+            inputs: &inputs,
+            env: &env,
+            start,
+            end,
+        };
+        let outputs = [
+            BuildScriptOutput::RerunIfChanged("build.rs".to_owned()),
+            BuildScriptOutput::RerunIfChanged("src/lib.rs".to_owned()),
+            BuildScriptOutput::RerunIfChanged("../pkg/wrapper.h".to_owned()),
+            BuildScriptOutput::RerunIfChanged("/usr/include/zlib.h".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("TARGET".to_owned()),
+            BuildScriptOutput::RerunIfEnvChanged("ZLIB_DIR".to_owned()),
+        ];
+        let mut findings = audit.run(&outputs, &out_dir).unwrap();
+        findings.sort();
+        let out_dir_name = |name: &str| format!("out_dir/{name}");
+        assert_eq!(
+            findings,
+            vec![
+                Finding::UndeclaredInput("../pkg/wrapper.h".to_owned()),
+                Finding::UndeclaredInput("/usr/include/zlib.h".to_owned()),
+                Finding::UndeclaredEnv("ZLIB_DIR".to_owned()),
+                Finding::LeakedPath {
+                    file: out_dir_name("nested/path.rs"),
+                    path: exec_root.display().to_string(),
+                },
+                Finding::Timestamp {
+                    file: out_dir_name("date.rs"),
+                    timestamp: "Oct 18 2026".to_owned(),
+                },
+                Finding::Timestamp {
+                    file: out_dir_name("time.rs"),
+                    timestamp: "1792300005".to_owned(),
+                },
+            ]
+        );
+    }
+}
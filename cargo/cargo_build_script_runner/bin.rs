@@ -15,13 +15,19 @@
 //! A simple wrapper around a build_script execution to generate file to reuse
 //! by rust_library/rust_binary.
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::env;
 use std::fs::{create_dir_all, read_to_string, write};
+use std::num::NonZeroUsize;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::time::SystemTime;
 
+use cargo_build_script_runner::audit::{self, Audit};
 use cargo_build_script_runner::cargo_manifest_dir::{remove_symlink, symlink, RunfilesMaker};
+#[cfg(unix)]
+use cargo_build_script_runner::jobserver::Jobserver;
+use cargo_build_script_runner::report::Report;
 use cargo_build_script_runner::{BuildScriptOutput, CompileAndLinkFlags};
 
 fn run_buildrs() -> Result<(), String> {
@@ -43,12 +49,20 @@ fn run_buildrs() -> Result<(), String> {
         compile_flags_file,
         link_flags_file,
         link_search_paths_file,
+        bin_link_flags_file,
+        cdylib_link_flags_file,
         output_dep_env_path,
         stdout_path,
         stderr_path,
         rundir,
         input_dep_env_paths,
         cargo_manifest_maker,
+        output_override,
+        audit_report_path,
+        audit_inputs_path,
+        audit_fail,
+        jobs,
+        report_path,
     } = Args::parse();
 
     if let Some(cargo_manifest_maker) = &cargo_manifest_maker {
@@ -61,115 +75,197 @@ fn run_buildrs() -> Result<(), String> {
         .unwrap_or_else(|_| panic!("Failed to make output directory: {:?}", out_dir_abs));
 
     let mut exec_root_links = Vec::new();
-    if should_symlink_exec_root() {
-        // Symlink the execroot to the manifest_dir so that we can use relative paths in the arguments.
-        let exec_root_paths = std::fs::read_dir(&exec_root)
-            .map_err(|err| format!("Failed while listing exec root: {err:?}"))?;
-        for path in exec_root_paths {
-            let path = path
-                .map_err(|err| {
-                    format!("Failed while getting path from exec root listing: {err:?}")
-                })?
-                .path();
-
-            let file_name = path
-                .file_name()
-                .ok_or_else(|| "Failed while getting file name".to_string())?;
-            let link = manifest_dir.join(file_name);
-
-            symlink_if_not_exists(&path, &link)
-                .map_err(|err| format!("Failed to symlink {path:?} to {link:?}: {err}"))?;
-
-            exec_root_links.push(link)
+    let mut script_env: BTreeSet<String> = env::vars().map(|(key, _)| key).collect();
+    let start = SystemTime::now();
+    let (buildrs_outputs, status, stdout, stderr) = if let Some(output_override) = &output_override
+    {
+        // The outputs are provided by the user, so the script isn't run at all.
+        let outputs = BuildScriptOutput::outputs_from_override(
+            &exec_root.join(output_override),
+            &exec_root.to_string_lossy(),
+        )?;
+        (outputs, None, Vec::new(), Vec::new())
+    } else {
+        if should_symlink_exec_root() {
+            // Symlink the execroot to the manifest_dir so that we can use relative paths in the arguments.
+            let exec_root_paths = std::fs::read_dir(&exec_root)
+                .map_err(|err| format!("Failed while listing exec root: {err:?}"))?;
+            for path in exec_root_paths {
+                let path = path
+                    .map_err(|err| {
+                        format!("Failed while getting path from exec root listing: {err:?}")
+                    })?
+                    .path();
+
+                let file_name = path
+                    .file_name()
+                    .ok_or_else(|| "Failed while getting file name".to_string())?;
+                let link = manifest_dir.join(file_name);
+
+                symlink_if_not_exists(&path, &link)
+                    .map_err(|err| format!("Failed to symlink {path:?} to {link:?}: {err}"))?;
+
+                exec_root_links.push(link)
+            }
         }
-    }
 
-    let target_env_vars =
-        get_target_env_vars(&rustc_env).expect("Error getting target env vars from rustc");
-
-    let working_directory = resolve_rundir(&rundir, &exec_root, &manifest_dir)?;
-
-    let mut command = Command::new(exec_root.join(progname));
-    command
-        .current_dir(&working_directory)
-        .envs(target_env_vars)
-        .env("OUT_DIR", &out_dir_abs)
-        .env("CARGO_MANIFEST_DIR", manifest_dir)
-        .env("RUSTC", rustc)
-        .env("RUST_BACKTRACE", "full");
-
-    for dep_env_path in input_dep_env_paths.iter() {
-        if let Ok(contents) = read_to_string(dep_env_path) {
-            for line in contents.split('\n') {
-                // split on empty contents will still produce a single empty string in iterable.
-                if line.is_empty() {
-                    continue;
-                }
-                match line.split_once('=') {
-                    Some((key, value)) => {
-                        command.env(key, value.replace("${pwd}", &exec_root.to_string_lossy()));
+        let target_env_vars =
+            get_target_env_vars(&rustc_env).expect("Error getting target env vars from rustc");
+
+        let working_directory = resolve_rundir(&rundir, &exec_root, &manifest_dir)?;
+
+        let mut command = Command::new(exec_root.join(&progname));
+        command
+            .current_dir(&working_directory)
+            .envs(target_env_vars)
+            .env("OUT_DIR", &out_dir_abs)
+            .env("CARGO_MANIFEST_DIR", &manifest_dir)
+            .env("RUSTC", rustc)
+            .env("RUST_BACKTRACE", "full");
+
+        for dep_env_path in input_dep_env_paths.iter() {
+            if let Ok(contents) = read_to_string(dep_env_path) {
+                for line in contents.split('\n') {
+                    // split on empty contents will still produce a single empty string in iterable.
+                    if line.is_empty() {
+                        continue;
                     }
-                    _ => {
-                        return Err(
-                            "error: Wrong environment file format, should not happen".to_owned()
-                        )
+                    match line.split_once('=') {
+                        Some((key, value)) => {
+                            command.env(key, value.replace("${pwd}", &exec_root.to_string_lossy()));
+                        }
+                        _ => {
+                            return Err("error: Wrong environment file format, should not happen"
+                                .to_owned())
+                        }
                     }
                 }
+            } else {
+                return Err("error: Dependency environment file unreadable".to_owned());
             }
-        } else {
-            return Err("error: Dependency environment file unreadable".to_owned());
         }
-    }
 
-    for tool_env_var in &["CC", "CXX", "LD"] {
-        if let Some(tool_path) = env::var_os(tool_env_var) {
-            command.env(tool_env_var, exec_root.join(tool_path));
+        for tool_env_var in &["CC", "CXX", "LD"] {
+            if let Some(tool_path) = env::var_os(tool_env_var) {
+                command.env(tool_env_var, exec_root.join(tool_path));
+            }
+        }
+
+        if let Some(ar_path) = env::var_os("AR") {
+            // The default OSX toolchain uses libtool as ar_executable not ar.
+            // This doesn't work when used as $AR, so simply don't set it - tools will probably fall back to
+            // /usr/bin/ar which is probably good enough.
+            if Path::new(&ar_path).file_name() == Some("libtool".as_ref()) {
+                command.env_remove("AR");
+            } else {
+                command.env("AR", exec_root.join(ar_path));
+            }
         }
-    }
 
-    if let Some(ar_path) = env::var_os("AR") {
-        // The default OSX toolchain uses libtool as ar_executable not ar.
-        // This doesn't work when used as $AR, so simply don't set it - tools will probably fall back to
-        // /usr/bin/ar which is probably good enough.
-        if Path::new(&ar_path).file_name() == Some("libtool".as_ref()) {
-            command.env_remove("AR");
-        } else {
-            command.env("AR", exec_root.join(ar_path));
+        // replace env vars with a ${pwd} prefix with the exec_root
+        for (key, value) in env::vars() {
+            let exec_root_str = exec_root.to_str().expect("exec_root not in utf8");
+            if value.contains("${pwd}") {
+                env::set_var(key, value.replace("${pwd}", exec_root_str));
+            }
         }
-    }
 
-    // replace env vars with a ${pwd} prefix with the exec_root
-    for (key, value) in env::vars() {
-        let exec_root_str = exec_root.to_str().expect("exec_root not in utf8");
-        if value.contains("${pwd}") {
-            env::set_var(key, value.replace("${pwd}", exec_root_str));
+        // Bazel does not support byte strings so in order to correctly represent `CARGO_ENCODED_RUSTFLAGS`
+        // the escaped `\x1f` sequences need to be unescaped
+        if let Ok(encoded_rustflags) = env::var("CARGO_ENCODED_RUSTFLAGS") {
+            command.env(
+                "CARGO_ENCODED_RUSTFLAGS",
+                encoded_rustflags.replace("\\x1f", "\x1f"),
+            );
         }
-    }
 
-    // Bazel does not support byte strings so in order to correctly represent `CARGO_ENCODED_RUSTFLAGS`
-    // the escaped `\x1f` sequences need to be unescaped
-    if let Ok(encoded_rustflags) = env::var("CARGO_ENCODED_RUSTFLAGS") {
-        command.env(
-            "CARGO_ENCODED_RUSTFLAGS",
-            encoded_rustflags.replace("\\x1f", "\x1f"),
-        );
-    }
+        if let Some(jobs) = jobs {
+            command.env("NUM_JOBS", jobs.to_string());
+        }
+        // Dropped, removing the jobserver, once the build script has run.
+        // Jobservers are fifos, which only exist on unix.
+        #[cfg(unix)]
+        let _jobserver = match jobs.map(Jobserver::new).transpose() {
+            Ok(jobserver) => {
+                if let Some(jobserver) = &jobserver {
+                    command.envs(jobserver.env());
+                }
+                jobserver
+            }
+            Err(e) => {
+                eprintln!("Warning: failed to create a jobserver: {e}");
+                None
+            }
+        };
 
-    let (buildrs_outputs, process_output) = BuildScriptOutput::outputs_from_command(&mut command)
-        .map_err(|process_output| {
-        format!(
-            "Build script process failed{}\n--stdout:\n{}\n--stderr:\n{}",
-            if let Some(exit_code) = process_output.status.code() {
-                format!(" with exit code {exit_code}")
+        for (key, value) in command.get_envs() {
+            let key = key.to_string_lossy().into_owned();
+            if value.is_some() {
+                script_env.insert(key);
             } else {
-                String::new()
-            },
-            String::from_utf8(process_output.stdout)
-                .expect("Failed to parse stdout of child process"),
-            String::from_utf8(process_output.stderr)
-                .expect("Failed to parse stdout of child process"),
+                script_env.remove(&key);
+            }
+        }
+
+        let (Ok((buildrs_outputs, process_output)) | Err((buildrs_outputs, process_output))) =
+            BuildScriptOutput::outputs_from_command(&mut command);
+        (
+            buildrs_outputs,
+            Some(process_output.status),
+            process_output.stdout,
+            process_output.stderr,
         )
-    })?;
+    };
+
+    let package = env::var("CARGO_PKG_NAME").unwrap_or_default();
+    let report = Report {
+        package: &package,
+        script: &progname,
+        status,
+        outputs: &buildrs_outputs,
+        stdout: &stdout,
+        stderr: &stderr,
+    };
+    if let Some(path) = &report_path {
+        write(path, report.to_json())
+            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
+    }
+    for warning in report.warnings() {
+        eprintln!("Build Script Warning: {warning}");
+    }
+    for output in &buildrs_outputs {
+        if let BuildScriptOutput::Unsupported(line) = output {
+            eprintln!("Warning: build script returned unsupported directive `{line}`");
+        }
+    }
+    if report.failed() {
+        return Err(report.failure_summary(report_path.as_deref()));
+    }
+
+    if let Some(path) = &audit_report_path {
+        let inputs = match &audit_inputs_path {
+            Some(inputs_path) => audit::read_inputs(&exec_root.join(inputs_path))?,
+            None => BTreeSet::new(),
+        };
+        let findings = Audit {
+            exec_root: &exec_root,
+            manifest_dir: &manifest_dir,
+            inputs: &inputs,
+            env: &script_env,
+            start,
+            end: SystemTime::now(),
+        }
+        .run(&buildrs_outputs, &out_dir_abs)?;
+        let report: String = findings.iter().map(|f| format!("{f}\n")).collect();
+        write(path, &report)
+            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
+        if !findings.is_empty() {
+            if audit_fail {
+                return Err(format!("Build script is not hermetic:\n{report}"));
+            }
+            eprint!("Warning: build script is not hermetic:\n{report}");
+        }
+    }
 
     write(
         &env_file,
@@ -189,18 +285,18 @@ fn run_buildrs() -> Result<(), String> {
     .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", output_dep_env_path, e));
 
     if let Some(path) = &stdout_path {
-        write(path, process_output.stdout)
-            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
+        write(path, stdout).unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
     }
     if let Some(path) = &stderr_path {
-        write(path, process_output.stderr)
-            .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
+        write(path, stderr).unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", path, e));
     }
 
     let CompileAndLinkFlags {
         compile_flags,
         link_flags,
         link_search_paths,
+        bin_link_flags,
+        cdylib_link_flags,
     } = BuildScriptOutput::outputs_to_flags(&buildrs_outputs, &exec_root.to_string_lossy());
 
     write(&compile_flags_file, compile_flags.as_bytes())
@@ -213,6 +309,14 @@ fn run_buildrs() -> Result<(), String> {
             link_search_paths_file, e
         )
     });
+    write(&bin_link_flags_file, bin_link_flags.as_bytes())
+        .unwrap_or_else(|e| panic!("Unable to write file {:?}: {:#?}", bin_link_flags_file, e));
+    write(&cdylib_link_flags_file, cdylib_link_flags.as_bytes()).unwrap_or_else(|e| {
+        panic!(
+            "Unable to write file {:?}: {:#?}",
+            cdylib_link_flags_file, e
+        )
+    });
 
     if !exec_root_links.is_empty() {
         for link in exec_root_links {
@@ -282,12 +386,20 @@ struct Args {
     compile_flags_file: String,
     link_flags_file: String,
     link_search_paths_file: String,
+    bin_link_flags_file: String,
+    cdylib_link_flags_file: String,
     output_dep_env_path: String,
     stdout_path: Option<String>,
     stderr_path: Option<String>,
     rundir: String,
     input_dep_env_paths: Vec<String>,
     cargo_manifest_maker: Option<RunfilesMaker>,
+    output_override: Option<String>,
+    audit_report_path: Option<String>,
+    audit_inputs_path: Option<String>,
+    audit_fail: bool,
+    jobs: Option<NonZeroUsize>,
+    report_path: Option<String>,
 }
 
 impl Args {
@@ -305,6 +417,10 @@ impl Args {
             Err("Argument `link_flags_file` not provided".to_owned());
         let mut link_search_paths_file: Result<String, String> =
             Err("Argument `link_search_paths_file` not provided".to_owned());
+        let mut bin_link_flags_file: Result<String, String> =
+            Err("Argument `bin_link_flags_file` not provided".to_owned());
+        let mut cdylib_link_flags_file: Result<String, String> =
+            Err("Argument `cdylib_link_flags_file` not provided".to_owned());
         let mut output_dep_env_path: Result<String, String> =
             Err("Argument `output_dep_env_path` not provided".to_owned());
         let mut stdout_path = None;
@@ -312,6 +428,12 @@ impl Args {
         let mut rundir: Result<String, String> = Err("Argument `rundir` not provided".to_owned());
         let mut input_dep_env_paths = Vec::new();
         let mut cargo_manifest_maker = None;
+        let mut output_override = None;
+        let mut audit_report_path = None;
+        let mut audit_inputs_path = None;
+        let mut audit_fail = false;
+        let mut jobs = None;
+        let mut report_path = None;
 
         for mut arg in env::args().skip(1) {
             if arg.starts_with("--script=") {
@@ -328,6 +450,10 @@ impl Args {
                 link_flags_file = Ok(arg.split_off("--link_flags=".len()));
             } else if arg.starts_with("--link_search_paths=") {
                 link_search_paths_file = Ok(arg.split_off("--link_search_paths=".len()));
+            } else if arg.starts_with("--bin_link_flags=") {
+                bin_link_flags_file = Ok(arg.split_off("--bin_link_flags=".len()));
+            } else if arg.starts_with("--cdylib_link_flags=") {
+                cdylib_link_flags_file = Ok(arg.split_off("--cdylib_link_flags=".len()));
             } else if arg.starts_with("--dep_env_out=") {
                 output_dep_env_path = Ok(arg.split_off("--dep_env_out=".len()));
             } else if arg.starts_with("--stdout=") {
@@ -338,6 +464,22 @@ impl Args {
                 rundir = Ok(arg.split_off("--rundir=".len()))
             } else if arg.starts_with("--input_dep_env_path=") {
                 input_dep_env_paths.push(arg.split_off("--input_dep_env_path=".len()));
+            } else if arg.starts_with("--output_override=") {
+                output_override = Some(arg.split_off("--output_override=".len()));
+            } else if arg.starts_with("--audit_report=") {
+                audit_report_path = Some(arg.split_off("--audit_report=".len()));
+            } else if arg.starts_with("--audit_inputs=") {
+                audit_inputs_path = Some(arg.split_off("--audit_inputs=".len()));
+            } else if arg == "--audit_fail" {
+                audit_fail = true;
+            } else if arg.starts_with("--report=") {
+                report_path = Some(arg.split_off("--report=".len()));
+            } else if arg.starts_with("--jobs=") {
+                jobs = Some(
+                    arg.split_off("--jobs=".len())
+                        .parse()
+                        .expect("Argument `jobs` must be a positive number"),
+                );
             } else if arg.starts_with("--cargo_manifest_args=") {
                 cargo_manifest_maker = Some(RunfilesMaker::from_param_file(
                     &arg.split_off("--cargo_manifest_args=".len()),
@@ -353,12 +495,20 @@ impl Args {
             compile_flags_file: compile_flags_file.unwrap(),
             link_flags_file: link_flags_file.unwrap(),
             link_search_paths_file: link_search_paths_file.unwrap(),
+            bin_link_flags_file: bin_link_flags_file.unwrap(),
+            cdylib_link_flags_file: cdylib_link_flags_file.unwrap(),
             output_dep_env_path: output_dep_env_path.unwrap(),
             stdout_path,
             stderr_path,
             rundir: rundir.unwrap(),
             input_dep_env_paths,
             cargo_manifest_maker,
+            output_override,
+            audit_report_path,
+            audit_inputs_path,
+            audit_fail,
+            jobs,
+            report_path,
         }
     }
 }
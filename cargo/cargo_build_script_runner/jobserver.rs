@@ -0,0 +1,121 @@
+//! A GNU make jobserver for build scripts.
+//!
+//! Build scripts, typically through the `cc` crate, limit the jobs they run in
+//! parallel by taking tokens from the jobserver cargo describes in
+//! `CARGO_MAKEFLAGS`. The jobserver created here is a named fifo which, unlike
+//! an anonymous pipe, doesn't rely on file descriptors being inherited.
+
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::PathBuf;
+
+/// A jobserver allowing `jobs` jobs to run in parallel, removed when dropped.
+#[derive(Debug)]
+pub struct Jobserver {
+    fifo: PathBuf,
+    // Kept open so the tokens aren't lost when no client has the fifo open.
+    file: std::fs::File,
+}
+
+fn mkfifo(path: &std::path::Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int};
+    use std::os::unix::ffi::OsStrExt;
+
+    #[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd"))]
+    type Mode = u16;
+    #[cfg(not(any(target_os = "macos", target_os = "ios", target_os = "freebsd")))]
+    type Mode = u32;
+
+    extern "C" {
+        fn mkfifo(path: *const c_char, mode: Mode) -> c_int;
+    }
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: `path` is a valid nul terminated string.
+    if unsafe { mkfifo(path.as_ptr(), 0o600) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+impl Jobserver {
+    /// Creates a jobserver for `jobs` parallel jobs in the temporary directory.
+    pub fn new(jobs: NonZeroUsize) -> io::Result<Self> {
+        use std::io::Write;
+        use std::time::{SystemTime, UNIX_EPOCH};
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let fifo = std::env::temp_dir().join(format!(
+            "rules_rust_jobserver_{}_{}",
+            std::process::id(),
+            nanos
+        ));
+        mkfifo(&fifo)?;
+        let file = match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&fifo)
+        {
+            Ok(file) => file,
+            Err(e) => {
+                let _ = std::fs::remove_file(&fifo);
+                return Err(e);
+            }
+        };
+        let mut jobserver = Jobserver { fifo, file };
+        // The build script holds an implicit token, so it may run one job
+        // without taking any.
+        let tokens = vec![b'+'; jobs.get() - 1];
+        jobserver.file.write_all(&tokens)?;
+        Ok(jobserver)
+    }
+
+    /// The environment variables describing the jobserver to build scripts.
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        vec![(
+            "CARGO_MAKEFLAGS",
+            format!("-j --jobserver-auth=fifo:{}", self.fifo.display()),
+        )]
+    }
+}
+
+impl Drop for Jobserver {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.fifo);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Read;
+
+    #[test]
+    fn test_jobserver() {
+        let jobserver = Jobserver::new(NonZeroUsize::new(4).unwrap()).unwrap();
+        let env = jobserver.env();
+        assert_eq!(env[0].0, "CARGO_MAKEFLAGS");
+        let fifo = env[0]
+            .1
+            .strip_prefix("-j --jobserver-auth=fifo:")
+            .map(PathBuf::from)
+            .unwrap();
+
+        // Clients open the fifo themselves and find a token for each job but
+        // their own.
+        let mut client = std::fs::File::open(&fifo).unwrap();
+        let mut tokens = [0; 3];
+        client.read_exact(&mut tokens).unwrap();
+        assert_eq!(&tokens, b"+++");
+
+        drop(jobserver);
+        assert!(!fifo.exists());
+    }
+}
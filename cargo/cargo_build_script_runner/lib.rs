@@ -15,17 +15,33 @@
 //! Parse the output of a cargo build.rs script and generate a list of flags and
 //! environment variable for the build.
 use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
 use std::process::{Command, Output};
 
+pub mod audit;
 pub mod cargo_manifest_dir;
+#[cfg(unix)]
+pub mod jobserver;
+pub mod report;
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct CompileAndLinkFlags {
     pub compile_flags: String,
     pub link_flags: String,
     pub link_search_paths: String,
+    /// Linker flags for binaries, as `BIN=FLAG` lines where `BIN` is `*` for
+    /// all binaries.
+    pub bin_link_flags: String,
+    /// Linker flags for cdylib crates.
+    pub cdylib_link_flags: String,
 }
 
+/// The `--check-cfg` flags cargo passes for every crate, added whenever a build
+/// script declares its own cfgs as passing any `--check-cfg` enables checking.
+/// Crate features aren't known to the build script runner, so any feature is
+/// accepted.
+const DEFAULT_CHECK_CFGS: [&str; 3] = ["cfg(docsrs)", "cfg(feature, values(any()))", "cfg(test)"];
+
 /// Enum containing all the considered return value from the script
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BuildScriptOutput {
@@ -39,12 +55,54 @@ pub enum BuildScriptOutput {
     Flags(String),
     /// cargo::rustc-link-arg
     LinkArg(String),
+    /// cargo::rustc-link-arg-bin, the name of the binary and the flag
+    LinkArgBin(String, String),
+    /// cargo::rustc-link-arg-bins
+    LinkArgBins(String),
+    /// cargo::rustc-cdylib-link-arg
+    CdylibLinkArg(String),
+    /// cargo::rustc-check-cfg
+    CheckCfg(String),
     /// cargo::rustc-env
     Env(String),
-    /// cargo::VAR=VALUE
+    /// cargo::metadata=VAR=VALUE, or cargo:VAR=VALUE in the legacy syntax
     DepEnv(String),
+    /// cargo::error
+    Error(String),
+    /// cargo::rerun-if-changed, only used to audit the build script
+    RerunIfChanged(String),
+    /// cargo::rerun-if-env-changed, only used to audit the build script
+    RerunIfEnvChanged(String),
+    /// cargo::warning
+    Warning(String),
+    /// A directive cargo supports but Bazel doesn't, as printed by the script
+    Unsupported(String),
+    /// A directive cargo would reject, with a description of the problem
+    Invalid(String),
 }
 
+/// The keys of `cargo:KEY=VALUE` lines which are instructions rather than
+/// metadata in the legacy syntax.
+const LEGACY_INSTRUCTIONS: [&str; 17] = [
+    "rustc-flags",
+    "rustc-link-lib",
+    "rustc-link-search",
+    "rustc-link-arg-cdylib",
+    "rustc-cdylib-link-arg",
+    "rustc-link-arg-bins",
+    "rustc-link-arg-bin",
+    "rustc-link-arg-tests",
+    "rustc-link-arg-benches",
+    "rustc-link-arg-examples",
+    "rustc-link-arg",
+    "rustc-cfg",
+    "rustc-check-cfg",
+    "rustc-env",
+    "warning",
+    "rerun-if-changed",
+    "rerun-if-env-changed",
+];
+
 impl BuildScriptOutput {
     /// Converts a line into a [BuildScriptOutput] enum.
     ///
@@ -53,60 +111,101 @@ impl BuildScriptOutput {
     /// assert_eq!(BuildScriptOutput::new("cargo::rustc-link-lib=lib"), Some(BuildScriptOutput::LinkLib("lib".to_owned())));
     /// ```
     fn new(line: &str) -> Option<BuildScriptOutput> {
-        let split = line.splitn(2, '=').collect::<Vec<_>>();
-        if split.len() <= 1 {
+        let (directive, new_syntax) = if let Some(directive) = line.strip_prefix("cargo::") {
+            (directive, true)
+        } else if let Some(directive) = line.strip_prefix("cargo:") {
+            (directive, false)
+        } else {
             // Not a cargo directive.
             return None;
-        }
-        let param = split[1].trim().to_owned();
-        let cargo_instruction_name = {
-            if split[0].starts_with("cargo::") {
-                &split[0][7..]
-            } else if split[0].starts_with("cargo:") {
-                &split[0][6..]
-            } else {
-                // Not a cargo directive.
-                return None;
-            }
         };
+        let Some((key, value)) = directive.split_once('=') else {
+            // Lines without a value are ignored by the legacy syntax.
+            return new_syntax.then(|| {
+                BuildScriptOutput::Invalid(format!(
+                    "expected a line with `cargo::KEY=VALUE`, got `{}`",
+                    line.trim_end()
+                ))
+            });
+        };
+        let key = key.trim();
+        let param = value.trim().to_owned();
+
+        // cargo:KEY=VALUE — Metadata, used by links scripts. The legacy syntax
+        // only reserves the keys which were instructions at the time.
+        if !new_syntax && !LEGACY_INSTRUCTIONS.contains(&key) {
+            return Some(BuildScriptOutput::dep_env(key, &param));
+        }
 
-        match cargo_instruction_name {
+        match key {
             "rustc-link-lib" => Some(BuildScriptOutput::LinkLib(param)),
             "rustc-link-search" => Some(BuildScriptOutput::LinkSearch(param)),
             "rustc-cfg" => Some(BuildScriptOutput::Cfg(param)),
             "rustc-flags" => Some(BuildScriptOutput::Flags(param)),
             "rustc-link-arg" => Some(BuildScriptOutput::LinkArg(param)),
-            "rustc-env" => Some(BuildScriptOutput::Env(param)),
-            "rerun-if-changed" | "rerun-if-env-changed" =>
-            // Ignored because Bazel will re-run if those change all the time.
-            {
-                None
+            "rustc-link-arg-bin" => match param.split_once('=') {
+                Some((bin, arg)) => Some(BuildScriptOutput::LinkArgBin(
+                    bin.to_owned(),
+                    arg.to_owned(),
+                )),
+                None => Some(BuildScriptOutput::Invalid(format!(
+                    "expected `{key}=BIN=FLAG`, got `{}`",
+                    line.trim_end()
+                ))),
+            },
+            "rustc-link-arg-bins" => Some(BuildScriptOutput::LinkArgBins(param)),
+            "rustc-cdylib-link-arg" | "rustc-link-arg-cdylib" => {
+                Some(BuildScriptOutput::CdylibLinkArg(param))
             }
-            "warning" => {
-                eprint!("Build Script Warning: {}", split[1]);
-                None
-            }
-            "rustc-cdylib-link-arg" | "rustc-link-arg-bin" | "rustc-link-arg-bins" => {
-                // cargo::rustc-cdylib-link-arg=FLAG — Passes custom flags to a linker for cdylib crates.
-                // cargo::rustc-link-arg-bin=BIN=FLAG – Passes custom flags to a linker for the binary BIN.
-                // cargo::rustc-link-arg-bins=FLAG – Passes custom flags to a linker for binaries.
-                eprint!(
-                    "Warning: build script returned unsupported directive `{}`",
-                    split[0]
-                );
-                None
-            }
-            _ => {
-                // cargo::KEY=VALUE — Metadata, used by links scripts.
-                Some(BuildScriptOutput::DepEnv(format!(
-                    "{}={}",
-                    cargo_instruction_name.to_uppercase().replace('-', "_"),
-                    param
-                )))
+            "rustc-check-cfg" => Some(BuildScriptOutput::CheckCfg(param)),
+            "rustc-env" => Some(BuildScriptOutput::Env(param)),
+            // Bazel re-runs the script whenever its inputs change, these are only
+            // kept to check that they are declared.
+            "rerun-if-changed" => Some(BuildScriptOutput::RerunIfChanged(param)),
+            "rerun-if-env-changed" => Some(BuildScriptOutput::RerunIfEnvChanged(param)),
+            "rustc-link-arg-tests" | "rustc-link-arg-examples" | "rustc-link-arg-benches" => {
+                // Bazel doesn't build examples or benches from the crate's build script, and
+                // tests are regular `rust_test` targets which don't match cargo's test targets.
+                Some(BuildScriptOutput::Unsupported(line.trim_end().to_owned()))
             }
+            "warning" => Some(BuildScriptOutput::Warning(param)),
+            "error" => Some(BuildScriptOutput::Error(param)),
+            // cargo::metadata=KEY=VALUE — Metadata, used by links scripts.
+            "metadata" => match param.split_once('=') {
+                Some((key, value)) => Some(BuildScriptOutput::dep_env(key.trim(), value.trim())),
+                None => Some(BuildScriptOutput::Invalid(format!(
+                    "expected `cargo::metadata=KEY=VALUE`, got `{}`",
+                    line.trim_end()
+                ))),
+            },
+            _ => Some(BuildScriptOutput::Invalid(format!(
+                "unknown key `{key}` in `{}`; metadata must use `cargo::metadata=KEY=VALUE`",
+                line.trim_end()
+            ))),
         }
     }
 
+    fn dep_env(key: &str, value: &str) -> BuildScriptOutput {
+        BuildScriptOutput::DepEnv(format!(
+            "{}={}",
+            key.to_uppercase().replace('-', "_"),
+            value
+        ))
+    }
+
+    /// Returns the errors reported by the build script, or caused by invalid
+    /// directives, which should fail the build.
+    pub fn errors(outputs: &[BuildScriptOutput]) -> Vec<String> {
+        outputs
+            .iter()
+            .filter_map(|output| match output {
+                BuildScriptOutput::Error(e) => Some(e.to_owned()),
+                BuildScriptOutput::Invalid(e) => Some(format!("invalid build script output: {e}")),
+                _ => None,
+            })
+            .collect()
+    }
+
     /// Converts a [BufReader] into a vector of [BuildScriptOutput] enums.
     fn outputs_from_reader<T: Read>(mut reader: BufReader<T>) -> Vec<BuildScriptOutput> {
         let mut result = Vec::<BuildScriptOutput>::new();
@@ -128,21 +227,38 @@ impl BuildScriptOutput {
     }
 
     /// Take a [Command], execute it and converts its input into a vector of [BuildScriptOutput]
+    ///
+    /// The output is parsed even if the command fails, as it may explain why.
+    #[allow(clippy::type_complexity)]
     pub fn outputs_from_command(
         cmd: &mut Command,
-    ) -> Result<(Vec<BuildScriptOutput>, Output), Output> {
+    ) -> Result<(Vec<BuildScriptOutput>, Output), (Vec<BuildScriptOutput>, Output)> {
         let child_output = cmd
             .output()
             .unwrap_or_else(|e| panic!("Unable to start command:\n{:#?}\n{:?}", cmd, e));
+        let reader = BufReader::new(child_output.stdout.as_slice());
+        let output = Self::outputs_from_reader(reader);
         if child_output.status.success() {
-            let reader = BufReader::new(child_output.stdout.as_slice());
-            let output = Self::outputs_from_reader(reader);
             Ok((output, child_output))
         } else {
-            Err(child_output)
+            Err((output, child_output))
         }
     }
 
+    /// Read the [BuildScriptOutput]s of a file holding `cargo::` directives, used in
+    /// place of running a build script. `${pwd}` is replaced with `exec_root`.
+    pub fn outputs_from_override(
+        path: &Path,
+        exec_root: &str,
+    ) -> Result<Vec<BuildScriptOutput>, String> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format!("Unable to read output override {path:?}: {e}"))?;
+        let contents = contents.replace("${pwd}", exec_root);
+        Ok(Self::outputs_from_reader(BufReader::new(
+            contents.as_bytes(),
+        )))
+    }
+
     /// Convert a vector of [BuildScriptOutput] into a list of environment variables.
     pub fn outputs_to_env(outputs: &[BuildScriptOutput], exec_root: &str) -> String {
         outputs
@@ -189,6 +305,8 @@ impl BuildScriptOutput {
         let mut compile_flags = Vec::new();
         let mut link_flags = Vec::new();
         let mut link_search_paths = Vec::new();
+        let mut bin_link_flags = Vec::new();
+        let mut cdylib_link_flags = Vec::new();
 
         for flag in outputs {
             match flag {
@@ -197,14 +315,36 @@ impl BuildScriptOutput {
                 BuildScriptOutput::LinkArg(e) => compile_flags.push(format!("-Clink-arg={e}")),
                 BuildScriptOutput::LinkLib(e) => link_flags.push(format!("-l{e}")),
                 BuildScriptOutput::LinkSearch(e) => link_search_paths.push(format!("-L{e}")),
+                BuildScriptOutput::LinkArgBin(bin, e) => {
+                    bin_link_flags.push(format!("{bin}=-Clink-arg={e}"))
+                }
+                BuildScriptOutput::LinkArgBins(e) => {
+                    bin_link_flags.push(format!("*=-Clink-arg={e}"))
+                }
+                BuildScriptOutput::CdylibLinkArg(e) => {
+                    cdylib_link_flags.push(format!("-Clink-arg={e}"))
+                }
+                BuildScriptOutput::CheckCfg(e) => compile_flags.push(format!("--check-cfg={e}")),
                 _ => {}
             }
         }
+        if outputs
+            .iter()
+            .any(|output| matches!(output, BuildScriptOutput::CheckCfg(_)))
+        {
+            compile_flags.extend(
+                DEFAULT_CHECK_CFGS
+                    .iter()
+                    .map(|c| format!("--check-cfg={c}")),
+            );
+        }
 
         CompileAndLinkFlags {
             compile_flags: compile_flags.join("\n"),
             link_flags: Self::redact_exec_root(&link_flags.join("\n"), exec_root),
             link_search_paths: Self::redact_exec_root(&link_search_paths.join("\n"), exec_root),
+            bin_link_flags: bin_link_flags.join("\n"),
+            cdylib_link_flags: cdylib_link_flags.join("\n"),
         }
     }
 
@@ -234,7 +374,7 @@ mod tests {
     fn from_read_buffer_to_env_and_flags_test_impl(buff: Cursor<&str>) {
         let reader = BufReader::new(buff);
         let result = BuildScriptOutput::outputs_from_reader(reader);
-        assert_eq!(result.len(), 13);
+        assert_eq!(result.len(), 14);
         assert_eq!(result[0], BuildScriptOutput::LinkLib("sdfsdf".to_owned()));
         assert_eq!(result[1], BuildScriptOutput::Env("FOO=BAR".to_owned()));
         assert_eq!(
@@ -245,27 +385,31 @@ mod tests {
         assert_eq!(result[4], BuildScriptOutput::Flags("-Lblah".to_owned()));
         assert_eq!(
             result[5],
-            BuildScriptOutput::Cfg("feature=awesome".to_owned())
+            BuildScriptOutput::RerunIfChanged("build.rs".to_owned())
         );
         assert_eq!(
             result[6],
-            BuildScriptOutput::DepEnv("VERSION=123".to_owned())
+            BuildScriptOutput::Cfg("feature=awesome".to_owned())
         );
         assert_eq!(
             result[7],
+            BuildScriptOutput::DepEnv("VERSION=123".to_owned())
+        );
+        assert_eq!(
+            result[8],
             BuildScriptOutput::DepEnv("VERSION_NUMBER=1010107f".to_owned())
         );
         assert_eq!(
-            result[9],
+            result[10],
             BuildScriptOutput::Env("SOME_PATH=/some/absolute/path/beep".to_owned())
         );
         assert_eq!(
-            result[10],
+            result[11],
             BuildScriptOutput::LinkArg("-weak_framework".to_owned())
         );
-        assert_eq!(result[11], BuildScriptOutput::LinkArg("Metal".to_owned()));
+        assert_eq!(result[12], BuildScriptOutput::LinkArg("Metal".to_owned()));
         assert_eq!(
-            result[12],
+            result[13],
             BuildScriptOutput::Env("no_trailing_newline=true".to_owned())
         );
         assert_eq!(
@@ -286,6 +430,8 @@ mod tests {
                         .to_owned(),
                 link_flags: "-lsdfsdf".to_owned(),
                 link_search_paths: "-L${pwd}/bleh".to_owned(),
+                bin_link_flags: String::new(),
+                cdylib_link_flags: String::new(),
             }
         );
     }
@@ -299,11 +445,11 @@ cargo::rustc-env=FOO=BAR
 cargo::rustc-link-search=/some/absolute/path/bleh
 cargo::rustc-env=BAR=FOO
 cargo::rustc-flags=-Lblah
-cargo::rerun-if-changed=ignored
+cargo::rerun-if-changed=build.rs
 cargo::rustc-cfg=feature=awesome
-cargo::version=123
-cargo::version_number=1010107f
-cargo::include_path=/some/absolute/path/include
+cargo::metadata=version=123
+cargo::metadata=version_number=1010107f
+cargo::metadata=include_path=/some/absolute/path/include
 cargo::rustc-env=SOME_PATH=/some/absolute/path/beep
 cargo::rustc-link-arg=-weak_framework
 cargo::rustc-link-arg=Metal
@@ -324,7 +470,7 @@ cargo:rustc-env=FOO=BAR
 cargo:rustc-link-search=/some/absolute/path/bleh
 cargo:rustc-env=BAR=FOO
 cargo:rustc-flags=-Lblah
-cargo:rerun-if-changed=ignored
+cargo:rerun-if-changed=build.rs
 cargo:rustc-cfg=feature=awesome
 cargo:version=123
 cargo:version_number=1010107f
@@ -339,6 +485,259 @@ non-assignment-instructions-are-ignored",
         from_read_buffer_to_env_and_flags_test_impl(buff);
     }
 
+    #[test]
+    fn test_link_args_and_check_cfg() {
+        let buff = Cursor::new(
+            "
+cargo::rustc-link-arg-bin=my-tool=-Wl,--stack,8000000
+cargo::rustc-link-arg-bins=-Wl,-z,relro
+cargo::rustc-cdylib-link-arg=-Wl,-soname,libfoo.so
+cargo:rustc-link-arg-bin=missing-flag
+cargo::rustc-check-cfg=cfg(has_foo)
+cargo:rustc-check-cfg=cfg(foo_version, values(\"1\", \"2\"))
+cargo::rustc-cfg=has_foo",
+        );
+        let reader = BufReader::new(buff);
+        let result = BuildScriptOutput::outputs_from_reader(reader);
+        assert_eq!(
+            result,
+            vec![
+                BuildScriptOutput::LinkArgBin(
+                    "my-tool".to_owned(),
+                    "-Wl,--stack,8000000".to_owned()
+                ),
+                BuildScriptOutput::LinkArgBins("-Wl,-z,relro".to_owned()),
+                BuildScriptOutput::CdylibLinkArg("-Wl,-soname,libfoo.so".to_owned()),
+                BuildScriptOutput::Invalid(
+                    "expected `rustc-link-arg-bin=BIN=FLAG`, got `cargo:rustc-link-arg-bin=missing-flag`"
+                        .to_owned()
+                ),
+                BuildScriptOutput::CheckCfg("cfg(has_foo)".to_owned()),
+                BuildScriptOutput::CheckCfg("cfg(foo_version, values(\"1\", \"2\"))".to_owned()),
+                BuildScriptOutput::Cfg("has_foo".to_owned()),
+            ]
+        );
+        assert_eq!(
+            BuildScriptOutput::outputs_to_dep_env(&result, "foo", "/some/absolute/path"),
+            ""
+        );
+        assert_eq!(
+            BuildScriptOutput::outputs_to_flags(&result, "/some/absolute/path"),
+            CompileAndLinkFlags {
+                compile_flags: "--check-cfg=cfg(has_foo)\n\
+                    --check-cfg=cfg(foo_version, values(\"1\", \"2\"))\n\
+                    --cfg=has_foo\n\
+                    --check-cfg=cfg(docsrs)\n\
+                    --check-cfg=cfg(feature, values(any()))\n\
+                    --check-cfg=cfg(test)"
+                    .to_owned(),
+                link_flags: String::new(),
+                link_search_paths: String::new(),
+                bin_link_flags: "my-tool=-Clink-arg=-Wl,--stack,8000000\n\
+                    *=-Clink-arg=-Wl,-z,relro"
+                    .to_owned(),
+                cdylib_link_flags: "-Clink-arg=-Wl,-soname,libfoo.so".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_directive_corpus() {
+        use BuildScriptOutput::*;
+
+        let corpus: &[(&str, Option<BuildScriptOutput>)] = &[
+            // Instructions are the same in both syntaxes.
+            (
+                "cargo::rustc-link-lib=static=foo",
+                Some(LinkLib("static=foo".to_owned())),
+            ),
+            (
+                "cargo:rustc-link-lib=static=foo",
+                Some(LinkLib("static=foo".to_owned())),
+            ),
+            (
+                "cargo::rustc-link-search=native=/lib",
+                Some(LinkSearch("native=/lib".to_owned())),
+            ),
+            (
+                "cargo:rustc-link-search=native=/lib",
+                Some(LinkSearch("native=/lib".to_owned())),
+            ),
+            (
+                "cargo::rustc-flags=-l foo",
+                Some(Flags("-l foo".to_owned())),
+            ),
+            ("cargo:rustc-flags=-l foo", Some(Flags("-l foo".to_owned()))),
+            ("cargo::rustc-cfg=foo", Some(Cfg("foo".to_owned()))),
+            ("cargo:rustc-cfg=foo", Some(Cfg("foo".to_owned()))),
+            (
+                "cargo::rustc-check-cfg=cfg(foo)",
+                Some(CheckCfg("cfg(foo)".to_owned())),
+            ),
+            (
+                "cargo:rustc-check-cfg=cfg(foo)",
+                Some(CheckCfg("cfg(foo)".to_owned())),
+            ),
+            ("cargo::rustc-env=FOO=bar", Some(Env("FOO=bar".to_owned()))),
+            ("cargo:rustc-env=FOO=bar", Some(Env("FOO=bar".to_owned()))),
+            ("cargo::rustc-link-arg=-g", Some(LinkArg("-g".to_owned()))),
+            ("cargo:rustc-link-arg=-g", Some(LinkArg("-g".to_owned()))),
+            (
+                "cargo::rustc-link-arg-bins=-g",
+                Some(LinkArgBins("-g".to_owned())),
+            ),
+            (
+                "cargo:rustc-link-arg-bins=-g",
+                Some(LinkArgBins("-g".to_owned())),
+            ),
+            (
+                "cargo::rustc-link-arg-bin=foo=-g",
+                Some(LinkArgBin("foo".to_owned(), "-g".to_owned())),
+            ),
+            (
+                "cargo:rustc-link-arg-bin=foo=-g",
+                Some(LinkArgBin("foo".to_owned(), "-g".to_owned())),
+            ),
+            (
+                "cargo::rustc-cdylib-link-arg=-g",
+                Some(CdylibLinkArg("-g".to_owned())),
+            ),
+            (
+                "cargo:rustc-cdylib-link-arg=-g",
+                Some(CdylibLinkArg("-g".to_owned())),
+            ),
+            (
+                "cargo::rustc-link-arg-cdylib=-g",
+                Some(CdylibLinkArg("-g".to_owned())),
+            ),
+            (
+                "cargo:rustc-link-arg-cdylib=-g",
+                Some(CdylibLinkArg("-g".to_owned())),
+            ),
+            (
+                "cargo::rustc-link-arg-tests=-g",
+                Some(Unsupported("cargo::rustc-link-arg-tests=-g".to_owned())),
+            ),
+            (
+                "cargo:rustc-link-arg-benches=-g",
+                Some(Unsupported("cargo:rustc-link-arg-benches=-g".to_owned())),
+            ),
+            (
+                "cargo::rerun-if-changed=build.rs",
+                Some(RerunIfChanged("build.rs".to_owned())),
+            ),
+            (
+                "cargo:rerun-if-env-changed=FOO",
+                Some(RerunIfEnvChanged("FOO".to_owned())),
+            ),
+            (
+                "cargo::warning=careful",
+                Some(Warning("careful".to_owned())),
+            ),
+            ("cargo:warning=careful", Some(Warning("careful".to_owned()))),
+            // Keys and values are trimmed.
+            ("cargo:: rustc-cfg = foo \n", Some(Cfg("foo".to_owned()))),
+            // Metadata.
+            (
+                "cargo::metadata=include=/inc",
+                Some(DepEnv("INCLUDE=/inc".to_owned())),
+            ),
+            (
+                "cargo::metadata=root-dir=a=b",
+                Some(DepEnv("ROOT_DIR=a=b".to_owned())),
+            ),
+            (
+                "cargo:include=/inc",
+                Some(DepEnv("INCLUDE=/inc".to_owned())),
+            ),
+            (
+                "cargo:root-dir=a=b",
+                Some(DepEnv("ROOT_DIR=a=b".to_owned())),
+            ),
+            // `metadata` and `error` aren't instructions in the legacy syntax.
+            (
+                "cargo:metadata=include=/inc",
+                Some(DepEnv("METADATA=include=/inc".to_owned())),
+            ),
+            ("cargo:error=oops", Some(DepEnv("ERROR=oops".to_owned()))),
+            // Errors.
+            ("cargo::error=oops", Some(Error("oops".to_owned()))),
+            (
+                "cargo::include=/inc",
+                Some(Invalid(
+                    "unknown key `include` in `cargo::include=/inc`; metadata must use \
+                    `cargo::metadata=KEY=VALUE`"
+                        .to_owned(),
+                )),
+            ),
+            (
+                "cargo::metadata=include",
+                Some(Invalid(
+                    "expected `cargo::metadata=KEY=VALUE`, got `cargo::metadata=include`"
+                        .to_owned(),
+                )),
+            ),
+            (
+                "cargo::rustc-cfg",
+                Some(Invalid(
+                    "expected a line with `cargo::KEY=VALUE`, got `cargo::rustc-cfg`".to_owned(),
+                )),
+            ),
+            // Other lines are ignored.
+            ("cargo:rustc-cfg", None),
+            ("cargo-rustc-cfg=foo", None),
+            ("rustc-cfg=foo", None),
+            ("", None),
+        ];
+        for (line, expected) in corpus {
+            assert_eq!(&BuildScriptOutput::new(line), expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        let outputs = BuildScriptOutput::outputs_from_reader(BufReader::new(Cursor::new(
+            "cargo::rustc-cfg=foo\ncargo::error=first\ncargo::bogus=x\ncargo::error=second\n",
+        )));
+        assert_eq!(
+            BuildScriptOutput::errors(&outputs),
+            vec![
+                "first".to_owned(),
+                "invalid build script output: unknown key `bogus` in `cargo::bogus=x`; \
+                metadata must use `cargo::metadata=KEY=VALUE`"
+                    .to_owned(),
+                "second".to_owned(),
+            ]
+        );
+        assert!(BuildScriptOutput::errors(&outputs[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_outputs_from_override() {
+        let dir = std::path::PathBuf::from(std::env::var("TEST_TMPDIR").unwrap());
+        let path = dir.join("override.txt");
+        std::fs::write(
+            &path,
+            "cargo::rustc-link-search=native=${pwd}/vendor/lib\n\
+             cargo::rustc-link-lib=static=foo\n\
+             cargo::rustc-cfg=has_foo\n\
+             cargo::metadata=include=${pwd}/vendor/include\n",
+        )
+        .unwrap();
+        let outputs =
+            BuildScriptOutput::outputs_from_override(&path, "/some/absolute/path").unwrap();
+        assert_eq!(
+            outputs,
+            vec![
+                BuildScriptOutput::LinkSearch("native=/some/absolute/path/vendor/lib".to_owned()),
+                BuildScriptOutput::LinkLib("static=foo".to_owned()),
+                BuildScriptOutput::Cfg("has_foo".to_owned()),
+                BuildScriptOutput::DepEnv("INCLUDE=/some/absolute/path/vendor/include".to_owned()),
+            ]
+        );
+        assert!(BuildScriptOutput::outputs_from_override(&dir.join("missing.txt"), "/").is_err());
+    }
+
     #[test]
     fn invalid_utf8() {
         let buff = Cursor::new(
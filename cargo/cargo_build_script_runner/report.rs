@@ -0,0 +1,327 @@
+//! Structured reports of build script runs.
+//!
+//! The report is a json file describing how the build script exited, the
+//! directives it printed and the end of its logs. Failures are summarized in a
+//! few lines which point to it, rather than dumping the script's output.
+
+use std::process::ExitStatus;
+
+use crate::BuildScriptOutput;
+
+/// The number of bytes kept from the end of the build script's stdout and stderr.
+const LOG_TAIL_BYTES: usize = 64 * 1024;
+
+/// The number of lines of stderr shown in the summary of a failure.
+const SUMMARY_TAIL_LINES: usize = 20;
+
+/// The run of a build script.
+pub struct Report<'a> {
+    /// The name of the package the build script belongs to.
+    pub package: &'a str,
+    /// The path of the build script.
+    pub script: &'a str,
+    /// How the script exited, `None` if it wasn't run as its output was
+    /// overridden.
+    pub status: Option<ExitStatus>,
+    pub outputs: &'a [BuildScriptOutput],
+    pub stdout: &'a [u8],
+    pub stderr: &'a [u8],
+}
+
+/// Quotes and escapes `value` as a json string.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+fn json_array<I: IntoIterator<Item = String>>(values: I) -> String {
+    format!("[{}]", values.into_iter().collect::<Vec<_>>().join(","))
+}
+
+/// Returns the end of `log`, at most `max_bytes` long, and whether it was truncated.
+fn tail(log: &[u8], max_bytes: usize) -> (String, bool) {
+    let start = log.len().saturating_sub(max_bytes);
+    (
+        String::from_utf8_lossy(&log[start..]).into_owned(),
+        start > 0,
+    )
+}
+
+/// Returns the key of the directive `output` was parsed from and its value,
+/// or `None` for outputs which aren't directives applied to the build.
+fn directive(output: &BuildScriptOutput) -> Option<(&'static str, String)> {
+    Some(match output {
+        BuildScriptOutput::LinkLib(value) => ("rustc-link-lib", value.clone()),
+        BuildScriptOutput::LinkSearch(value) => ("rustc-link-search", value.clone()),
+        BuildScriptOutput::Cfg(value) => ("rustc-cfg", value.clone()),
+        BuildScriptOutput::Flags(value) => ("rustc-flags", value.clone()),
+        BuildScriptOutput::LinkArg(value) => ("rustc-link-arg", value.clone()),
+        BuildScriptOutput::LinkArgBin(bin, arg) => ("rustc-link-arg-bin", format!("{bin}={arg}")),
+        BuildScriptOutput::LinkArgBins(value) => ("rustc-link-arg-bins", value.clone()),
+        BuildScriptOutput::CdylibLinkArg(value) => ("rustc-cdylib-link-arg", value.clone()),
+        BuildScriptOutput::CheckCfg(value) => ("rustc-check-cfg", value.clone()),
+        BuildScriptOutput::Env(value) => ("rustc-env", value.clone()),
+        BuildScriptOutput::DepEnv(value) => ("metadata", value.clone()),
+        _ => return None,
+    })
+}
+
+/// Returns the key and value of directives which Bazel has no use for.
+fn ignored_directive(output: &BuildScriptOutput) -> Option<(&'static str, String)> {
+    match output {
+        BuildScriptOutput::RerunIfChanged(value) => Some(("rerun-if-changed", value.clone())),
+        BuildScriptOutput::RerunIfEnvChanged(value) => {
+            Some(("rerun-if-env-changed", value.clone()))
+        }
+        _ => None,
+    }
+}
+
+fn json_directive((key, value): (&str, String)) -> String {
+    format!(
+        "{{\"key\":{},\"value\":{}}}",
+        json_string(key),
+        json_string(&value)
+    )
+}
+
+impl Report<'_> {
+    /// Whether the build script failed, either by exiting unsuccessfully or
+    /// by reporting errors.
+    pub fn failed(&self) -> bool {
+        self.status.is_some_and(|status| !status.success())
+            || !BuildScriptOutput::errors(self.outputs).is_empty()
+    }
+
+    /// The warnings printed by the build script.
+    pub fn warnings(&self) -> Vec<&str> {
+        self.outputs
+            .iter()
+            .filter_map(|output| match output {
+                BuildScriptOutput::Warning(warning) => Some(warning.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Serializes the report to json.
+    pub fn to_json(&self) -> String {
+        let status = match self.status {
+            None => "skipped",
+            Some(_) if self.failed() => "failure",
+            Some(_) => "success",
+        };
+        let exit_code = self
+            .status
+            .and_then(|status| status.code())
+            .map_or_else(|| "null".to_owned(), |code| code.to_string());
+        let log = |log: &[u8]| {
+            let (text, truncated) = tail(log, LOG_TAIL_BYTES);
+            format!(
+                "{{\"text\":{},\"truncated\":{truncated}}}",
+                json_string(&text)
+            )
+        };
+        let fields = [
+            ("package", json_string(self.package)),
+            ("script", json_string(self.script)),
+            ("status", json_string(status)),
+            ("exit_code", exit_code),
+            (
+                "directives",
+                json_array(
+                    self.outputs
+                        .iter()
+                        .filter_map(directive)
+                        .map(json_directive),
+                ),
+            ),
+            (
+                "ignored",
+                json_array(
+                    self.outputs
+                        .iter()
+                        .filter_map(ignored_directive)
+                        .map(json_directive),
+                ),
+            ),
+            (
+                "unsupported",
+                json_array(self.outputs.iter().filter_map(|output| match output {
+                    BuildScriptOutput::Unsupported(line) => Some(json_string(line)),
+                    _ => None,
+                })),
+            ),
+            (
+                "warnings",
+                json_array(self.warnings().into_iter().map(json_string)),
+            ),
+            (
+                "errors",
+                json_array(
+                    BuildScriptOutput::errors(self.outputs)
+                        .iter()
+                        .map(|e| json_string(e)),
+                ),
+            ),
+            ("stdout", log(self.stdout)),
+            ("stderr", log(self.stderr)),
+        ];
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|(key, value)| format!("{}:{value}", json_string(key)))
+            .collect();
+        format!("{{{}}}\n", fields.join(","))
+    }
+
+    /// A short description of why the build script failed, pointing at the
+    /// full report at `report_path` if there is one.
+    pub fn failure_summary(&self, report_path: Option<&str>) -> String {
+        let mut summary = format!("Build script of `{}` failed", self.package);
+        match self.status {
+            Some(status) if !status.success() => match status.code() {
+                Some(code) => summary.push_str(&format!(" with exit code {code}")),
+                None => summary.push_str(&format!(" with {status}")),
+            },
+            _ => summary.push_str(" with errors"),
+        }
+        summary.push_str(&format!(" ({})", self.script));
+        for error in BuildScriptOutput::errors(self.outputs) {
+            summary.push_str(&format!("\nerror: {error}"));
+        }
+        let stderr = String::from_utf8_lossy(self.stderr);
+        let lines: Vec<_> = stderr.lines().collect();
+        if !lines.is_empty() {
+            let tail = &lines[lines.len().saturating_sub(SUMMARY_TAIL_LINES)..];
+            if tail.len() < lines.len() {
+                summary.push_str(&format!("\n--stderr (last {} lines):", tail.len()));
+            } else {
+                summary.push_str("\n--stderr:");
+            }
+            for line in tail {
+                summary.push_str(&format!("\n{line}"));
+            }
+        }
+        if let Some(report_path) = report_path {
+            summary.push_str(&format!("\nThe full report is at {report_path}"));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    fn exit_status(code: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(code << 8)
+    }
+
+    #[cfg(windows)]
+    fn exit_status(code: i32) -> ExitStatus {
+        use std::os::windows::process::ExitStatusExt;
+        ExitStatus::from_raw(code as u32)
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(
+            json_string("a \"quoted\"\\path\n\u{1b}"),
+            r#""a \"quoted\"\\path\n\u001b""#
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let outputs = vec![
+            BuildScriptOutput::Cfg("foo".to_owned()),
+            BuildScriptOutput::LinkArgBin("tool".to_owned(), "-g".to_owned()),
+            BuildScriptOutput::RerunIfChanged("build.rs".to_owned()),
+            BuildScriptOutput::Unsupported("cargo::rustc-link-arg-tests=-g".to_owned()),
+            BuildScriptOutput::Warning("careful".to_owned()),
+        ];
+        let report = Report {
+            package: "foo-sys",
+            script: "foo/build_script_",
+            status: Some(exit_status(0)),
+            outputs: &outputs,
+            stdout: b"cargo::rustc-cfg=foo\n",
+            stderr: b"",
+        };
+        assert!(!report.failed());
+        assert_eq!(
+            report.to_json(),
+            concat!(
+                r#"{"package":"foo-sys","script":"foo/build_script_","status":"success","exit_code":0,"#,
+                r#""directives":[{"key":"rustc-cfg","value":"foo"},{"key":"rustc-link-arg-bin","value":"tool=-g"}],"#,
+                r#""ignored":[{"key":"rerun-if-changed","value":"build.rs"}],"#,
+                r#""unsupported":["cargo::rustc-link-arg-tests=-g"],"warnings":["careful"],"errors":[],"#,
+                r#""stdout":{"text":"cargo::rustc-cfg=foo\n","truncated":false},"#,
+                r#""stderr":{"text":"","truncated":false}}"#,
+                "\n"
+            )
+        );
+
+        let report = Report {
+            status: None,
+            ..report
+        };
+        assert!(report
+            .to_json()
+            .contains(r#""status":"skipped","exit_code":null"#));
+    }
+
+    #[test]
+    fn test_failure_summary() {
+        let stderr: String = (1..=30).map(|i| format!("line {i}\n")).collect();
+        let outputs = vec![BuildScriptOutput::Error("missing libfoo".to_owned())];
+        let report = Report {
+            package: "foo-sys",
+            script: "foo/build_script_",
+            status: Some(exit_status(101)),
+            outputs: &outputs,
+            stdout: b"",
+            stderr: stderr.as_bytes(),
+        };
+        assert!(report.failed());
+        let summary = report.failure_summary(Some("foo/build_script.report.json"));
+        let lines: Vec<_> = summary.lines().collect();
+        assert_eq!(
+            lines[..3],
+            [
+                "Build script of `foo-sys` failed with exit code 101 (foo/build_script_)",
+                "error: missing libfoo",
+                "--stderr (last 20 lines):",
+            ]
+        );
+        assert_eq!(lines[3], "line 11");
+        assert_eq!(
+            lines.last(),
+            Some(&"The full report is at foo/build_script.report.json")
+        );
+        assert!(report
+            .to_json()
+            .contains(r#""status":"failure","exit_code":101"#));
+    }
+
+    #[test]
+    fn test_tail() {
+        assert_eq!(tail(b"short", 10), ("short".to_owned(), false));
+        assert_eq!(tail(b"0123456789", 4), ("6789".to_owned(), true));
+    }
+}
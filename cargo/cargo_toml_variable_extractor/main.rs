@@ -1,6 +1,7 @@
 use std::{
     borrow::Cow,
     env::args_os,
+    ffi::OsString,
     fs::{read_to_string, File},
     io::Write,
     path::{Path, PathBuf},
@@ -12,7 +13,7 @@ use cargo_util_schemas::manifest::{
 };
 
 fn main() {
-    let args: Vec<_> = args_os().collect();
+    let (args, target) = parse_target_args(args_os());
     let (out_path, manifest, workspace) = match &args[..] {
         [_, out_path, manifest_path] => (out_path, parse_manifest(manifest_path), None),
         [_, out_path, manifest_path, workspace_manifest_path] => (
@@ -39,7 +40,9 @@ fn main() {
                 |v| v.to_string_lossy(),
             );
             panic!(
-                "Usage: {} path/to/Cargo.toml [path/to/workspace/Cargo.toml]",
+                "Usage: {} [--target_kind=<kind> [--target_name=<name>] [--primary_package] \
+                 [--target_tmpdir=<path>] [--bin_exe=<name>=<path>]...] \
+                 path/to/Cargo.toml [path/to/workspace/Cargo.toml]",
                 argv0
             );
         }
@@ -47,6 +50,114 @@ fn main() {
 
     let mut out = File::create(out_path).expect("Failed to create output file");
     print_manifest_env_vars(&mut out, &manifest, workspace.as_ref());
+    print_target_env_vars(&mut out, &manifest, &target);
+}
+
+/// The kinds of Cargo targets which get target specific env vars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TargetKind {
+    Lib,
+    Bin,
+    Example,
+    Test,
+    Bench,
+}
+
+impl TargetKind {
+    fn parse(kind: &str) -> Self {
+        match kind {
+            "lib" | "proc-macro" => TargetKind::Lib,
+            "bin" => TargetKind::Bin,
+            "example" => TargetKind::Example,
+            "test" => TargetKind::Test,
+            "bench" => TargetKind::Bench,
+            other => panic!(
+                "Unknown target kind '{}', expected one of lib, proc-macro, bin, example, test or bench",
+                other
+            ),
+        }
+    }
+}
+
+/// The target env vars are printed for, in addition to the ones of its package.
+#[derive(Debug, Default)]
+struct Target {
+    /// The kind of target, no target specific env vars are printed without one.
+    kind: Option<TargetKind>,
+    /// The name of the target, defaulting to the name Cargo gives it.
+    name: Option<String>,
+    /// Whether the package is one the user is building directly, rather than a dependency.
+    primary_package: bool,
+    /// The directory integration tests and benchmarks can write temporary files to.
+    tmpdir: Option<String>,
+    /// The names and paths of the binaries of the package, for integration tests and benchmarks.
+    bin_exes: Vec<(String, String)>,
+}
+
+/// Splits the `--flag` arguments describing the target from the positional arguments.
+fn parse_target_args(args: impl Iterator<Item = OsString>) -> (Vec<OsString>, Target) {
+    let mut positional = Vec::new();
+    let mut target = Target::default();
+    for arg in args {
+        let Some(flag) = arg.to_str().and_then(|arg| arg.strip_prefix("--")) else {
+            positional.push(arg);
+            continue;
+        };
+        match flag.split_once('=') {
+            Some(("target_kind", kind)) => target.kind = Some(TargetKind::parse(kind)),
+            Some(("target_name", name)) => target.name = Some(name.to_owned()),
+            Some(("target_tmpdir", tmpdir)) => target.tmpdir = Some(tmpdir.to_owned()),
+            Some(("bin_exe", bin_exe)) => {
+                let (name, path) = bin_exe.split_once('=').unwrap_or_else(|| {
+                    panic!(
+                        "Expected --bin_exe=<name>=<path>, got '{}'",
+                        arg.to_string_lossy()
+                    )
+                });
+                target.bin_exes.push((name.to_owned(), path.to_owned()));
+            }
+            None if flag == "primary_package" => target.primary_package = true,
+            _ => panic!("Unknown flag '{}'", arg.to_string_lossy()),
+        }
+    }
+    (positional, target)
+}
+
+/// Prints the env vars Cargo sets for a specific target of the package, like `CARGO_CRATE_NAME`.
+fn print_target_env_vars(out: &mut impl Write, manifest: &TomlManifest, target: &Target) {
+    let Some(kind) = target.kind else {
+        return;
+    };
+
+    let package_name = manifest.package.as_ref().map(|p| p.name.as_str());
+    let name = target
+        .name
+        .as_deref()
+        .or_else(|| match kind {
+            TargetKind::Lib => manifest
+                .lib
+                .as_ref()
+                .and_then(|lib| lib.name.as_deref())
+                .or(package_name),
+            _ => package_name,
+        })
+        .expect("The name of a target without a package must be passed with --target_name");
+
+    print_env_str(out, "CARGO_CRATE_NAME", &name.replace('-', "_"));
+    if matches!(kind, TargetKind::Bin | TargetKind::Example) {
+        print_env_str(out, "CARGO_BIN_NAME", name);
+    }
+    if target.primary_package {
+        print_env_str(out, "CARGO_PRIMARY_PACKAGE", "1");
+    }
+    if matches!(kind, TargetKind::Test | TargetKind::Bench) {
+        if let Some(tmpdir) = &target.tmpdir {
+            print_env_str(out, "CARGO_TARGET_TMPDIR", tmpdir);
+        }
+        for (bin_name, path) in &target.bin_exes {
+            print_env_str(out, &format!("CARGO_BIN_EXE_{}", bin_name), path);
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -0,0 +1,13 @@
+#[test]
+fn bin_exe() {
+    assert_eq!(env!("CARGO_CRATE_NAME"), "bin_exe_test");
+
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_standalone_bin"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("CARGO_PKG_NAME=standalone\n"), "{stdout}");
+    assert!(stdout.contains("CARGO_BIN_NAME=standalone\n"), "{stdout}");
+}
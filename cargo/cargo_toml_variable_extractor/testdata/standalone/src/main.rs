@@ -22,4 +22,7 @@ fn main() {
     println!("CARGO_PKG_LICENSE_FILE={}", env!("CARGO_PKG_LICENSE_FILE"));
     println!("CARGO_PKG_RUST_VERSION={}", env!("CARGO_PKG_RUST_VERSION"));
     println!("CARGO_PKG_README={}", env!("CARGO_PKG_README"));
+    println!("CARGO_CRATE_NAME={}", env!("CARGO_CRATE_NAME"));
+    println!("CARGO_BIN_NAME={}", env!("CARGO_BIN_NAME"));
+    println!("CARGO_PRIMARY_PACKAGE={}", env!("CARGO_PRIMARY_PACKAGE"));
 }
@@ -21,6 +21,10 @@ load(
     _extract_cargo_lints = "extract_cargo_lints",
 )
 load("//cargo/private:cargo_toml_env_vars.bzl", _cargo_toml_env_vars = "cargo_toml_env_vars")
+load(
+    "//cargo/private:cargo_toml_metadata.bzl",
+    _extract_cargo_toml_metadata = "extract_cargo_toml_metadata",
+)
 
 cargo_bootstrap_repository = _cargo_bootstrap_repository
 cargo_env = _cargo_env
@@ -29,5 +33,6 @@ cargo_build_script = _cargo_build_script
 cargo_dep_env = _cargo_dep_env
 
 extract_cargo_lints = _extract_cargo_lints
+extract_cargo_toml_metadata = _extract_cargo_toml_metadata
 
 cargo_toml_env_vars = _cargo_toml_env_vars
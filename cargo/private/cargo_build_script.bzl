@@ -322,6 +322,8 @@ def _cargo_build_script_impl(ctx):
     flags_out = ctx.actions.declare_file(ctx.label.name + ".flags")
     link_flags = ctx.actions.declare_file(ctx.label.name + ".linkflags")
     link_search_paths = ctx.actions.declare_file(ctx.label.name + ".linksearchpaths")  # rustc-link-search, propagated from transitive dependencies
+    bin_link_flags = ctx.actions.declare_file(ctx.label.name + ".binlinkflags")  # rustc-link-arg-bin(s), only applied to binaries
+    cdylib_link_flags = ctx.actions.declare_file(ctx.label.name + ".cdyliblinkflags")  # rustc-cdylib-link-arg, only applied to cdylibs
     compilation_mode_opt_level = get_compilation_mode_opts(ctx, toolchain).opt_level
 
     script_tools = []
@@ -346,7 +348,11 @@ def _cargo_build_script_impl(ctx):
     # until more reliable functionality is implemented in Bazel:
     # https://github.com/bazelbuild/bazel/issues/15486
     incompatible_runfiles_cargo_manifest_dir = ctx.attr._incompatible_runfiles_cargo_manifest_dir[BuildSettingInfo].value
-    if not incompatible_runfiles_cargo_manifest_dir:
+    output_override = ctx.file.output_override
+    if output_override:
+        # The build script isn't run, so its runfiles aren't needed.
+        manifest_dir = "{}.runfiles/{}/{}".format(script.path, workspace_name, ctx.label.package)
+    elif not incompatible_runfiles_cargo_manifest_dir:
         script_data.append(ctx.attr.script[DefaultInfo].default_runfiles.files)
         manifest_dir = "{}.runfiles/{}/{}".format(script.path, workspace_name, ctx.label.package)
     else:
@@ -497,8 +503,7 @@ def _cargo_build_script_impl(ctx):
     ))
 
     tools = depset(
-        direct = [
-            script,
+        direct = ([] if output_override else [script]) + [
             ctx.executable._cargo_build_script_runner,
         ] + ([toolchain.target_json] if toolchain.target_json else []),
         transitive = script_data + script_tools + toolchain_tools,
@@ -517,13 +522,29 @@ def _cargo_build_script_impl(ctx):
     args.add(flags_out, format = "--flags_out=%s")
     args.add(link_flags, format = "--link_flags=%s")
     args.add(link_search_paths, format = "--link_search_paths=%s")
+    args.add(bin_link_flags, format = "--bin_link_flags=%s")
+    args.add(cdylib_link_flags, format = "--cdylib_link_flags=%s")
     args.add(dep_env_out, format = "--dep_env_out=%s")
     args.add(ctx.attr.rundir, format = "--rundir=%s")
 
+    build_script_jobs = ctx.attr._build_script_jobs[BuildSettingInfo].value
+    if build_script_jobs > 0:
+        args.add(build_script_jobs, format = "--jobs=%s")
+
     output_groups = {
         "out_dir": depset([out_dir]),
     }
 
+    # Reports are only for users, so unlike `extra_output` aren't compile data.
+    report_outputs = []
+
+    build_script_reports = ctx.attr._build_script_reports[BuildSettingInfo].value
+    if build_script_reports:
+        report = ctx.actions.declare_file(ctx.label.name + ".report.json")
+        args.add(report, format = "--report=%s")
+        report_outputs.append(report)
+        output_groups["report"] = depset([report])
+
     debug_std_streams_output_group = ctx.attr._debug_std_streams_output_group[BuildSettingInfo].value
     if debug_std_streams_output_group:
         debug_stdout = ctx.actions.declare_file(ctx.label.name + ".stdout.log")
@@ -536,6 +557,10 @@ def _cargo_build_script_impl(ctx):
 
     build_script_inputs = []
 
+    if output_override:
+        args.add(output_override, format = "--output_override=%s")
+        build_script_inputs.append(output_override)
+
     for dep in ctx.attr.link_deps:
         if rust_common.dep_info in dep and dep[rust_common.dep_info].dep_env:
             dep_env_file = dep[rust_common.dep_info].dep_env
@@ -554,6 +579,33 @@ def _cargo_build_script_impl(ctx):
     if experimental_symlink_execroot:
         env["RULES_RUST_SYMLINK_EXEC_ROOT"] = "1"
 
+    inputs = depset(build_script_inputs, transitive = extra_inputs)
+
+    audit_build_scripts = ctx.attr._audit_build_scripts[BuildSettingInfo].value
+    if audit_build_scripts != "off":
+        # The runner can't see which files are inputs of the action, so they're
+        # listed in a file for it to check the script's declared inputs against.
+        audit_inputs = ctx.actions.declare_file(ctx.label.name + ".audit_inputs")
+        audit_inputs_args = ctx.actions.args()
+        audit_inputs_args.set_param_file_format("multiline")
+        audit_inputs_args.add_all(
+            depset(transitive = [inputs, tools]),
+            expand_directories = False,
+        )
+        ctx.actions.write(
+            output = audit_inputs,
+            content = audit_inputs_args,
+        )
+        inputs = depset([audit_inputs], transitive = [inputs])
+
+        audit_report = ctx.actions.declare_file(ctx.label.name + ".audit.txt")
+        args.add(audit_report, format = "--audit_report=%s")
+        args.add(audit_inputs, format = "--audit_inputs=%s")
+        if audit_build_scripts == "error":
+            args.add("--audit_fail")
+        report_outputs.append(audit_report)
+        output_groups["audit"] = depset([audit_report])
+
     ctx.actions.run(
         executable = ctx.executable._cargo_build_script_runner,
         arguments = [args] + extra_args,
@@ -563,10 +615,12 @@ def _cargo_build_script_impl(ctx):
             flags_out,
             link_flags,
             link_search_paths,
+            bin_link_flags,
+            cdylib_link_flags,
             dep_env_out,
-        ] + extra_output,
+        ] + extra_output + report_outputs,
         tools = tools,
-        inputs = depset(build_script_inputs, transitive = extra_inputs),
+        inputs = inputs,
         mnemonic = "CargoBuildScriptRun",
         progress_message = "Running Cargo build script {}".format(pkg_name),
         env = env,
@@ -580,6 +634,8 @@ def _cargo_build_script_impl(ctx):
         # since bazel is lazy.
         DefaultInfo(files = depset([out_dir])),
         BuildInfo(
+            bin_linker_flags = bin_link_flags,
+            cdylib_linker_flags = cdylib_link_flags,
             out_dir = out_dir,
             rustc_env = env_out,
             dep_env = dep_env_out,
@@ -622,6 +678,17 @@ cargo_build_script = rule(
         "links": attr.string(
             doc = "The name of the native library this crate links against.",
         ),
+        "output_override": attr.label(
+            doc = dedent("""\
+                A file of `cargo::` directives to use in place of the output of `script`.
+
+                When set, the build script is not run and the directives are parsed from
+                this file instead. `${pwd}` is replaced with the exec root. Since the
+                output generally depends on the target, use a `select()` to provide a file
+                per platform.
+            """),
+            allow_single_file = True,
+        ),
         "pkg_name": attr.string(
             doc = "The name of package being compiled, if not derived from `name`.",
         ),
@@ -678,6 +745,15 @@ cargo_build_script = rule(
             default = Label("//cargo/cargo_build_script_runner:runner"),
             cfg = "exec",
         ),
+        "_audit_build_scripts": attr.label(
+            default = Label("//cargo/settings:audit_build_scripts"),
+        ),
+        "_build_script_jobs": attr.label(
+            default = Label("//cargo/settings:build_script_jobs"),
+        ),
+        "_build_script_reports": attr.label(
+            default = Label("//cargo/settings:build_script_reports"),
+        ),
         "_cargo_manifest_dir_filename_suffixes_to_retain": attr.label(
             default = Label("//cargo/settings:cargo_manifest_dir_filename_suffixes_to_retain"),
         ),
@@ -35,6 +35,7 @@ def cargo_build_script(
         tags = None,
         aliases = None,
         pkg_name = None,
+        output_override = None,
         **kwargs):
     """Compile and execute a rust build script to generate build attributes
 
@@ -123,6 +124,8 @@ def cargo_build_script(
         rustc_env (dict, optional): Environment variables to set in rustc when compiling the build script.
         rustc_env_files (list of label, optional): Files containing additional environment variables to set for rustc
             when building the build script.
+        output_override (label, optional): A file of `cargo::` directives used in place of the output of the build script,
+            which is then not run. `${pwd}` is replaced with the exec root. Use a `select()` to provide a file per platform.
         rustc_flags (list, optional): List of compiler flags passed to `rustc`.
         visibility (list of label, optional): Visibility to apply to the generated build script output.
         tags: (list of str, optional): Tags to apply to the generated build script output.
@@ -218,5 +221,6 @@ def cargo_build_script(
         visibility = visibility,
         tags = tags,
         pkg_name = pkg_name,
+        output_override = output_override,
         **kwargs
     )
@@ -29,6 +29,8 @@ def _cargo_dep_env_impl(ctx):
         #
         # TLDR: This BuildInfo propagates up build script dependencies.
         build_infos.append(BuildInfo(
+            bin_linker_flags = empty_file,
+            cdylib_linker_flags = empty_file,
             dep_env = empty_file,
             flags = empty_file,
             linker_flags = empty_file,
@@ -48,6 +50,8 @@ def _cargo_dep_env_impl(ctx):
         # In the future, we could consider setting rustc_env here, and also propagating dep_dir
         # so files in it can be referenced there.
         BuildInfo(
+            bin_linker_flags = empty_file,
+            cdylib_linker_flags = empty_file,
             dep_env = empty_file,
             flags = empty_file,
             linker_flags = empty_file,
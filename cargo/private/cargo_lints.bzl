@@ -38,21 +38,54 @@ def _extract_cargo_lints(ctx):
         progress_message = "Reading Cargo metadata to get Lints for {}".format(ctx.attr.name),
     )
 
+    rustc_lint_files = [rustc_lints_out]
+    rustdoc_lint_files = [rustdoc_lints_out]
+
+    if ctx.attr.check_cfg:
+        check_cfg_args = ctx.actions.args()
+        check_cfg_args.add("--manifest_toml={0}".format(ctx.file.manifest.path))
+        if ctx.attr.workspace:
+            check_cfg_args.add("--workspace_toml={0}".format(ctx.file.workspace.path))
+        check_cfg_args.add("check-cfg")
+
+        check_cfg_out = ctx.actions.declare_file(ctx.label.name + ".check_cfg")
+        check_cfg_args.add(check_cfg_out)
+
+        ctx.actions.run(
+            outputs = [check_cfg_out],
+            executable = ctx.file._cargo_toml_info,
+            inputs = inputs,
+            arguments = [check_cfg_args],
+            mnemonic = "CargoCheckCfg",
+            progress_message = "Reading Cargo metadata to get check-cfg for {}".format(ctx.attr.name),
+        )
+
+        outputs.append(check_cfg_out)
+        rustc_lint_files.append(check_cfg_out)
+        rustdoc_lint_files.append(check_cfg_out)
+
     return [
         DefaultInfo(files = depset(outputs), runfiles = ctx.runfiles(outputs)),
         LintsInfo(
             rustc_lint_flags = [],
-            rustc_lint_files = [rustc_lints_out],
+            rustc_lint_files = rustc_lint_files,
             clippy_lint_flags = [],
             clippy_lint_files = [clippy_lints_out],
             rustdoc_lint_flags = [],
-            rustdoc_lint_files = [rustdoc_lints_out],
+            rustdoc_lint_files = rustdoc_lint_files,
         ),
     ]
 
 extract_cargo_lints = rule(
     implementation = _extract_cargo_lints,
     attrs = {
+        "check_cfg": attr.bool(
+            default = False,
+            doc = (
+                "Whether to also pass the `--check-cfg` arguments Cargo would to rustc, for the " +
+                "features declared in the manifest and `check-cfg` of `[lints.rust.unexpected_cfgs]`."
+            ),
+        ),
         "manifest": attr.label(
             allow_single_file = True,
             mandatory = True,
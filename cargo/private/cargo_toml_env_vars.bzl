@@ -5,6 +5,25 @@ def _cargo_toml_env_vars_impl(ctx):
 
     inputs = [ctx.file.src]
     args = ctx.actions.args()
+
+    if ctx.attr.target_kind:
+        args.add(ctx.attr.target_kind, format = "--target_kind=%s")
+        if ctx.attr.target_name:
+            args.add(ctx.attr.target_name, format = "--target_name=%s")
+        if ctx.attr.primary_package:
+            args.add("--primary_package")
+        if ctx.attr.target_tmpdir:
+            args.add(ctx.attr.target_tmpdir, format = "--target_tmpdir=%s")
+        for binary in ctx.attr.binaries:
+            executable = binary[DefaultInfo].files_to_run.executable
+            name = executable.basename
+            if name.endswith(".exe"):
+                name = name[:-len(".exe")]
+
+            # Tests run from the root of their runfiles, where the binary can be found at its
+            # short path.
+            args.add("--bin_exe={}={}".format(name, executable.short_path))
+
     args.add(out)
     args.add(ctx.file.src)
 
@@ -27,11 +46,34 @@ def _cargo_toml_env_vars_impl(ctx):
 cargo_toml_env_vars = rule(
     implementation = _cargo_toml_env_vars_impl,
     attrs = {
+        "binaries": attr.label_list(
+            doc = (
+                "Binaries of the package to set `CARGO_BIN_EXE_<name>` to the path of, for `test` and `bench` " +
+                "targets. `<name>` is the name of the binary's executable, without extension. The path is " +
+                "relative to the root of the test's runfiles, so the binaries must also be in its `data`."
+            ),
+        ),
+        "primary_package": attr.bool(
+            doc = "Whether to set `CARGO_PRIMARY_PACKAGE`, which Cargo sets for the packages the user asked to build rather than their dependencies.",
+        ),
         "src": attr.label(
             allow_single_file = True,
             mandatory = True,
             doc = "Cargo.toml file to derive env vars from",
         ),
+        "target_kind": attr.string(
+            doc = (
+                "The kind of target the env vars are for, enabling the target specific env vars like " +
+                "`CARGO_CRATE_NAME` and `CARGO_BIN_NAME`. If unset, only the env vars of the package are set."
+            ),
+            values = ["", "lib", "proc-macro", "bin", "example", "test", "bench"],
+        ),
+        "target_name": attr.string(
+            doc = "The name of the target, defaulting to the name of the package, or the `[lib]` name for libraries.",
+        ),
+        "target_tmpdir": attr.string(
+            doc = "A directory `test` and `bench` targets may write to, which `CARGO_TARGET_TMPDIR` is set to. It isn't set if empty.",
+        ),
         "workspace": attr.label(
             allow_single_file = True,
             doc = "Workspace Cargo.toml file from which values may be inherited",
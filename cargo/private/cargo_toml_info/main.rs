@@ -4,10 +4,10 @@
 //! information for a crate, that should live in crate_universe. This tool is intended to read
 //! non-build related Cargo metadata like lints, authors, or badges.
 
-use cargo_toml::{Lint, LintLevel, Manifest};
+use cargo_toml::{Lint, LintLevel, Manifest, Value};
 
 use std::borrow::Cow;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::error::Error;
 use std::fs::File;
 use std::io::{LineWriter, Write};
@@ -25,11 +25,12 @@ pub fn main() -> Result<(), Box<dyn Error>> {
     let mut workspace_manifest = None;
 
     // Optionally populate the manifest with info from the parent workspace, if one is provided.
-    if let Some(workspace_path) = workspace_toml {
-        let manifest = Manifest::from_path(&workspace_path)?;
+    if let Some(workspace_path) = &workspace_toml {
+        let manifest = Manifest::from_path(workspace_path)?;
         let workspace_details = Some((&manifest, workspace_path.as_path()));
 
-        // TODO(parkmycar): Fix cargo_toml so we inherit lints from our workspace.
+        // `cargo_toml` doesn't inherit lints from the workspace, they're resolved by
+        // `resolve_lints`.
         //
         // See: <https://gitlab.com/lib.rs/cargo_toml/-/issues/35>
         crate_manifest.complete_from_path_and_workspace(&manifest_toml, workspace_details)?;
@@ -40,6 +41,46 @@ pub fn main() -> Result<(), Box<dyn Error>> {
         Command::Lints(args) => {
             generate_lints_info(&crate_manifest, workspace_manifest.as_ref(), args)?
         }
+        Command::CheckCfg(args) => {
+            // `cargo_toml` doesn't keep the `check-cfg` of lints, so they are read from the raw
+            // manifests.
+            let crate_value: Value = manifest_contents.parse()?;
+            let workspace_value: Option<Value> = match &workspace_toml {
+                Some(path) => Some(std::fs::read_to_string(path)?.parse()?),
+                None => None,
+            };
+            generate_check_cfg_info(
+                &crate_manifest,
+                &crate_value,
+                workspace_value.as_ref(),
+                args,
+            )?
+        }
+        Command::Metadata(args) => {
+            let crate_value: Value = manifest_contents.parse()?;
+            let metadata = crate_value
+                .get("package")
+                .and_then(|package| package.get("metadata"));
+            write_json(
+                &args,
+                &metadata.map_or_else(|| "{}".to_owned(), value_to_json),
+            )?
+        }
+        Command::Badges(args) => {
+            // The raw manifest is used so badges `cargo_toml` doesn't know of are kept.
+            let crate_value: Value = manifest_contents.parse()?;
+            let badges = crate_value.get("badges");
+            write_json(
+                &args,
+                &badges.map_or_else(|| "{}".to_owned(), value_to_json),
+            )?
+        }
+        Command::Targets(args) => {
+            // Only the targets declared in the manifest are listed, not the ones Cargo would
+            // discover from the layout of the package.
+            let crate_value: Value = manifest_contents.parse()?;
+            write_json(&args, &targets_to_json(&crate_value)?)?
+        }
     }
 
     Ok(())
@@ -52,6 +93,9 @@ struct LintsArgs {
     output_rustdoc_lints: PathBuf,
 }
 
+/// Lints by tool, then by name, as in the `[lints]` table of a manifest.
+type LintGroups = BTreeMap<String, BTreeMap<String, Lint>>;
+
 enum LintGroup {
     Rustc,
     Clippy,
@@ -59,28 +103,32 @@ enum LintGroup {
 }
 
 impl LintGroup {
-    pub fn key(&self) -> &'static str {
+    /// Whether the lints of `tool` are passed to this [`LintGroup`].
+    ///
+    /// Each group only gets the lints of its own tool, the lints of other tools are ignored. Tools
+    /// which rustc doesn't know, like `cargo` or third party linters, would fail the build with
+    /// E0602.
+    pub fn includes(&self, tool: &str) -> bool {
         match self {
-            LintGroup::Rustc => "rust",
-            LintGroup::Clippy => "clippy",
-            LintGroup::RustDoc => "rustdoc",
+            LintGroup::Rustc => tool == "rust",
+            LintGroup::Clippy => tool == "clippy",
+            LintGroup::RustDoc => tool == "rustdoc",
         }
     }
+}
 
-    /// Format a lint `name` and `level` for this [`LintGroup`].
-    pub fn format_cli_arg(&self, name: &str, level: LintLevel) -> String {
-        let level = match level {
-            LintLevel::Allow => "allow",
-            LintLevel::Warn => "warn",
-            LintLevel::Forbid => "forbid",
-            LintLevel::Deny => "deny",
-        };
+/// Format a lint `name` of `tool` and its `level` as a CLI flag.
+fn format_cli_arg(tool: &str, name: &str, level: LintLevel) -> String {
+    let level = match level {
+        LintLevel::Allow => "allow",
+        LintLevel::Warn => "warn",
+        LintLevel::Forbid => "forbid",
+        LintLevel::Deny => "deny",
+    };
 
-        match self {
-            LintGroup::Rustc => format!("--{level}={name}"),
-            LintGroup::Clippy => format!("--{level}=clippy::{name}"),
-            LintGroup::RustDoc => format!("--{level}=rustdoc::{name}"),
-        }
+    match tool {
+        "rust" => format!("--{level}={name}"),
+        tool => format!("--{level}={tool}::{name}"),
     }
 }
 
@@ -92,9 +140,39 @@ fn lint_priority(lint: &cargo_toml::Lint) -> i32 {
     }
 }
 
+/// Returns the lints which apply to a crate, taken from the workspace if the manifest inherits
+/// its lints.
+///
+/// Like Cargo, this is an error if the manifest both inherits its lints and sets some of its own.
+fn resolve_lints<'a>(
+    crate_manifest: &'a Manifest,
+    workspace_manifest: Option<&'a Manifest>,
+) -> Result<Option<&'a LintGroups>, Box<dyn Error>> {
+    match &crate_manifest.lints {
+        Some(lints) if lints.workspace => {
+            if !lints.groups.is_empty() {
+                return Err(
+                    "cannot override `workspace.lints` in `lints`, either remove the overrides \
+                     or `lints.workspace = true` and manually specify the lints"
+                        .into(),
+                );
+            }
+            let workspace = workspace_manifest
+                .and_then(|manifest| manifest.workspace.as_ref())
+                .ok_or({
+                    "manifest inherits lints from the workspace, but no workspace manifest provided"
+                })?;
+            Ok(workspace.lints.as_ref())
+        }
+        Some(lints) => Ok(Some(&lints.groups)),
+        None => Ok(None),
+    }
+}
+
 /// Formats the given lint set for the given group into CLI format.
 ///
-/// This functions sorts the lints based on priority if given.
+/// The lints are sorted the way Cargo does: by priority, then by reverse name so that groups like
+/// `all` come after the lints they contain.
 ///
 /// # Args
 /// - `lints`: The lint to format, the value should come from the `lints.groups` field of the manifest.
@@ -102,29 +180,33 @@ fn lint_priority(lint: &cargo_toml::Lint) -> i32 {
 ///
 /// # Returns
 ///
-/// An iterator of strings which can be passed to the linter as a CLI flag.
+/// A list of strings which can be passed to the linter as CLI flags.
 ///
-fn format_lint_set<'a>(
-    lints: Option<&'a BTreeMap<String, BTreeMap<String, Lint>>>,
-    group: &'a LintGroup,
-) -> Option<impl Iterator<Item = String> + 'a> {
-    let lints = lints?.get(group.key())?;
-    let mut lints = Vec::from_iter(lints);
-    lints.sort_by(|(_, a), (_, b)| {
-        let a_priority = lint_priority(a);
-        let b_priority = lint_priority(b);
-        a_priority.cmp(&b_priority)
-    });
-
-    let formatted = lints.into_iter().map(|(name, lint)| {
-        let level = match lint {
-            cargo_toml::Lint::Detailed { level, priority: _ } => level,
-            cargo_toml::Lint::Simple(level) => level,
-        };
-        group.format_cli_arg(name, *level)
-    });
+fn format_lint_set(lints: Option<&LintGroups>, group: &LintGroup) -> Vec<String> {
+    let Some(lints) = lints else {
+        return Vec::new();
+    };
+
+    let mut lints: Vec<_> = lints
+        .iter()
+        .filter(|(tool, _)| group.includes(tool))
+        .flat_map(|(tool, lints)| {
+            lints.iter().map(move |(name, lint)| {
+                let level = match lint {
+                    cargo_toml::Lint::Detailed { level, priority: _ } => level,
+                    cargo_toml::Lint::Simple(level) => level,
+                };
+                (
+                    lint_priority(lint),
+                    std::cmp::Reverse(name),
+                    format_cli_arg(tool, name, *level),
+                )
+            })
+        })
+        .collect();
+    lints.sort();
 
-    Some(formatted)
+    lints.into_iter().map(|(_, _, arg)| arg).collect()
 }
 
 /// Generates space seperated <lint name> <lint level> files that get read back in by Bazel.
@@ -145,29 +227,15 @@ fn generate_lints_info(
         (LintGroup::RustDoc, output_rustdoc_lints),
     ];
 
-    let lints = match &crate_manifest.lints {
-        Some(lints) if lints.workspace => {
-            let workspace = workspace_manifest
-                .as_ref()
-                .and_then(|manifest| manifest.workspace.as_ref())
-                .ok_or({
-                    "manifest inherits lints from the workspace, but no workspace manifest provided"
-                })?;
-            workspace.lints.as_ref()
-        }
-        Some(lints) => Some(&lints.groups),
-        None => None,
-    };
+    let lints = resolve_lints(crate_manifest, workspace_manifest)?;
 
     for (group, path) in groups {
         let file = File::create(&path)?;
         let mut writer = LineWriter::new(file);
 
-        if let Some(args) = format_lint_set(lints, &group) {
-            for arg in args {
-                writeln!(&mut writer, "{arg}")?;
-            }
-        };
+        for arg in format_lint_set(lints, &group) {
+            writeln!(&mut writer, "{arg}")?;
+        }
 
         writer.flush()?;
     }
@@ -175,6 +243,212 @@ fn generate_lints_info(
     Ok(())
 }
 
+#[derive(Debug)]
+struct CheckCfgArgs {
+    output_check_cfg: PathBuf,
+}
+
+/// Returns the features of a crate, including the implicit features of optional dependencies
+/// which aren't referred to with `dep:` in `[features]`.
+fn crate_features(manifest: &Manifest) -> BTreeSet<String> {
+    let mut features: BTreeSet<String> = manifest.features.keys().cloned().collect();
+    let explicit_deps: BTreeSet<&str> = manifest
+        .features
+        .values()
+        .flatten()
+        .filter_map(|value| value.strip_prefix("dep:"))
+        .collect();
+    let targets = manifest.target.values();
+    let optional_deps = [&manifest.dependencies, &manifest.build_dependencies]
+        .into_iter()
+        .chain(targets.flat_map(|target| [&target.dependencies, &target.build_dependencies]))
+        .flatten()
+        .filter(|(_, dep)| dep.optional())
+        .map(|(name, _)| name);
+    for name in optional_deps {
+        if !explicit_deps.contains(name.as_str()) {
+            features.insert(name.clone());
+        }
+    }
+    features
+}
+
+/// Returns the `check-cfg` values of `[lints.rust.unexpected_cfgs]`, taken from the workspace if
+/// the manifest inherits its lints.
+fn unexpected_cfgs_check_cfg(
+    crate_manifest: &Value,
+    workspace_manifest: Option<&Value>,
+) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut lints = crate_manifest.get("lints");
+    if lints
+        .and_then(|lints| lints.get("workspace"))
+        .and_then(Value::as_bool)
+        .unwrap_or(false)
+    {
+        let workspace = workspace_manifest
+            .and_then(|manifest| manifest.get("workspace"))
+            .ok_or(
+                "manifest inherits lints from the workspace, but no workspace manifest provided",
+            )?;
+        lints = workspace.get("lints");
+    }
+
+    let Some(check_cfg) = lints
+        .and_then(|lints| lints.get("rust"))
+        .and_then(|rust| rust.get("unexpected_cfgs"))
+        .and_then(|unexpected_cfgs| unexpected_cfgs.get("check-cfg"))
+    else {
+        return Ok(Vec::new());
+    };
+    let invalid = || "`lints.rust.unexpected_cfgs.check-cfg` must be an array of strings";
+    check_cfg
+        .as_array()
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|value| Ok(value.as_str().ok_or_else(invalid)?.to_owned()))
+        .collect()
+}
+
+/// Formats the `--check-cfg` arguments Cargo passes to rustc for a crate.
+fn format_check_cfg(features: &BTreeSet<String>, check_cfg: &[String]) -> Vec<String> {
+    let values = features
+        .iter()
+        .map(|feature| format!("{feature:?}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut args = vec![
+        "--check-cfg=cfg(docsrs,test)".to_owned(),
+        format!("--check-cfg=cfg(feature, values({values}))"),
+    ];
+    args.extend(check_cfg.iter().map(|cfg| format!("--check-cfg={cfg}")));
+    args
+}
+
+/// Generates a file of `--check-cfg` arguments, one per line, that gets read back in by Bazel.
+fn generate_check_cfg_info(
+    crate_manifest: &Manifest,
+    crate_value: &Value,
+    workspace_value: Option<&Value>,
+    args: CheckCfgArgs,
+) -> Result<(), Box<dyn Error>> {
+    let check_cfg = unexpected_cfgs_check_cfg(crate_value, workspace_value)?;
+    let file = File::create(&args.output_check_cfg)?;
+    let mut writer = LineWriter::new(file);
+    for arg in format_check_cfg(&crate_features(crate_manifest), &check_cfg) {
+        writeln!(&mut writer, "{arg}")?;
+    }
+    writer.flush()?;
+
+    Ok(())
+}
+
+#[derive(Debug)]
+struct JsonArgs {
+    output_json: PathBuf,
+}
+
+/// Quotes and escapes `value` as a JSON string.
+fn json_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Formats a TOML value as JSON.
+///
+/// Datetimes are formatted as strings and floats JSON can't represent, like `nan`, as `null`.
+fn value_to_json(value: &Value) -> String {
+    match value {
+        Value::String(s) => json_string(s),
+        Value::Integer(i) => i.to_string(),
+        Value::Float(f) if f.is_finite() => format!("{f:?}"),
+        Value::Float(_) => "null".to_owned(),
+        Value::Boolean(b) => b.to_string(),
+        Value::Datetime(datetime) => json_string(&datetime.to_string()),
+        Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(value_to_json).collect();
+            format!("[{}]", values.join(","))
+        }
+        Value::Table(table) => {
+            let entries: Vec<String> = table
+                .iter()
+                .map(|(key, value)| format!("{}:{}", json_string(key), value_to_json(value)))
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
+    }
+}
+
+/// The kinds of targets which can be declared as arrays of tables in a manifest.
+const TARGET_KINDS: [&str; 4] = ["bin", "example", "test", "bench"];
+
+/// Formats the `[[bin]]`, `[[example]]`, `[[test]]` and `[[bench]]` targets declared in a
+/// manifest as a JSON object with their names, paths and required features.
+///
+/// A target's `name` or `path` is `null` when it's left for Cargo to infer.
+fn targets_to_json(crate_manifest: &Value) -> Result<String, Box<dyn Error>> {
+    let mut kinds = Vec::new();
+    for kind in TARGET_KINDS {
+        let declared = match crate_manifest.get(kind) {
+            Some(declared) => declared
+                .as_array()
+                .ok_or_else(|| format!("`{kind}` must be an array of tables"))?
+                .as_slice(),
+            None => &[],
+        };
+
+        let mut targets = Vec::new();
+        for target in declared {
+            let field = |name: &str| -> Result<String, String> {
+                match target.get(name) {
+                    Some(value) => value
+                        .as_str()
+                        .map(json_string)
+                        .ok_or_else(|| format!("`{kind}.{name}` must be a string")),
+                    None => Ok("null".to_owned()),
+                }
+            };
+            let required_features = match target.get("required-features") {
+                Some(features) => features
+                    .as_array()
+                    .filter(|features| features.iter().all(Value::is_str))
+                    .map(|features| value_to_json(&Value::Array(features.clone())))
+                    .ok_or_else(|| {
+                        format!("`{kind}.required-features` must be an array of strings")
+                    })?,
+                None => "[]".to_owned(),
+            };
+            targets.push(format!(
+                "{{\"name\":{},\"path\":{},\"required_features\":{required_features}}}",
+                field("name")?,
+                field("path")?,
+            ));
+        }
+        kinds.push(format!("{}:[{}]", json_string(kind), targets.join(",")));
+    }
+
+    Ok(format!("{{{}}}", kinds.join(",")))
+}
+
+/// Writes `json`, followed by a newline, to the output of `args`.
+fn write_json(args: &JsonArgs, json: &str) -> Result<(), Box<dyn Error>> {
+    let mut file = File::create(&args.output_json)?;
+    writeln!(&mut file, "{json}")?;
+    Ok(())
+}
+
 #[derive(Debug)]
 struct Args {
     manifest_toml: PathBuf,
@@ -236,6 +510,15 @@ enum Command {
     /// 3. output for rustdoc lints
     ///
     Lints(LintsArgs),
+    /// Expects a filesystem path to output the `--check-cfg` arguments for rustc to.
+    CheckCfg(CheckCfgArgs),
+    /// Expects a filesystem path to output the `[package.metadata]` table to, as JSON.
+    Metadata(JsonArgs),
+    /// Expects a filesystem path to output the `[badges]` table to, as JSON.
+    Badges(JsonArgs),
+    /// Expects a filesystem path to output the declared `[[bin]]`, `[[example]]`, `[[test]]` and
+    /// `[[bench]]` targets to, as JSON.
+    Targets(JsonArgs),
 }
 
 impl TryFrom<RemainingArgs> for Command {
@@ -276,6 +559,38 @@ impl TryFrom<RemainingArgs> for Command {
                     output_rustdoc_lints,
                 }))
             }
+            "check-cfg" => {
+                let output_check_cfg = args.next().map(PathBuf::from).ok_or(Cow::Borrowed(
+                    "expected output path for check-cfg arguments",
+                ))?;
+
+                if args.peek().is_some() {
+                    let remaining: Vec<String> = args.collect();
+                    let msg = format!("expected end of arguments, found: {remaining:?}");
+                    return Err(Cow::Owned(msg));
+                }
+
+                Ok(Command::CheckCfg(CheckCfgArgs { output_check_cfg }))
+            }
+            action @ ("metadata" | "badges" | "targets") => {
+                let output_json = args
+                    .next()
+                    .map(PathBuf::from)
+                    .ok_or_else(|| Cow::Owned(format!("expected output path for {action} json")))?;
+
+                if args.peek().is_some() {
+                    let remaining: Vec<String> = args.collect();
+                    let msg = format!("expected end of arguments, found: {remaining:?}");
+                    return Err(Cow::Owned(msg));
+                }
+
+                let args = JsonArgs { output_json };
+                Ok(match action {
+                    "metadata" => Command::Metadata(args),
+                    "badges" => Command::Badges(args),
+                    _ => Command::Targets(args),
+                })
+            }
             other => Err(format!("unknown action: {other}").into()),
         }
     }
@@ -289,7 +604,7 @@ mod test {
     /// Tests priority handling of different lints
     #[test]
     fn format_lint_set() {
-        assert!(super::format_lint_set(None, &super::LintGroup::Rustc).is_none());
+        assert!(super::format_lint_set(None, &super::LintGroup::Rustc).is_empty());
 
         let lints_map: BTreeMap<String, BTreeMap<String, Lint>> = BTreeMap::from_iter([(
             "clippy".into(),
@@ -345,31 +660,270 @@ mod test {
             ]),
         )]);
 
-        assert!(super::format_lint_set(Some(&lints_map), &super::LintGroup::Rustc).is_none());
+        assert!(super::format_lint_set(Some(&lints_map), &super::LintGroup::Rustc).is_empty());
 
-        let lints: Vec<String> =
-            super::format_lint_set(Some(&lints_map), &super::LintGroup::Clippy)
-                .unwrap()
-                .collect();
+        // Lints of the same priority are sorted by reverse name, like Cargo does.
+        let lints = super::format_lint_set(Some(&lints_map), &super::LintGroup::Clippy);
         assert_eq!(
             lints,
             [
                 "--deny=clippy::rustc_deny",
-                "--deny=clippy::clippy_deny",
                 "--forbid=clippy::clippy_forbid",
-                "--warn=clippy::clippy_warn",
-                "--allow=clippy::rustc_allow",
-                "--deny=clippy::rustc_deny_without_priority",
-                "--deny=clippy::rustc_deny_without_priority2",
-                "--forbid=clippy::rustc_forbid",
-                "--warn=clippy::rustc_warn",
-                "--allow=clippy::rustdoc_allow",
+                "--deny=clippy::clippy_deny",
                 "--deny=clippy::rustdoc_deny",
+                "--allow=clippy::rustdoc_allow",
+                "--warn=clippy::rustc_warn",
+                "--forbid=clippy::rustc_forbid",
+                "--deny=clippy::rustc_deny_without_priority2",
+                "--deny=clippy::rustc_deny_without_priority",
+                "--allow=clippy::rustc_allow",
+                "--warn=clippy::clippy_warn",
                 "--forbid=clippy::rustdoc_forbid"
             ]
         );
     }
 
+    /// Tests that each group only gets the lints of its own tool.
+    #[test]
+    fn format_lint_set_splits_tools() {
+        let manifest = cargo_toml::Manifest::from_str(
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+
+            [lints.rust]
+            unsafe_code = "forbid"
+            warnings = { level = "deny", priority = -2 }
+
+            [lints.clippy]
+            all = { level = "warn", priority = -1 }
+            pedantic = { level = "warn", priority = -1 }
+            unwrap_used = "deny"
+
+            [lints.rustdoc]
+            broken_intra_doc_links = "deny"
+
+            [lints.cargo]
+            implicit_features = "warn"
+
+            [lints.mytool]
+            custom = "allow"
+            "#,
+        )
+        .unwrap();
+        let lints = Some(&manifest.lints.as_ref().unwrap().groups);
+
+        assert_eq!(
+            super::format_lint_set(lints, &super::LintGroup::Rustc),
+            ["--deny=warnings", "--forbid=unsafe_code"]
+        );
+        assert_eq!(
+            super::format_lint_set(lints, &super::LintGroup::Clippy),
+            [
+                "--warn=clippy::pedantic",
+                "--warn=clippy::all",
+                "--deny=clippy::unwrap_used"
+            ]
+        );
+        assert_eq!(
+            super::format_lint_set(lints, &super::LintGroup::RustDoc),
+            ["--deny=rustdoc::broken_intra_doc_links"]
+        );
+    }
+
+    #[test]
+    fn resolve_lints() {
+        let workspace = cargo_toml::Manifest::from_str(
+            r#"
+            [workspace]
+            members = ["foo"]
+
+            [workspace.lints.rust]
+            unsafe_code = "forbid"
+
+            [workspace.lints.clippy]
+            all = { level = "deny", priority = -1 }
+            "#,
+        )
+        .unwrap();
+        let manifest = |lints: &str| {
+            cargo_toml::Manifest::from_str(&format!(
+                "[package]\nname = \"foo\"\nversion = \"0.1.0\"\n{lints}"
+            ))
+            .unwrap()
+        };
+
+        // Inherited lints are the workspace's.
+        let inheriting = manifest("[lints]\nworkspace = true");
+        let lints = super::resolve_lints(&inheriting, Some(&workspace))
+            .unwrap()
+            .unwrap();
+        assert_eq!(lints.keys().collect::<Vec<_>>(), ["clippy", "rust"]);
+        assert_eq!(
+            super::format_lint_set(Some(lints), &super::LintGroup::Clippy),
+            ["--deny=clippy::all"]
+        );
+        assert!(super::resolve_lints(&inheriting, None).is_err());
+
+        // Lints can't be both inherited and overridden.
+        let overriding =
+            manifest("[lints]\nworkspace = true\n[lints.rust]\nunsafe_code = \"allow\"");
+        let error = super::resolve_lints(&overriding, Some(&workspace)).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("cannot override `workspace.lints`"));
+
+        // Lints which aren't inherited are the crate's own.
+        let own = manifest("[lints.rust]\nunsafe_code = \"allow\"");
+        let lints = super::resolve_lints(&own, Some(&workspace)).unwrap();
+        assert_eq!(
+            super::format_lint_set(lints, &super::LintGroup::Rustc),
+            ["--allow=unsafe_code"]
+        );
+
+        let none = manifest("");
+        assert!(super::resolve_lints(&none, Some(&workspace))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn crate_features() {
+        let manifest = cargo_toml::Manifest::from_str(
+            r#"
+            [package]
+            name = "foo"
+            version = "0.1.0"
+
+            [features]
+            default = ["std"]
+            std = []
+            tls = ["dep:rustls"]
+
+            [dependencies]
+            rustls = { version = "0.23", optional = true }
+            serde = { version = "1", optional = true }
+            log = "0.4"
+
+            [target.'cfg(unix)'.dependencies]
+            libc = { version = "0.2", optional = true }
+            "#,
+        )
+        .unwrap();
+
+        let features: Vec<_> = super::crate_features(&manifest).into_iter().collect();
+        assert_eq!(features, ["default", "libc", "serde", "std", "tls"]);
+    }
+
+    #[test]
+    fn unexpected_cfgs_check_cfg() {
+        let crate_manifest: cargo_toml::Value = r#"
+            [lints.rust.unexpected_cfgs]
+            level = "warn"
+            check-cfg = ['cfg(tokio_unstable)', 'cfg(foo, values("bar"))']
+            "#
+        .parse()
+        .unwrap();
+        assert_eq!(
+            super::unexpected_cfgs_check_cfg(&crate_manifest, None).unwrap(),
+            ["cfg(tokio_unstable)", r#"cfg(foo, values("bar"))"#]
+        );
+
+        let inheriting_manifest: cargo_toml::Value = "lints.workspace = true".parse().unwrap();
+        let workspace_manifest: cargo_toml::Value = r#"
+            [workspace.lints.rust]
+            unexpected_cfgs = { level = "warn", check-cfg = ['cfg(loom)'] }
+            "#
+        .parse()
+        .unwrap();
+        assert_eq!(
+            super::unexpected_cfgs_check_cfg(&inheriting_manifest, Some(&workspace_manifest))
+                .unwrap(),
+            ["cfg(loom)"]
+        );
+        assert!(super::unexpected_cfgs_check_cfg(&inheriting_manifest, None).is_err());
+
+        let no_lints: cargo_toml::Value = "".parse().unwrap();
+        assert!(super::unexpected_cfgs_check_cfg(&no_lints, None)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn format_check_cfg() {
+        let features = ["default".to_owned(), "std".to_owned()].into();
+        assert_eq!(
+            super::format_check_cfg(&features, &["cfg(loom)".to_owned()]),
+            [
+                "--check-cfg=cfg(docsrs,test)",
+                r#"--check-cfg=cfg(feature, values("default", "std"))"#,
+                "--check-cfg=cfg(loom)",
+            ]
+        );
+        assert_eq!(
+            super::format_check_cfg(&Default::default(), &[])[1],
+            "--check-cfg=cfg(feature, values())"
+        );
+    }
+
+    #[test]
+    fn value_to_json() {
+        let manifest: cargo_toml::Value = r#"
+            [package.metadata.docs.rs]
+            all-features = true
+            rustdoc-args = ["--cfg", "docsrs"]
+            targets = []
+
+            [package.metadata.release]
+            message = "Release \"{{version}}\"\n"
+            level = 2
+            ratio = 0.5
+            date = 2024-01-02
+            "#
+        .parse()
+        .unwrap();
+        let metadata = manifest.get("package").unwrap().get("metadata").unwrap();
+        assert_eq!(
+            super::value_to_json(metadata),
+            concat!(
+                r#"{"docs":{"rs":{"all-features":true,"rustdoc-args":["--cfg","docsrs"],"targets":[]}},"#,
+                r#""release":{"date":"2024-01-02","level":2,"message":"Release \"{{version}}\"\n","ratio":0.5}}"#,
+            )
+        );
+    }
+
+    #[test]
+    fn targets_to_json() {
+        let manifest: cargo_toml::Value = r#"
+            [[bin]]
+            name = "tool"
+            path = "src/bin/tool.rs"
+            required-features = ["cli"]
+
+            [[bin]]
+            name = "other"
+
+            [[bench]]
+            name = "perf"
+            harness = false
+            "#
+        .parse()
+        .unwrap();
+        assert_eq!(
+            super::targets_to_json(&manifest).unwrap(),
+            concat!(
+                r#"{"bin":[{"name":"tool","path":"src/bin/tool.rs","required_features":["cli"]},"#,
+                r#"{"name":"other","path":null,"required_features":[]}],"#,
+                r#""example":[],"test":[],"#,
+                r#""bench":[{"name":"perf","path":null,"required_features":[]}]}"#,
+            )
+        );
+
+        let invalid: cargo_toml::Value = "[[test]]\nrequired-features = \"cli\"".parse().unwrap();
+        assert!(super::targets_to_json(&invalid).is_err());
+    }
+
     #[test]
     fn lint_priority() {
         // Test different lint priority scenarios
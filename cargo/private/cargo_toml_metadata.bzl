@@ -0,0 +1,64 @@
+"""
+Rule used to export the non-build metadata of a `Cargo.toml` as JSON.
+
+The [`[package.metadata]`](https://doc.rust-lang.org/cargo/reference/manifest.html#the-metadata-table)
+and [`[badges]`](https://doc.rust-lang.org/cargo/reference/manifest.html#the-badges-section) tables are
+exported as they are written, while the declared [targets](https://doc.rust-lang.org/cargo/reference/cargo-targets.html)
+are exported as `{"bin": [...], "example": [...], "test": [...], "bench": [...]}` where each target is
+`{"name": ..., "path": ..., "required_features": [...]}`.
+"""
+
+_COMMANDS = ["metadata", "badges", "targets"]
+
+def _extract_cargo_toml_metadata(ctx):
+    outputs = {}
+    for command in _COMMANDS:
+        output = ctx.actions.declare_file("{}.{}.json".format(ctx.label.name, command))
+
+        args = ctx.actions.args()
+        args.add("--manifest_toml={0}".format(ctx.file.manifest.path))
+        args.add(command)
+        args.add(output)
+
+        ctx.actions.run(
+            outputs = [output],
+            executable = ctx.file._cargo_toml_info,
+            inputs = [ctx.file.manifest],
+            arguments = [args],
+            mnemonic = "CargoTomlMetadata",
+            progress_message = "Reading Cargo {} of {}".format(command, ctx.attr.name),
+        )
+        outputs[command] = output
+
+    return [
+        DefaultInfo(files = depset(outputs.values())),
+        OutputGroupInfo(**{
+            command: depset([output])
+            for command, output in outputs.items()
+        }),
+    ]
+
+extract_cargo_toml_metadata = rule(
+    implementation = _extract_cargo_toml_metadata,
+    doc = """\
+Exports the `[package.metadata]` and `[badges]` tables and the declared `[[bin]]`, `[[example]]`,
+`[[test]]` and `[[bench]]` targets of a `Cargo.toml` as JSON files.
+
+The files are named `<name>.metadata.json`, `<name>.badges.json` and `<name>.targets.json` and
+are also available through the `metadata`, `badges` and `targets` output groups. Targets which
+Cargo would discover from the layout of the package, without being declared, aren't listed.
+""",
+    attrs = {
+        "manifest": attr.label(
+            allow_single_file = True,
+            mandatory = True,
+            doc = "Cargo.toml to read metadata from.",
+        ),
+        "_cargo_toml_info": attr.label(
+            allow_single_file = True,
+            executable = True,
+            default = Label("//cargo/private/cargo_toml_info:cargo_toml_info"),
+            cfg = "exec",
+        ),
+    },
+)
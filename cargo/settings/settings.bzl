@@ -3,7 +3,7 @@
 Definitions for all `@rules_rust//cargo` settings
 """
 
-load("@bazel_skylib//rules:common_settings.bzl", "bool_flag", "string_list_flag")
+load("@bazel_skylib//rules:common_settings.bzl", "bool_flag", "int_flag", "string_flag", "string_list_flag")
 
 def experimental_symlink_execroot():
     """A flag for which causes `cargo_build_script` to symlink the execroot of the action to \
@@ -47,6 +47,46 @@ def debug_std_streams_output_group():
         build_setting_default = False,
     )
 
+def audit_build_scripts():
+    """A flag which audits `cargo_build_script` targets for hermeticity.
+
+    Files and environment variables declared with `cargo::rerun-if-changed` and \
+    `cargo::rerun-if-env-changed` are checked against the inputs and environment of \
+    the action, and `OUT_DIR` is scanned for absolute exec root or sandbox paths and \
+    timestamps. The findings are written to an `audit` output group. When set to \
+    `warn` they are also printed, and when set to `error` they fail the build.
+    """
+    string_flag(
+        name = "audit_build_scripts",
+        build_setting_default = "off",
+        values = [
+            "error",
+            "off",
+            "warn",
+        ],
+    )
+
+def build_script_jobs():
+    """A flag which sets the number of jobs `cargo_build_script` targets may run in parallel.
+
+    When greater than zero, build scripts are given `NUM_JOBS` and a GNU make jobserver \
+    in `CARGO_MAKEFLAGS`, which crates like `cc` use to compile sources in parallel. \
+    The jobserver is a named fifo, so is only provided on unix platforms.
+    """
+    int_flag(
+        name = "build_script_jobs",
+        build_setting_default = 0,
+    )
+
+def build_script_reports():
+    """A flag which adds a `report` output group to `cargo_build_script` targets that contains \
+    a json report of the build script's exit status, directives, warnings and logs.
+    """
+    bool_flag(
+        name = "build_script_reports",
+        build_setting_default = False,
+    )
+
 def use_default_shell_env():
     """A flag which controls the global default of `ctx.actions.run.use_default_shell_env` for `cargo_build_script` targets.
     """
@@ -1,3 +1,8 @@
+//! Bazel labels as they appear in the generated BUILD files.
+//!
+//! This doesn't use `//util/label`: cargo_bazel is also built with Cargo,
+//! and that library is only a Bazel target without a Cargo manifest.
+
 use std::fmt::{self, Display};
 use std::path::Path;
 use std::str::FromStr;
@@ -121,9 +121,11 @@ def _clippy_aspect_impl(target, ctx):
         clippy_flags = clippy_flags + \
                        ctx.rule.attr.lint_config[LintsInfo].clippy_lint_flags + \
                        ctx.rule.attr.lint_config[LintsInfo].rustc_lint_flags
+        # Clippy lint files come last so they can re-apply the rustc lints in an order merged
+        # with the clippy ones, see `extract_cargo_lints`.
         lint_files = lint_files + \
-                     ctx.rule.attr.lint_config[LintsInfo].clippy_lint_files + \
-                     ctx.rule.attr.lint_config[LintsInfo].rustc_lint_files
+                     ctx.rule.attr.lint_config[LintsInfo].rustc_lint_files + \
+                     ctx.rule.attr.lint_config[LintsInfo].clippy_lint_files
 
     compile_inputs, out_dir, build_env_files, build_flags_files, linkstamp_outs, ambiguous_libs = collect_inputs(
         ctx,
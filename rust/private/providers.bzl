@@ -73,6 +73,8 @@ CrateGroupInfo = provider(
 BuildInfo = provider(
     doc = "A provider containing `rustc` build settings for a given Crate.",
     fields = {
+        "bin_linker_flags": "Optional[File]: file containing `BIN=FLAG` lines of linker flags for the binary `BIN`, or for all binaries if `BIN` is `*`",
+        "cdylib_linker_flags": "Optional[File]: file containing linker flags which only apply to cdylib crates",
         "compile_data": "Depset[File]: Compile data provided by the build script that was not copied into `out_dir`.",
         "dep_env": "Optinal[File]: extra build script environment varibles to be set to direct dependencies.",
         "flags": "Optional[File]: file containing additional flags to pass to rustc",
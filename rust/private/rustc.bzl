@@ -721,6 +721,7 @@ def collect_inputs(
             build_info_inputs.append(build_info.rustc_env)
         if build_info.flags:
             build_info_inputs.append(build_info.flags)
+        build_info_inputs.extend(_get_crate_type_linker_flags(build_info, crate_info))
 
     # The old default behavior was to include data files at compile time.
     # This flag controls whether to include data files in compile_data.
@@ -825,6 +826,7 @@ def construct_arguments(
         out_dir,
         build_env_files,
         build_flags_files,
+        build_info = None,
         emit = ["dep-info", "link"],
         force_all_deps_direct = False,
         add_flags_for_binary = False,
@@ -835,7 +837,8 @@ def construct_arguments(
         build_metadata = False,
         force_depend_on_objects = False,
         skip_expanding_rustc_env = False,
-        error_format = None):
+        error_format = None,
+        use_persistent_worker = False):
     """Builds an Args object containing common rustc flags
 
     Args:
@@ -855,6 +858,7 @@ def construct_arguments(
         out_dir (str): The path to the output directory for the target Crate.
         build_env_files (list): Files containing rustc environment variables, for instance from `cargo_build_script` actions.
         build_flags_files (depset): The output files of a `cargo_build_script` actions containing rustc build flags
+        build_info (BuildInfo, optional): The target Crate's build settings, used for linker flags which only apply to some crate types.
         emit (list): Values for the --emit flag to rustc.
         force_all_deps_direct (bool, optional): Whether to pass the transitive rlibs with --extern
             to the commandline as opposed to -L.
@@ -868,6 +872,7 @@ def construct_arguments(
         force_depend_on_objects (bool): Force using `.rlib` object files instead of metadata (`.rmeta`) files even if they are available.
         skip_expanding_rustc_env (bool): Whether to skip expanding CrateInfo.rustc_env_attr
         error_format (str, optional): Error format to pass to the `--error-format` command line argument. If set to None, uses the "_error_format" entry in `attr`.
+        use_persistent_worker (bool, optional): Whether the action runs in a process_wrapper persistent worker, which receives all arguments through param files.
 
     Returns:
         tuple: A tuple of the following items
@@ -890,10 +895,22 @@ def construct_arguments(
     # Wrapper args first
     process_wrapper_flags = ctx.actions.args()
 
+    # Bazel passes the param files of an action to a persistent worker and starts the
+    # worker with all other arguments, so every argument needs to be in a param file.
+    if use_persistent_worker:
+        process_wrapper_flags.set_param_file_format("multiline")
+        process_wrapper_flags.use_param_file("@%s", use_always = True)
+
     for build_env_file in build_env_files:
         process_wrapper_flags.add("--env-file", build_env_file)
 
     process_wrapper_flags.add_all(build_flags_files, before_each = "--arg-file")
+    if include_link_flags:
+        for linker_flags in _get_crate_type_linker_flags(build_info, crate_info):
+            if crate_info.type == "bin":
+                process_wrapper_flags.add("--bin-arg-file", linker_flags)
+            else:
+                process_wrapper_flags.add("--arg-file", linker_flags)
 
     # Certain rust build processes expect to find files from the environment
     # variable `$CARGO_MANIFEST_DIR`. Examples of this include pest, tera,
@@ -924,6 +941,9 @@ def construct_arguments(
 
     # Arguments for launching rustc from the process wrapper
     rustc_path = ctx.actions.args()
+    if use_persistent_worker:
+        rustc_path.set_param_file_format("multiline")
+        rustc_path.use_param_file("@%s", use_always = True)
     rustc_path.add("--")
     rustc_path.add(tool_path)
 
@@ -936,7 +956,7 @@ def construct_arguments(
     # Rustc arguments
     rustc_flags = ctx.actions.args()
     rustc_flags.set_param_file_format("multiline")
-    rustc_flags.use_param_file("@%s", use_always = False)
+    rustc_flags.use_param_file("@%s", use_always = use_persistent_worker)
     rustc_flags.add(crate_info.root)
     rustc_flags.add(crate_info.name, format = "--crate-name=%s")
     rustc_flags.add(crate_info.type, format = "--crate-type=%s")
@@ -1062,6 +1082,9 @@ def construct_arguments(
     if toolchain.llvm_cov and ctx.configuration.coverage_enabled:
         # https://doc.rust-lang.org/rustc/instrument-coverage.html
         rustc_flags.add("--codegen=instrument-coverage")
+        if toolchain._experimental_coverage_options != "off":
+            # https://doc.rust-lang.org/unstable-book/compiler-flags/coverage-options.html
+            rustc_flags.add("-Zcoverage-options={}".format(toolchain._experimental_coverage_options))
 
     if toolchain._experimental_link_std_dylib:
         rustc_flags.add("--codegen=prefer-dynamic")
@@ -1149,6 +1172,20 @@ def construct_arguments(
 
     return args, env
 
+def _add_remap_diagnostic_paths_flags(args, toolchain):
+    """Adds the process_wrapper flags rewriting the paths in rustc diagnostics.
+
+    Args:
+        args (struct): The arguments of a `Rustc` or `RustcMetadata` action, see `construct_arguments`.
+        toolchain (rust_toolchain): The current `rust_toolchain`.
+    """
+    if toolchain._experimental_remap_diagnostic_paths:
+        args.process_wrapper_flags.add("--remap-diagnostic-paths", "true")
+    args.process_wrapper_flags.add_all(
+        toolchain._experimental_remap_diagnostic_path_prefixes,
+        before_each = "--remap-diagnostic-path-prefix",
+    )
+
 def rustc_compile_action(
         *,
         ctx,
@@ -1243,6 +1280,47 @@ def rustc_compile_action(
         experimental_use_cc_common_link = experimental_use_cc_common_link,
     )
 
+    # A report of all diagnostics of the crate, written by process_wrapper.
+    diagnostics_output = None
+    if ctx.executable._process_wrapper and toolchain._experimental_diagnostics_output != "off":
+        diagnostics_output = ctx.actions.declare_file(
+            crate_info.output.basename + (".sarif" if toolchain._experimental_diagnostics_output == "sarif" else ".diagnostics.jsonl"),
+            sibling = crate_info.output,
+        )
+
+    # The machine applicable suggestions of rustc, see `//tools/rust_fix`.
+    fixes_output = None
+    if ctx.executable._process_wrapper and toolchain._experimental_fixes_output:
+        fixes_output = ctx.actions.declare_file(crate_info.output.basename + ".fixes.jsonl", sibling = crate_info.output)
+
+    # The resource usage and timings of each action, see `//tools/build_profile`.
+    resource_report = None
+    metadata_resource_report = None
+    if ctx.executable._process_wrapper and toolchain._experimental_resource_report:
+        resource_report = ctx.actions.declare_file(crate_info.output.basename + ".resources.json", sibling = crate_info.output)
+        if build_metadata:
+            metadata_resource_report = ctx.actions.declare_file(build_metadata.basename + ".resources.json", sibling = build_metadata)
+
+    # A warning and lint policy enforced by process_wrapper on the diagnostics of the crate.
+    lint_policy = toolchain._experimental_lint_policy if ctx.executable._process_wrapper else None
+    if lint_policy:
+        compile_inputs = depset([lint_policy], transitive = [compile_inputs])
+
+    # Rewrites the paths in diagnostics to point to the workspace sources.
+    remap_diagnostic_paths = bool(ctx.executable._process_wrapper) and (
+        toolchain._experimental_remap_diagnostic_paths or bool(toolchain._experimental_remap_diagnostic_path_prefixes)
+    )
+
+    # Persistent workers are keyed on the action environment, so the environment of the
+    # crate is passed in a file instead. All crates then share a single worker process.
+    # It goes first so that the environment files of build scripts take precedence.
+    use_persistent_worker = toolchain._experimental_use_process_wrapper_worker and bool(ctx.executable._process_wrapper)
+    worker_env_file = None
+    if use_persistent_worker:
+        worker_env_file = ctx.actions.declare_file(crate_info.output.basename + ".worker_env", sibling = crate_info.output)
+        build_env_files = [worker_env_file] + build_env_files
+        compile_inputs = depset([worker_env_file], transitive = [compile_inputs])
+
     # The types of rustc outputs to emit.
     # If we build metadata, we need to keep the command line of the two invocations
     # (rlib and rmeta) as similar as possible, otherwise rustc rejects the rmeta as
@@ -1275,11 +1353,24 @@ def rustc_compile_action(
         out_dir = out_dir,
         build_env_files = build_env_files,
         build_flags_files = build_flags_files,
+        build_info = build_info,
         force_all_deps_direct = force_all_deps_direct,
         stamp = stamp,
-        use_json_output = bool(build_metadata) or bool(rustc_output) or bool(rustc_rmeta_output),
+        use_json_output = bool(build_metadata) or bool(rustc_output) or bool(rustc_rmeta_output) or bool(diagnostics_output) or bool(fixes_output) or bool(lint_policy) or remap_diagnostic_paths,
         skip_expanding_rustc_env = skip_expanding_rustc_env,
+        use_persistent_worker = use_persistent_worker,
     )
+    if lint_policy:
+        args.process_wrapper_flags.add("--lint-policy", lint_policy)
+    if remap_diagnostic_paths:
+        _add_remap_diagnostic_paths_flags(args, toolchain)
+    if diagnostics_output:
+        args.process_wrapper_flags.add("--diagnostics-output", diagnostics_output)
+        args.process_wrapper_flags.add("--diagnostics-format", toolchain._experimental_diagnostics_output)
+    if fixes_output:
+        args.process_wrapper_flags.add("--fixes-output", fixes_output)
+    if resource_report:
+        args.process_wrapper_flags.add("--resource-report", resource_report)
 
     args_metadata = None
     if build_metadata:
@@ -1301,17 +1392,58 @@ def rustc_compile_action(
             out_dir = out_dir,
             build_env_files = build_env_files,
             build_flags_files = build_flags_files,
+            build_info = build_info,
             force_all_deps_direct = force_all_deps_direct,
             stamp = stamp,
             use_json_output = True,
             build_metadata = True,
+            use_persistent_worker = use_persistent_worker,
         )
+        if metadata_resource_report:
+            args_metadata.process_wrapper_flags.add("--resource-report", metadata_resource_report)
+        if lint_policy:
+            args_metadata.process_wrapper_flags.add("--lint-policy", lint_policy)
+        if remap_diagnostic_paths:
+            _add_remap_diagnostic_paths_flags(args_metadata, toolchain)
+
+        # In a persistent worker, the RustcMetadata action leaves rustc running once the
+        # metadata is emitted and the Rustc action picks up its outputs.
+        if use_persistent_worker:
+            for pipelined_args in [args, args_metadata]:
+                pipelined_args.process_wrapper_flags.add("--rustc-pipelining-key", crate_info.output.path)
+            args.process_wrapper_flags.add("--rustc-pipelined-output", crate_info.output)
 
     env = dict(ctx.configuration.default_shell_env)
 
     # this is the final list of env vars
     env.update(env_from_args)
 
+    action_env = env
+    if worker_env_file:
+        action_env = dict(ctx.configuration.default_shell_env)
+        worker_env = []
+        for key, value in env_from_args.items():
+            if "\n" in value:
+                # Environment files have one variable per line.
+                action_env[key] = value
+            else:
+                worker_env.append("{}={}\n".format(key, value))
+        ctx.actions.write(worker_env_file, "".join(worker_env))
+
+    # `supports-multiplex-sandboxing` is deliberately not set: the worker can't run a pipelined
+    # rustc process across the sandboxes of its two actions, and fails sandboxed requests.
+    execution_requirements = {}
+    if use_persistent_worker:
+        execution_requirements = {
+            "supports-multiplex-workers": "1",
+            "supports-workers": "1",
+        }
+
+    # Both actions of a pipelined compilation need to run in the same worker process.
+    metadata_execution_requirements = dict(execution_requirements)
+    if use_persistent_worker:
+        metadata_execution_requirements["worker-key-mnemonic"] = "Rustc"
+
     if hasattr(attr, "version") and attr.version != "0.0.0":
         formatted_version = " v{}".format(attr.version)
     else:
@@ -1342,6 +1474,12 @@ def rustc_compile_action(
     action_outputs = list(outputs)
     if rustc_output:
         action_outputs.append(rustc_output)
+    if diagnostics_output:
+        action_outputs.append(diagnostics_output)
+    if fixes_output:
+        action_outputs.append(fixes_output)
+    if resource_report:
+        action_outputs.append(resource_report)
 
     # Get the compilation mode for the current target.
     compilation_mode = get_compilation_mode_opts(ctx, toolchain)
@@ -1364,7 +1502,7 @@ def rustc_compile_action(
             executable = ctx.executable._process_wrapper,
             inputs = compile_inputs,
             outputs = action_outputs,
-            env = env,
+            env = action_env,
             arguments = args.all,
             mnemonic = "Rustc",
             progress_message = "Compiling Rust {} {}{} ({} files)".format(
@@ -1375,13 +1513,14 @@ def rustc_compile_action(
             ),
             toolchain = "@rules_rust//rust:toolchain_type",
             resource_set = get_rustc_resource_set(toolchain),
+            execution_requirements = execution_requirements,
         )
         if args_metadata:
             ctx.actions.run(
                 executable = ctx.executable._process_wrapper,
                 inputs = compile_inputs,
-                outputs = [build_metadata] + [x for x in [rustc_rmeta_output] if x],
-                env = env,
+                outputs = [build_metadata] + [x for x in [rustc_rmeta_output, metadata_resource_report] if x],
+                env = action_env,
                 arguments = args_metadata.all,
                 mnemonic = "RustcMetadata",
                 progress_message = "Compiling Rust metadata {} {}{} ({} files)".format(
@@ -1391,6 +1530,7 @@ def rustc_compile_action(
                     len(crate_info.srcs.to_list()),
                 ),
                 toolchain = "@rules_rust//rust:toolchain_type",
+                execution_requirements = metadata_execution_requirements,
             )
     elif hasattr(ctx.executable, "_bootstrap_process_wrapper"):
         # Run without process_wrapper
@@ -1588,6 +1728,12 @@ def rustc_compile_action(
             output_group_info["rustc_rmeta_output"] = depset([rustc_rmeta_output])
     if rustc_output:
         output_group_info["rustc_output"] = depset([rustc_output])
+    if diagnostics_output:
+        output_group_info["rust_diagnostics"] = depset([diagnostics_output])
+    if fixes_output:
+        output_group_info["rust_fixes"] = depset([fixes_output])
+    if resource_report:
+        output_group_info["rust_resource_reports"] = depset([x for x in [resource_report, metadata_resource_report] if x])
 
     if output_group_info:
         providers.append(OutputGroupInfo(**output_group_info))
@@ -1823,6 +1969,26 @@ def add_edition_flags(args, crate):
     if crate.edition != "2015":
         args.add(crate.edition, format = "--edition=%s")
 
+def _get_crate_type_linker_flags(build_info, crate_info):
+    """Collects the build script linker flag files which only apply to the type of the given crate.
+
+    Args:
+        build_info (BuildInfo): The BuildInfo provider from the target Crate's set of inputs.
+        crate_info (CrateInfo): The CrateInfo provider of the target crate.
+
+    Returns:
+        list: Files of `BIN=FLAG` lines for binaries, or of flags for cdylibs.
+    """
+    if not build_info:
+        return []
+    if crate_info.type == "bin" and not crate_info.is_test:
+        linker_flags = getattr(build_info, "bin_linker_flags", None)
+    elif crate_info.type == "cdylib":
+        linker_flags = getattr(build_info, "cdylib_linker_flags", None)
+    else:
+        linker_flags = None
+    return [linker_flags] if linker_flags else []
+
 def _create_extra_input_args(build_info, dep_info, include_link_flags = True):
     """Gather additional input arguments from transitive dependencies
 
@@ -36,10 +36,12 @@ use std::fs;
 use std::io;
 use std::path::Path;
 use std::path::PathBuf;
+use std::process::Command;
 
 const RUNFILES_DIR_ENV_VAR: &str = "RUNFILES_DIR";
 const MANIFEST_FILE_ENV_VAR: &str = "RUNFILES_MANIFEST_FILE";
 const TEST_SRCDIR_ENV_VAR: &str = "TEST_SRCDIR";
+const JAVA_RUNFILES_ENV_VAR: &str = "JAVA_RUNFILES";
 
 #[macro_export]
 macro_rules! rlocation {
@@ -137,11 +139,45 @@ enum Mode {
 
     /// Runfiles represented as a mapping of `rlocationpath` to real paths indicated
     /// by the `RUNFILES_MANIFEST_FILE` environment variable.
-    ManifestBased(HashMap<PathBuf, PathBuf>),
+    ManifestBased {
+        manifest_file: PathBuf,
+        path_mapping: HashMap<PathBuf, PathBuf>,
+    },
 }
 
 type RepoMappingKey = (String, String);
-type RepoMapping = HashMap<RepoMappingKey, String>;
+
+/// The mapping of apparent repo names to canonical ones, by source repo, of a
+/// `_repo_mapping` file.
+#[derive(Debug, Default, PartialEq)]
+struct RepoMapping {
+    /// Mappings for a single source repo.
+    exact: HashMap<RepoMappingKey, String>,
+
+    /// Mappings for all the source repos starting with a prefix, written
+    /// `<prefix>*` in compact repo mappings, in the order of the file. These
+    /// only apply when the source repo has no exact mapping.
+    prefixed: Vec<(String, String, String)>,
+}
+
+impl RepoMapping {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn get(&self, source_repo: &str, apparent_name: &str) -> Option<&str> {
+        let key = (source_repo.to_owned(), apparent_name.to_owned());
+        if let Some(canonical_name) = self.exact.get(&key) {
+            return Some(canonical_name);
+        }
+        self.prefixed
+            .iter()
+            .find(|(prefix, apparent, _)| {
+                apparent == apparent_name && source_repo.starts_with(prefix.as_str())
+            })
+            .map(|(_, _, canonical_name)| canonical_name.as_str())
+    }
+}
 
 /// An interface for accessing to [Bazel runfiles](https://bazel.build/extending/rules#runfiles).
 #[derive(Debug)]
@@ -181,16 +217,52 @@ impl Runfiles {
     fn create_manifest_based(manifest_path: &Path) -> Result<Mode> {
         let manifest_content = std::fs::read_to_string(manifest_path)
             .map_err(RunfilesError::RunfilesManifestIoError)?;
-        let path_mapping = manifest_content
-            .lines()
-            .flat_map(|line| {
-                let pair = line
-                    .split_once(' ')
-                    .ok_or(RunfilesError::RunfilesManifestInvalidFormat)?;
-                Ok::<(PathBuf, PathBuf), RunfilesError>((pair.0.into(), pair.1.into()))
-            })
-            .collect::<HashMap<_, _>>();
-        Ok(Mode::ManifestBased(path_mapping))
+        Ok(Mode::ManifestBased {
+            manifest_file: manifest_path.to_path_buf(),
+            path_mapping: parse_manifest(&manifest_content)?,
+        })
+    }
+
+    /// Returns the environment variables which let a child process find the
+    /// same runfiles, like `RUNFILES_DIR` and `RUNFILES_MANIFEST_FILE`.
+    ///
+    /// Relative paths are made absolute, so the child process may run in
+    /// another directory. For manifest based runfiles, `RUNFILES_DIR` and
+    /// `JAVA_RUNFILES` are only set if the runfiles directory next to the
+    /// manifest exists.
+    pub fn env_vars(&self) -> Vec<(&'static str, PathBuf)> {
+        let runfiles_dir = match &self.mode {
+            Mode::DirectoryBased(runfiles_dir) => Some(absolute(runfiles_dir)),
+            Mode::ManifestBased { manifest_file, .. } => {
+                runfiles_dir_of_manifest(&absolute(manifest_file)).filter(|dir| dir.is_dir())
+            }
+        };
+
+        let mut env_vars = Vec::new();
+        if let Mode::ManifestBased { manifest_file, .. } = &self.mode {
+            env_vars.push((MANIFEST_FILE_ENV_VAR, absolute(manifest_file)));
+        }
+        if let Some(runfiles_dir) = runfiles_dir {
+            env_vars.push((RUNFILES_DIR_ENV_VAR, runfiles_dir.clone()));
+            env_vars.push((JAVA_RUNFILES_ENV_VAR, runfiles_dir));
+        }
+        env_vars
+    }
+
+    /// Sets the environment variables of [Runfiles::env_vars] on `command`,
+    /// so the process it spawns can find the same runfiles.
+    ///
+    /// Those which don't apply to this [Runfiles] are removed from the
+    /// environment of `command`, rather than inherited.
+    pub fn configure_command<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        for env_var in [
+            MANIFEST_FILE_ENV_VAR,
+            RUNFILES_DIR_ENV_VAR,
+            JAVA_RUNFILES_ENV_VAR,
+        ] {
+            command.env_remove(env_var);
+        }
+        command.envs(self.env_vars())
     }
 
     /// Returns the runtime path of a runfile.
@@ -225,8 +297,7 @@ impl Runfiles {
             Some((name, alias)) => (name, Some(alias)),
             None => (path_str, None),
         };
-        let key: (String, String) = (source_repo.into(), repo_alias.into());
-        if let Some(target_repo_directory) = self.repo_mapping.get(&key) {
+        if let Some(target_repo_directory) = self.canonical_repo_name(repo_alias, source_repo) {
             match repo_path {
                 Some(repo_path) => {
                     raw_rlocation(&self.mode, format!("{target_repo_directory}/{repo_path}"))
@@ -237,14 +308,136 @@ impl Runfiles {
             raw_rlocation(&self.mode, path)
         }
     }
+
+    /// Returns the canonical name of the repo `apparent_name` refers to from
+    /// `source_repo`, or `None` if there's no mapping for it.
+    ///
+    /// `source_repo` is a canonical repo name, like the `REPOSITORY_NAME` the
+    /// `rlocation!` macro uses. The main repo's canonical name is empty.
+    pub fn canonical_repo_name(&self, apparent_name: &str, source_repo: &str) -> Option<&str> {
+        self.repo_mapping.get(source_repo, apparent_name)
+    }
+
+    /// Returns the entries of the repo mapping, as the source repo, apparent
+    /// name and canonical name of each.
+    ///
+    /// The source repo of entries which apply to all the repos starting with
+    /// a prefix is the prefix followed by `*`. Entries for a single source repo
+    /// are sorted and come first, followed by prefixed ones in precedence order.
+    pub fn repo_mapping(&self) -> Vec<(String, &str, &str)> {
+        let mut exact: Vec<_> = self
+            .repo_mapping
+            .exact
+            .iter()
+            .map(|((source, apparent), canonical)| {
+                (source.clone(), apparent.as_str(), canonical.as_str())
+            })
+            .collect();
+        exact.sort_unstable();
+        let prefixed = self
+            .repo_mapping
+            .prefixed
+            .iter()
+            .map(|(prefix, apparent, canonical)| {
+                (format!("{prefix}*"), apparent.as_str(), canonical.as_str())
+            });
+        exact.into_iter().chain(prefixed).collect()
+    }
 }
 
 fn raw_rlocation(mode: &Mode, path: impl AsRef<Path>) -> Option<PathBuf> {
     let path = path.as_ref();
     match mode {
         Mode::DirectoryBased(runfiles_dir) => Some(runfiles_dir.join(path)),
-        Mode::ManifestBased(path_mapping) => path_mapping.get(path).cloned(),
+        Mode::ManifestBased { path_mapping, .. } => {
+            if let Some(real_path) = path_mapping.get(path) {
+                return Some(real_path.clone());
+            }
+            // Runfiles under a directory which is itself a runfile aren't listed in the
+            // manifest, only the directory is. Look up the closest ancestor of the path
+            // instead and append the rest of the path to it.
+            path.ancestors().skip(1).find_map(|ancestor| {
+                let real_ancestor = path_mapping.get(ancestor)?;
+                let relative = path.strip_prefix(ancestor).ok()?;
+                Some(real_ancestor.join(relative))
+            })
+        }
+    }
+}
+
+/// Returns `path` relative to the current directory if it isn't absolute.
+fn absolute(path: &Path) -> PathBuf {
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+    env::current_dir()
+        .expect("The current working directory is always expected to be set.")
+        .join(path)
+}
+
+/// Returns the runfiles directory next to a manifest, which is either named
+/// `MANIFEST` in the directory or `<binary>.runfiles_manifest` next to it.
+fn runfiles_dir_of_manifest(manifest_file: &Path) -> Option<PathBuf> {
+    let file_name = manifest_file.file_name()?.to_str()?;
+    if file_name == "MANIFEST" {
+        return manifest_file.parent().map(Path::to_path_buf);
     }
+    let binary = file_name.strip_suffix(".runfiles_manifest")?;
+    Some(manifest_file.with_file_name(format!("{binary}.runfiles")))
+}
+
+/// Replaces the escape sequences of a manifest line: `\n` and `\b` for a newline and
+/// a backslash, and `\s` for a space when `unescape_spaces` is set.
+fn unescape_manifest_path(path: &str, unescape_spaces: bool) -> String {
+    let mut unescaped = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('b') => unescaped.push('\\'),
+            Some('s') if unescape_spaces => unescaped.push(' '),
+            Some(other) => {
+                unescaped.push('\\');
+                unescaped.push(other);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
+
+/// Parses the mapping of `rlocationpath` to real paths of a runfiles manifest.
+///
+/// Each line is the `rlocationpath`, a space and the real path. Lines starting with a
+/// space are escaped, as Bazel does when a path contains a space, newline or backslash:
+/// in the `rlocationpath` spaces are written `\s`, and in both paths newlines are
+/// written `\n` and backslashes `\b`.
+fn parse_manifest(content: &str) -> Result<HashMap<PathBuf, PathBuf>> {
+    content
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| match line.strip_prefix(' ') {
+            Some(escaped) => {
+                let (link, target) = escaped
+                    .split_once(' ')
+                    .ok_or(RunfilesError::RunfilesManifestInvalidFormat)?;
+                Ok((
+                    unescape_manifest_path(link, true).into(),
+                    unescape_manifest_path(target, false).into(),
+                ))
+            }
+            None => {
+                let (link, target) = line
+                    .split_once(' ')
+                    .ok_or(RunfilesError::RunfilesManifestInvalidFormat)?;
+                Ok((link.into(), target.into()))
+            }
+        })
+        .collect()
 }
 
 fn parse_repo_mapping(path: PathBuf) -> Result<RepoMapping> {
@@ -254,11 +447,25 @@ fn parse_repo_mapping(path: PathBuf) -> Result<RepoMapping> {
         .map_err(RunfilesError::RepoMappingIoError)?
         .lines()
     {
+        if line.is_empty() {
+            continue;
+        }
         let parts: Vec<&str> = line.splitn(3, ',').collect();
         if parts.len() < 3 {
             return Err(RunfilesError::RepoMappingInvalidFormat);
         }
-        repo_mapping.insert((parts[0].into(), parts[1].into()), parts[2].into());
+        match parts[0].strip_suffix('*') {
+            Some(prefix) => {
+                repo_mapping
+                    .prefixed
+                    .push((prefix.into(), parts[1].into(), parts[2].into()))
+            }
+            None => {
+                repo_mapping
+                    .exact
+                    .insert((parts[0].into(), parts[1].into()), parts[2].into());
+            }
+        }
     }
 
     Ok(repo_mapping)
@@ -541,7 +748,10 @@ mod test {
         let mut path_mapping = HashMap::new();
         path_mapping.insert("a/b".into(), "c/d".into());
         let r = Runfiles {
-            mode: Mode::ManifestBased(path_mapping),
+            mode: Mode::ManifestBased {
+                manifest_file: PathBuf::from("MANIFEST"),
+                path_mapping,
+            },
             repo_mapping: RepoMapping::new(),
         };
 
@@ -553,13 +763,200 @@ mod test {
         let mut path_mapping = HashMap::new();
         path_mapping.insert("a/b".into(), "c/d".into());
         let r = Runfiles {
-            mode: Mode::ManifestBased(path_mapping),
+            mode: Mode::ManifestBased {
+                manifest_file: PathBuf::from("MANIFEST"),
+                path_mapping,
+            },
             repo_mapping: RepoMapping::new(),
         };
 
         assert_eq!(r.rlocation("does/not/exist"), None);
     }
 
+    #[test]
+    fn test_manifest_based_directory_prefix() {
+        let mut path_mapping = HashMap::new();
+        path_mapping.insert("repo/dir".into(), "/real/dir".into());
+        path_mapping.insert("repo/dir/nested/file".into(), "/elsewhere/file".into());
+        let r = Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: PathBuf::from("MANIFEST"),
+                path_mapping,
+            },
+            repo_mapping: RepoMapping::new(),
+        };
+
+        assert_eq!(r.rlocation("repo/dir"), Some(PathBuf::from("/real/dir")));
+        assert_eq!(
+            r.rlocation("repo/dir/sub/file.txt"),
+            Some(PathBuf::from("/real/dir/sub/file.txt"))
+        );
+        // Exact matches take precedence over the directory.
+        assert_eq!(
+            r.rlocation("repo/dir/nested/file"),
+            Some(PathBuf::from("/elsewhere/file"))
+        );
+        assert_eq!(r.rlocation("repo/other"), None);
+        assert_eq!(r.rlocation("repo"), None);
+    }
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(concat!(
+            "repo/plain /real/plain\n",
+            "repo/empty \n",
+            "repo/target with spaces /real/path with spaces\n",
+            " repo/with\\sspace /real/with space\n",
+            " repo/with\\nnewline\\bbackslash /real/with\\nnewline\\bbackslash\n",
+            " repo/unknown\\xescape /real/target\\sstays\n",
+            "\n",
+        ))
+        .unwrap();
+
+        let expected: HashMap<PathBuf, PathBuf> = [
+            ("repo/plain", "/real/plain"),
+            ("repo/empty", ""),
+            ("repo/target", "with spaces /real/path with spaces"),
+            ("repo/with space", "/real/with space"),
+            (
+                "repo/with\nnewline\\backslash",
+                "/real/with\nnewline\\backslash",
+            ),
+            ("repo/unknown\\xescape", "/real/target\\sstays"),
+        ]
+        .iter()
+        .map(|(link, target)| (PathBuf::from(link), PathBuf::from(target)))
+        .collect();
+        assert_eq!(manifest, expected);
+
+        assert_eq!(
+            parse_manifest("no_separator"),
+            Err(RunfilesError::RunfilesManifestInvalidFormat)
+        );
+        assert_eq!(
+            parse_manifest(" escaped\\sno_separator"),
+            Err(RunfilesError::RunfilesManifestInvalidFormat)
+        );
+    }
+
+    #[test]
+    fn test_env_vars_directory_based() {
+        let runfiles_dir = make_runfiles_like_dir("test_env_vars_directory_based");
+        let r = Runfiles {
+            mode: Mode::DirectoryBased(PathBuf::from(&runfiles_dir)),
+            repo_mapping: RepoMapping::new(),
+        };
+
+        assert_eq!(
+            r.env_vars(),
+            [
+                (RUNFILES_DIR_ENV_VAR, PathBuf::from(&runfiles_dir)),
+                (JAVA_RUNFILES_ENV_VAR, PathBuf::from(&runfiles_dir)),
+            ]
+        );
+
+        let mut command = Command::new("true");
+        command.env(MANIFEST_FILE_ENV_VAR, "stale/MANIFEST");
+        r.configure_command(&mut command);
+        let envs: HashMap<_, _> = command.get_envs().collect();
+        assert_eq!(envs[OsStr::new(MANIFEST_FILE_ENV_VAR)], None);
+        assert_eq!(
+            envs[OsStr::new(RUNFILES_DIR_ENV_VAR)],
+            Some(OsStr::new(&runfiles_dir))
+        );
+        assert_eq!(
+            envs[OsStr::new(JAVA_RUNFILES_ENV_VAR)],
+            Some(OsStr::new(&runfiles_dir))
+        );
+    }
+
+    #[test]
+    fn test_env_vars_manifest_based() {
+        let temp_dir = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap())
+            .join("test_env_vars_manifest_based");
+        let runfiles_dir = temp_dir.join("tool.runfiles");
+        std::fs::create_dir_all(&runfiles_dir).unwrap();
+        let manifest_file = temp_dir.join("tool.runfiles_manifest");
+
+        let r = Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: manifest_file.clone(),
+                path_mapping: HashMap::new(),
+            },
+            repo_mapping: RepoMapping::new(),
+        };
+        assert_eq!(
+            r.env_vars(),
+            [
+                (MANIFEST_FILE_ENV_VAR, manifest_file.clone()),
+                (RUNFILES_DIR_ENV_VAR, runfiles_dir.clone()),
+                (JAVA_RUNFILES_ENV_VAR, runfiles_dir.clone()),
+            ]
+        );
+
+        let mut command = Command::new("true");
+        r.configure_command(&mut command);
+        let envs: HashMap<_, _> = command.get_envs().collect();
+        assert_eq!(
+            envs[OsStr::new(MANIFEST_FILE_ENV_VAR)],
+            Some(manifest_file.as_os_str())
+        );
+        assert_eq!(
+            envs[OsStr::new(RUNFILES_DIR_ENV_VAR)],
+            Some(runfiles_dir.as_os_str())
+        );
+
+        // A `MANIFEST` is within the runfiles directory it describes.
+        let manifest_file = temp_dir.join("MANIFEST");
+        let r = Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: manifest_file.clone(),
+                path_mapping: HashMap::new(),
+            },
+            repo_mapping: RepoMapping::new(),
+        };
+        assert_eq!(
+            r.env_vars(),
+            [
+                (MANIFEST_FILE_ENV_VAR, manifest_file),
+                (RUNFILES_DIR_ENV_VAR, temp_dir.clone()),
+                (JAVA_RUNFILES_ENV_VAR, temp_dir.clone()),
+            ]
+        );
+
+        // Without a runfiles directory, only the manifest is exported.
+        let r = Runfiles {
+            mode: Mode::ManifestBased {
+                manifest_file: temp_dir.join("other.runfiles_manifest"),
+                path_mapping: HashMap::new(),
+            },
+            repo_mapping: RepoMapping::new(),
+        };
+        assert_eq!(
+            r.env_vars(),
+            [(
+                MANIFEST_FILE_ENV_VAR,
+                temp_dir.join("other.runfiles_manifest")
+            )]
+        );
+    }
+
+    #[test]
+    fn test_runfiles_dir_of_manifest() {
+        assert_eq!(
+            runfiles_dir_of_manifest(Path::new("/out/bin/tool.runfiles/MANIFEST")),
+            Some(PathBuf::from("/out/bin/tool.runfiles"))
+        );
+        assert_eq!(
+            runfiles_dir_of_manifest(Path::new("/out/bin/tool.runfiles_manifest")),
+            Some(PathBuf::from("/out/bin/tool.runfiles"))
+        );
+        assert_eq!(
+            runfiles_dir_of_manifest(Path::new("/out/manifest.txt")),
+            None
+        );
+    }
+
     fn dedent(text: &str) -> String {
         text.lines()
             .map(|l| l.trim_start())
@@ -591,43 +988,123 @@ mod test {
 
         assert_eq!(
             parse_repo_mapping(valid),
-            Ok(RepoMapping::from([
-                (
-                    ("local_config_xcode".to_owned(), "rules_rust".to_owned()),
-                    "rules_rust".to_owned()
-                ),
-                (
-                    ("platforms".to_owned(), "rules_rust".to_owned()),
-                    "rules_rust".to_owned()
-                ),
-                (
+            Ok(RepoMapping {
+                exact: HashMap::from([
                     (
-                        "rust_darwin_aarch64__aarch64-apple-darwin__stable_tools".to_owned(),
+                        ("local_config_xcode".to_owned(), "rules_rust".to_owned()),
                         "rules_rust".to_owned()
                     ),
-                    "rules_rust".to_owned()
-                ),
-                (
-                    ("rules_rust_tinyjson".to_owned(), "rules_rust".to_owned()),
-                    "rules_rust".to_owned()
-                ),
-                (
-                    ("local_config_sh".to_owned(), "rules_rust".to_owned()),
-                    "rules_rust".to_owned()
-                ),
+                    (
+                        ("platforms".to_owned(), "rules_rust".to_owned()),
+                        "rules_rust".to_owned()
+                    ),
+                    (
+                        (
+                            "rust_darwin_aarch64__aarch64-apple-darwin__stable_tools".to_owned(),
+                            "rules_rust".to_owned()
+                        ),
+                        "rules_rust".to_owned()
+                    ),
+                    (
+                        ("rules_rust_tinyjson".to_owned(), "rules_rust".to_owned()),
+                        "rules_rust".to_owned()
+                    ),
+                    (
+                        ("local_config_sh".to_owned(), "rules_rust".to_owned()),
+                        "rules_rust".to_owned()
+                    ),
+                    (
+                        ("bazel_tools".to_owned(), "__main__".to_owned()),
+                        "rules_rust".to_owned()
+                    ),
+                    (
+                        ("local_config_cc".to_owned(), "rules_rust".to_owned()),
+                        "rules_rust".to_owned()
+                    ),
+                    (
+                        ("".to_owned(), "rules_rust".to_owned()),
+                        "rules_rust".to_owned()
+                    )
+                ]),
+                prefixed: Vec::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_compact_repo_mapping() {
+        let temp_dir = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap());
+        std::fs::create_dir_all(&temp_dir).unwrap();
+
+        let compact = temp_dir.join("test_parse_compact_repo_mapping.txt");
+        std::fs::write(
+            &compact,
+            dedent(
+                r#",my_module,_main
+            ,my_protobuf,protobuf+3.19.2
+            ,my_workspace,_main
+            my_module++ext+*,my_module,_main
+            my_module++ext+*,repo1,my_module++ext+repo1
+            my_module++ext+repo1,repo1,my_module++ext+override
+            protobuf+*,protobuf,protobuf+3.19.2
+            "#,
+            ),
+        )
+        .unwrap();
+
+        let r = Runfiles {
+            mode: Mode::DirectoryBased(PathBuf::from("/runfiles")),
+            repo_mapping: parse_repo_mapping(compact).unwrap(),
+        };
+
+        assert_eq!(
+            r.canonical_repo_name("my_protobuf", ""),
+            Some("protobuf+3.19.2")
+        );
+        assert_eq!(
+            r.canonical_repo_name("repo1", "my_module++ext+repo2"),
+            Some("my_module++ext+repo1")
+        );
+        assert_eq!(
+            r.canonical_repo_name("my_module", "my_module++ext+repo2"),
+            Some("_main")
+        );
+        assert_eq!(
+            r.canonical_repo_name("protobuf", "protobuf+3.19.2"),
+            Some("protobuf+3.19.2")
+        );
+        // Exact mappings take precedence over prefixed ones.
+        assert_eq!(
+            r.canonical_repo_name("repo1", "my_module++ext+repo1"),
+            Some("my_module++ext+override")
+        );
+        assert_eq!(r.canonical_repo_name("repo1", "other+"), None);
+        assert_eq!(r.canonical_repo_name("unknown", "protobuf+3.19.2"), None);
+
+        assert_eq!(
+            r.rlocation_from("repo1/foo/bar.txt", "my_module++ext+repo3"),
+            Some(PathBuf::from("/runfiles/my_module++ext+repo1/foo/bar.txt"))
+        );
+
+        assert_eq!(
+            r.repo_mapping(),
+            [
+                ("".to_owned(), "my_module", "_main"),
+                ("".to_owned(), "my_protobuf", "protobuf+3.19.2"),
+                ("".to_owned(), "my_workspace", "_main"),
                 (
-                    ("bazel_tools".to_owned(), "__main__".to_owned()),
-                    "rules_rust".to_owned()
+                    "my_module++ext+repo1".to_owned(),
+                    "repo1",
+                    "my_module++ext+override"
                 ),
+                ("my_module++ext+*".to_owned(), "my_module", "_main"),
                 (
-                    ("local_config_cc".to_owned(), "rules_rust".to_owned()),
-                    "rules_rust".to_owned()
+                    "my_module++ext+*".to_owned(),
+                    "repo1",
+                    "my_module++ext+repo1"
                 ),
-                (
-                    ("".to_owned(), "rules_rust".to_owned()),
-                    "rules_rust".to_owned()
-                )
-            ]))
+                ("protobuf+*".to_owned(), "protobuf", "protobuf+3.19.2"),
+            ]
         );
     }
 
@@ -8,6 +8,7 @@ load(
     "bool_flag",
     "int_flag",
     "string_flag",
+    "string_list_flag",
 )
 load(
     "//rust/private:clippy.bzl",
@@ -196,6 +197,126 @@ def experimental_use_coverage_metadata_files():
         build_setting_default = True,
     )
 
+def experimental_coverage_options():
+    """A flag to instrument crates for more kinds of coverage when running `bazel coverage`.
+
+    Supported values are:
+    - `off`: Only line and region coverage is collected.
+    - `branch`: Branch coverage is collected as well.
+    - `mcdc`: Branch and MC/DC coverage are collected as well.
+
+    The value is passed to rustc as `-Zcoverage-options`, which requires a nightly toolchain.
+    It has no effect for toolchains without `llvm_cov`.
+    """
+    string_flag(
+        name = "experimental_coverage_options",
+        build_setting_default = "off",
+        values = [
+            "branch",
+            "mcdc",
+            "off",
+        ],
+    )
+
+def experimental_diagnostics_output():
+    """A flag to write a report of the diagnostics of each crate as an output of its `Rustc` action.
+
+    Supported values are:
+    - `off`: No report is written.
+    - `jsonl`: One normalized json object per diagnostic, in `<crate output>.diagnostics.jsonl`.
+    - `sarif`: A SARIF 2.1.0 log, in `<crate output>.sarif`.
+
+    The reports are available in the `rust_diagnostics` output group.
+    """
+    string_flag(
+        name = "experimental_diagnostics_output",
+        build_setting_default = "off",
+        values = [
+            "jsonl",
+            "off",
+            "sarif",
+        ],
+    )
+
+def experimental_fixes_output():
+    """A flag to write the machine applicable suggestions of rustc for each crate to \
+    `<crate output>.fixes.jsonl`, which `//tools/rust_fix` applies to the sources.
+
+    The files are available in the `rust_fixes` output group.
+    """
+    bool_flag(
+        name = "experimental_fixes_output",
+        build_setting_default = False,
+    )
+
+def experimental_lint_policy():
+    """A label to a json file with a warning and lint policy enforced on the diagnostics of each crate.
+
+    The policy can suppress warnings, promote them to errors or limit their number, for all crates
+    or only for first-party or external ones. See `util/process_wrapper/policy.rs` for the format.
+    By default, no policy is enforced.
+    """
+    native.label_flag(
+        name = "experimental_lint_policy",
+        build_setting_default = ":no_lint_policy",
+    )
+
+    native.filegroup(
+        name = "no_lint_policy",
+        srcs = [],
+    )
+
+def experimental_remap_diagnostic_paths():
+    """A flag to rewrite the paths in rustc diagnostics relative to the workspace.
+
+    The exec root is stripped and sources symlinked into `bazel-out` are mapped back to the source
+    tree, so that editors and terminals can resolve the paths.
+    """
+    bool_flag(
+        name = "experimental_remap_diagnostic_paths",
+        build_setting_default = False,
+    )
+
+def experimental_remap_diagnostic_path_prefixes():
+    """A list of `FROM=TO` mappings rewriting the paths in rustc diagnostics starting with `FROM`.
+
+    `${pwd}` is replaced with the exec root on both sides. The last matching mapping is used.
+    """
+    string_list_flag(
+        name = "experimental_remap_diagnostic_path_prefixes",
+        build_setting_default = [],
+    )
+
+def experimental_resource_report():
+    """A flag to write the wall time, CPU time, peak memory usage and rustc timings of each `Rustc` \
+    and `RustcMetadata` action to a `.resources.json` file next to its output.
+
+    The reports are available in the `rust_resource_reports` output group and can be merged into a
+    build profile with `//tools/build_profile`.
+    """
+    bool_flag(
+        name = "experimental_resource_report",
+        build_setting_default = False,
+    )
+
+def experimental_use_process_wrapper_worker():
+    """A flag to run `Rustc` actions in process_wrapper as a multiplex persistent worker.
+
+    The arguments of each action are passed in param files and the environment of each crate in
+    an environment file, so that all crates share a single worker process. Combined with
+    `pipelined_compilation`, the `RustcMetadata` and `Rustc` actions of a library are served by a
+    single rustc process.
+
+    The worker doesn't support multiplex sandboxing, as both actions of a pipelined compilation
+    share a rustc process but would run in different sandboxes. `Rustc` actions therefore don't
+    set `supports-multiplex-sandboxing`, and `--experimental_worker_multiplex_sandboxing` runs them
+    unsandboxed.
+    """
+    bool_flag(
+        name = "experimental_use_process_wrapper_worker",
+        build_setting_default = False,
+    )
+
 def toolchain_generated_sysroot():
     """A flag to set rustc --sysroot flag to the sysroot generated by rust_toolchain."""
     bool_flag(
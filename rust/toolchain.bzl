@@ -740,10 +740,18 @@ def _rust_toolchain_impl(ctx):
         _rename_first_party_crates = rename_first_party_crates,
         _third_party_dir = third_party_dir,
         _pipelined_compilation = pipelined_compilation,
+        _experimental_coverage_options = ctx.attr._experimental_coverage_options[BuildSettingInfo].value,
+        _experimental_diagnostics_output = ctx.attr._experimental_diagnostics_output[BuildSettingInfo].value,
+        _experimental_fixes_output = ctx.attr._experimental_fixes_output[BuildSettingInfo].value,
         _experimental_link_std_dylib = _experimental_link_std_dylib(ctx),
+        _experimental_lint_policy = _experimental_lint_policy(ctx),
+        _experimental_remap_diagnostic_path_prefixes = ctx.attr._experimental_remap_diagnostic_path_prefixes[BuildSettingInfo].value,
+        _experimental_remap_diagnostic_paths = ctx.attr._experimental_remap_diagnostic_paths[BuildSettingInfo].value,
+        _experimental_resource_report = ctx.attr._experimental_resource_report[BuildSettingInfo].value,
         _experimental_use_cc_common_link = _experimental_use_cc_common_link(ctx),
         _experimental_use_global_allocator = experimental_use_global_allocator,
         _experimental_use_coverage_metadata_files = ctx.attr._experimental_use_coverage_metadata_files[BuildSettingInfo].value,
+        _experimental_use_process_wrapper_worker = ctx.attr._experimental_use_process_wrapper_worker[BuildSettingInfo].value,
         _incompatible_change_rust_test_compilation_output_directory = ctx.attr._incompatible_change_rust_test_compilation_output_directory[IncompatibleFlagInfo].enabled,
         _toolchain_generated_sysroot = ctx.attr._toolchain_generated_sysroot[BuildSettingInfo].value,
         _incompatible_do_not_include_data_in_compile_data = ctx.attr._incompatible_do_not_include_data_in_compile_data[IncompatibleFlagInfo].enabled,
@@ -757,6 +765,12 @@ def _rust_toolchain_impl(ctx):
         make_variable_info,
     ]
 
+def _experimental_lint_policy(ctx):
+    policy = ctx.files._experimental_lint_policy
+    if len(policy) > 1:
+        fail("//rust/settings:experimental_lint_policy must point to a single file, got {}".format(policy))
+    return policy[0] if policy else None
+
 def _experimental_link_std_dylib(ctx):
     return not is_exec_configuration(ctx) and \
            ctx.attr.experimental_link_std_dylib[BuildSettingInfo].value and \
@@ -942,6 +956,28 @@ rust_toolchain = rule(
         "_codegen_units": attr.label(
             default = Label("//rust/settings:codegen_units"),
         ),
+        "_experimental_coverage_options": attr.label(
+            default = Label("//rust/settings:experimental_coverage_options"),
+        ),
+        "_experimental_diagnostics_output": attr.label(
+            default = Label("//rust/settings:experimental_diagnostics_output"),
+        ),
+        "_experimental_fixes_output": attr.label(
+            default = Label("//rust/settings:experimental_fixes_output"),
+        ),
+        "_experimental_lint_policy": attr.label(
+            default = Label("//rust/settings:experimental_lint_policy"),
+            allow_files = True,
+        ),
+        "_experimental_remap_diagnostic_path_prefixes": attr.label(
+            default = Label("//rust/settings:experimental_remap_diagnostic_path_prefixes"),
+        ),
+        "_experimental_remap_diagnostic_paths": attr.label(
+            default = Label("//rust/settings:experimental_remap_diagnostic_paths"),
+        ),
+        "_experimental_resource_report": attr.label(
+            default = Label("//rust/settings:experimental_resource_report"),
+        ),
         "_experimental_use_allocator_libraries_with_mangled_symbols_setting": attr.label(
             default = Label("//rust/settings:experimental_use_allocator_libraries_with_mangled_symbols"),
             providers = [BuildSettingInfo],
@@ -960,6 +996,9 @@ rust_toolchain = rule(
                 "This flag is only relevant when used together with --@rules_rust//rust/settings:experimental_use_global_allocator."
             ),
         ),
+        "_experimental_use_process_wrapper_worker": attr.label(
+            default = Label("//rust/settings:experimental_use_process_wrapper_worker"),
+        ),
         "_incompatible_change_rust_test_compilation_output_directory": attr.label(
             default = Label("//rust/settings:incompatible_change_rust_test_compilation_output_directory"),
         ),
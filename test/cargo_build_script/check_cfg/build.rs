@@ -0,0 +1,6 @@
+//! A build.rs script which declares the cfgs it sets for the consuming crate.
+
+fn main() {
+    println!("cargo::rustc-check-cfg=cfg(build_rs_checked_cfg, values(\"on\", \"off\"))");
+    println!("cargo::rustc-cfg=build_rs_checked_cfg=\"on\"");
+}
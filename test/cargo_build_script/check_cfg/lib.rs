@@ -0,0 +1,16 @@
+//! Consumer of build.rs declared cfgs. Any cfg rustc doesn't expect fails the build.
+
+#![deny(unexpected_cfgs)]
+
+#[cfg(build_rs_checked_cfg = "on")]
+pub const DATA: &str = "on";
+
+#[cfg(not(build_rs_checked_cfg = "on"))]
+pub const DATA: &str = "off";
+
+/// Crate features are accepted as well.
+#[cfg(feature = "default")]
+pub const FEATURE: bool = true;
+
+#[cfg(not(feature = "default"))]
+pub const FEATURE: bool = false;
@@ -0,0 +1,9 @@
+#[test]
+pub fn test_data() {
+    assert_eq!(
+        "on",
+        lib::DATA,
+        "The `lib` crate was not compiled with the cfg produced from it's `build.rs`"
+    );
+    assert!(lib::FEATURE);
+}
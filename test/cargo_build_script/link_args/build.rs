@@ -0,0 +1,7 @@
+//! A build.rs script which sets linker flags for specific crate types.
+
+fn main() {
+    println!("cargo::rustc-link-arg-bin=named_bin=-Wl,--named-bin-only");
+    println!("cargo::rustc-link-arg-bins=-Wl,--all-bins");
+    println!("cargo::rustc-cdylib-link-arg=-Wl,--cdylib-only");
+}
@@ -0,0 +1,3 @@
+pub fn data() -> &'static str {
+    "data"
+}
@@ -0,0 +1,71 @@
+"""Analysis tests for the crate type specific linker flags of build scripts.
+
+`cargo::rustc-link-arg-bin(s)` flags are passed to binaries with
+`--bin-arg-file`, from which process_wrapper only picks the flags for all
+binaries and those of the binary with the crate name being built.
+`cargo::rustc-cdylib-link-arg` flags are passed to cdylibs with `--arg-file`.
+"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+
+_LINK_FLAG_FILES = ("binlinkflags", "cdyliblinkflags")
+
+def _link_flag_files(argv, flag):
+    """Returns the extensions of the build script linker flag files passed with `flag`."""
+    files = [argv[i + 1] for i in range(len(argv) - 1) if argv[i] == flag]
+    return [f.rsplit(".", 1)[-1] for f in files if f.rsplit(".", 1)[-1] in _LINK_FLAG_FILES]
+
+def _link_args_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    asserts.equals(env, ctx.attr.bin_arg_files, _link_flag_files(action.argv, "--bin-arg-file"))
+    asserts.equals(env, ctx.attr.arg_files, _link_flag_files(action.argv, "--arg-file"))
+    return analysistest.end(env)
+
+_link_args_test = analysistest.make(
+    _link_args_test_impl,
+    attrs = {
+        "arg_files": attr.string_list(),
+        "bin_arg_files": attr.string_list(),
+    },
+)
+
+def link_args_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    _link_args_test(
+        name = "named_bin_link_args_test",
+        target_under_test = ":named_bin",
+        bin_arg_files = ["binlinkflags"],
+    )
+
+    _link_args_test(
+        name = "other_bin_link_args_test",
+        target_under_test = ":other_bin",
+        bin_arg_files = ["binlinkflags"],
+    )
+
+    _link_args_test(
+        name = "cdylib_link_args_test",
+        target_under_test = ":cdylib",
+        arg_files = ["cdyliblinkflags"],
+    )
+
+    _link_args_test(
+        name = "lib_link_args_test",
+        target_under_test = ":lib",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":cdylib_link_args_test",
+            ":lib_link_args_test",
+            ":named_bin_link_args_test",
+            ":other_bin_link_args_test",
+        ],
+    )
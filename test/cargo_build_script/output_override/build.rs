@@ -0,0 +1,3 @@
+fn main() {
+    panic!("The build script should not be run when `output_override` is set");
+}
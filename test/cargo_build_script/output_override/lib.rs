@@ -0,0 +1,7 @@
+#[cfg(overridden)]
+pub const OVERRIDDEN: bool = true;
+
+#[cfg(not(overridden))]
+pub const OVERRIDDEN: bool = false;
+
+pub const VALUE: &str = env!("OVERRIDE_VALUE");
@@ -0,0 +1,5 @@
+#[test]
+pub fn test_output_override() {
+    assert!(lib::OVERRIDDEN);
+    assert_eq!("from_override", lib::VALUE);
+}
@@ -0,0 +1,108 @@
+"""Starlark tests for `//rust/settings:experimental_coverage_options`"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_library")
+load(
+    "//test/unit:common.bzl",
+    "assert_argv_contains",
+    "assert_argv_contains_not",
+)
+
+def _coverage_options_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains(env, action, "--codegen=instrument-coverage")
+    assert_argv_contains(env, action, "-Zcoverage-options=mcdc")
+
+    return analysistest.end(env)
+
+_coverage_options_test = analysistest.make(
+    _coverage_options_test_impl,
+    config_settings = {
+        "//command_line_option:collect_code_coverage": True,
+        str(Label("//rust/settings:experimental_coverage_options")): "mcdc",
+    },
+)
+
+def _no_coverage_options_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains(env, action, "--codegen=instrument-coverage")
+    assert_argv_contains_not(env, action, "-Zcoverage-options=branch")
+    assert_argv_contains_not(env, action, "-Zcoverage-options=mcdc")
+
+    return analysistest.end(env)
+
+_no_coverage_options_test = analysistest.make(
+    _no_coverage_options_test_impl,
+    config_settings = {
+        "//command_line_option:collect_code_coverage": True,
+    },
+)
+
+def _coverage_options_without_coverage_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains_not(env, action, "-Zcoverage-options=branch")
+
+    return analysistest.end(env)
+
+_coverage_options_without_coverage_test = analysistest.make(
+    _coverage_options_without_coverage_test_impl,
+    config_settings = {
+        str(Label("//rust/settings:experimental_coverage_options")): "branch",
+    },
+)
+
+def coverage_options_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "crate_lib",
+        out = "lib.rs",
+        content = [
+            "#[allow(dead_code)]",
+            "fn add() {}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    _coverage_options_test(
+        name = "coverage_options_test",
+        target_under_test = ":lib",
+    )
+
+    _no_coverage_options_test(
+        name = "no_coverage_options_test",
+        target_under_test = ":lib",
+    )
+
+    _coverage_options_without_coverage_test(
+        name = "coverage_options_without_coverage_test",
+        target_under_test = ":lib",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":coverage_options_test",
+            ":coverage_options_without_coverage_test",
+            ":no_coverage_options_test",
+        ],
+    )
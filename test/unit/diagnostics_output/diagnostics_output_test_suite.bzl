@@ -0,0 +1,119 @@
+"""Starlark tests for `//rust/settings:experimental_diagnostics_output`"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_binary", "rust_library")
+load(
+    "//test/unit:common.bzl",
+    "assert_argv_contains_not",
+    "assert_list_contains_adjacent_elements",
+)
+
+def _diagnostics_output_test_impl(ctx, format, extension):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+
+    reports = [o for o in action.outputs.to_list() if o.basename.endswith(extension)]
+    asserts.equals(env, 1, len(reports), "expected a {} output".format(extension))
+    report = reports[0]
+    assert_list_contains_adjacent_elements(env, action.argv, ["--diagnostics-output", report.path])
+    assert_list_contains_adjacent_elements(env, action.argv, ["--diagnostics-format", format])
+
+    output_group = target[OutputGroupInfo].rust_diagnostics.to_list()
+    asserts.equals(env, [report], output_group)
+
+    # The report isn't part of the default outputs.
+    asserts.false(env, report in target[DefaultInfo].files.to_list())
+
+    return analysistest.end(env)
+
+def _diagnostics_output_jsonl_test_impl(ctx):
+    return _diagnostics_output_test_impl(ctx, "jsonl", ".diagnostics.jsonl")
+
+_diagnostics_output_jsonl_test = analysistest.make(
+    _diagnostics_output_jsonl_test_impl,
+    config_settings = {str(Label("//rust/settings:experimental_diagnostics_output")): "jsonl"},
+)
+
+def _diagnostics_output_sarif_test_impl(ctx):
+    return _diagnostics_output_test_impl(ctx, "sarif", ".sarif")
+
+_diagnostics_output_sarif_test = analysistest.make(
+    _diagnostics_output_sarif_test_impl,
+    config_settings = {str(Label("//rust/settings:experimental_diagnostics_output")): "sarif"},
+)
+
+def _diagnostics_output_off_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains_not(env, action, "--diagnostics-output")
+    asserts.false(env, OutputGroupInfo in target and hasattr(target[OutputGroupInfo], "rust_diagnostics"))
+
+    return analysistest.end(env)
+
+_diagnostics_output_off_test = analysistest.make(_diagnostics_output_off_test_impl)
+
+def diagnostics_output_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "crate_lib",
+        out = "lib.rs",
+        content = [
+            "#[allow(dead_code)]",
+            "fn add() {}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    write_file(
+        name = "crate_bin",
+        out = "bin.rs",
+        content = [
+            "fn main() {}",
+            "",
+        ],
+    )
+
+    rust_binary(
+        name = "bin",
+        srcs = [":bin.rs"],
+        edition = "2021",
+    )
+
+    _diagnostics_output_jsonl_test(
+        name = "diagnostics_output_jsonl_test",
+        target_under_test = ":lib",
+    )
+
+    _diagnostics_output_sarif_test(
+        name = "diagnostics_output_sarif_test",
+        target_under_test = ":bin",
+    )
+
+    _diagnostics_output_off_test(
+        name = "diagnostics_output_off_test",
+        target_under_test = ":lib",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":diagnostics_output_jsonl_test",
+            ":diagnostics_output_off_test",
+            ":diagnostics_output_sarif_test",
+        ],
+    )
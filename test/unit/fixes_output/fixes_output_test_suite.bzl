@@ -0,0 +1,83 @@
+"""Starlark tests for `//rust/settings:experimental_fixes_output`"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_library")
+load(
+    "//test/unit:common.bzl",
+    "assert_argv_contains_not",
+    "assert_list_contains_adjacent_elements",
+)
+
+def _fixes_output_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    fixes = [o for o in action.outputs.to_list() if o.basename.endswith(".fixes.jsonl")]
+    asserts.equals(env, 1, len(fixes), "expected a .fixes.jsonl output")
+    assert_list_contains_adjacent_elements(env, action.argv, ["--fixes-output", fixes[0].path])
+
+    # process_wrapper needs the json output of rustc to extract the fixes.
+    assert_list_contains_adjacent_elements(env, action.argv, ["--rustc-output-format", "rendered"])
+
+    asserts.equals(env, fixes, target[OutputGroupInfo].rust_fixes.to_list())
+
+    return analysistest.end(env)
+
+_fixes_output_test = analysistest.make(
+    _fixes_output_test_impl,
+    config_settings = {str(Label("//rust/settings:experimental_fixes_output")): True},
+)
+
+def _no_fixes_output_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains_not(env, action, "--fixes-output")
+    asserts.false(env, OutputGroupInfo in target and hasattr(target[OutputGroupInfo], "rust_fixes"))
+
+    return analysistest.end(env)
+
+_no_fixes_output_test = analysistest.make(_no_fixes_output_test_impl)
+
+def fixes_output_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "crate_lib",
+        out = "lib.rs",
+        content = [
+            "#[allow(dead_code)]",
+            "fn add() {}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    _fixes_output_test(
+        name = "fixes_output_test",
+        target_under_test = ":lib",
+    )
+
+    _no_fixes_output_test(
+        name = "no_fixes_output_test",
+        target_under_test = ":lib",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":fixes_output_test",
+            ":no_fixes_output_test",
+        ],
+    )
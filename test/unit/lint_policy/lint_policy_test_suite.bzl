@@ -0,0 +1,95 @@
+"""Starlark tests for `//rust/settings:experimental_lint_policy`"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_library")
+load(
+    "//test/unit:common.bzl",
+    "assert_argv_contains_not",
+    "assert_list_contains_adjacent_elements",
+)
+
+_POLICY = Label("//test/unit/lint_policy:policy.json")
+
+# TODO: Fix pipeline compilation on windows
+# https://github.com/bazelbuild/rules_rust/issues/3383
+_NO_WINDOWS = select({
+    "@platforms//os:windows": ["@platforms//:incompatible"],
+    "//conditions:default": [],
+})
+
+def _lint_policy_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    actions = [a for a in target.actions if a.mnemonic in ["Rustc", "RustcMetadata"]]
+    asserts.equals(env, 2, len(actions), "expected a Rustc and a RustcMetadata action")
+    for action in actions:
+        policies = [i for i in action.inputs.to_list() if i.basename == "policy.json"]
+        asserts.equals(env, 1, len(policies), "expected the policy to be an input of " + action.mnemonic)
+        assert_list_contains_adjacent_elements(env, action.argv, ["--lint-policy", policies[0].path])
+
+        # The policy applies to the json output of rustc.
+        assert_list_contains_adjacent_elements(env, action.argv, ["--rustc-output-format", "rendered"])
+
+    return analysistest.end(env)
+
+_lint_policy_test = analysistest.make(
+    _lint_policy_test_impl,
+    config_settings = {
+        str(Label("//rust/settings:experimental_lint_policy")): str(_POLICY),
+        str(Label("//rust/settings:pipelined_compilation")): True,
+    },
+)
+
+def _no_lint_policy_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains_not(env, action, "--lint-policy")
+
+    return analysistest.end(env)
+
+_no_lint_policy_test = analysistest.make(_no_lint_policy_test_impl)
+
+def lint_policy_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "crate_lib",
+        out = "lib.rs",
+        content = [
+            "#[allow(dead_code)]",
+            "fn add() {}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    _lint_policy_test(
+        name = "lint_policy_test",
+        target_under_test = ":lib",
+        target_compatible_with = _NO_WINDOWS,
+    )
+
+    _no_lint_policy_test(
+        name = "no_lint_policy_test",
+        target_under_test = ":lib",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":lint_policy_test",
+            ":no_lint_policy_test",
+        ],
+    )
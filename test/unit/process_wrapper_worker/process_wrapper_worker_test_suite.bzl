@@ -0,0 +1,153 @@
+"""Starlark tests for `//rust/settings:experimental_use_process_wrapper_worker`"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_library")
+load(
+    "//test/unit:common.bzl",
+    "assert_argv_contains_not",
+    "assert_list_contains_adjacent_elements",
+)
+
+_USE_WORKER = {
+    str(Label("//rust/settings:experimental_use_process_wrapper_worker")): True,
+}
+
+# TODO: Fix pipeline compilation on windows
+# https://github.com/bazelbuild/rules_rust/issues/3383
+_NO_WINDOWS = select({
+    "@platforms//os:windows": ["@platforms//:incompatible"],
+    "//conditions:default": [],
+})
+
+def _worker_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    asserts.equals(env, "1", action.execution_info.get("supports-workers"))
+    asserts.equals(env, "1", action.execution_info.get("supports-multiplex-workers"))
+
+    # Sandboxed requests are rejected by the worker.
+    asserts.false(env, "supports-multiplex-sandboxing" in action.execution_info, "expected no multiplex sandboxing")
+
+    # The environment of the crate is passed in a file to share the worker between crates.
+    asserts.false(env, "CARGO_PKG_NAME" in action.env, "expected CARGO_PKG_NAME to not be in the action env")
+    env_files = [i for i in action.inputs.to_list() if i.basename.endswith(".worker_env")]
+    asserts.equals(env, 1, len(env_files), "expected a .worker_env input")
+    assert_list_contains_adjacent_elements(env, action.argv, ["--env-file", env_files[0].path])
+
+    return analysistest.end(env)
+
+_worker_test = analysistest.make(
+    _worker_test_impl,
+    config_settings = _USE_WORKER,
+)
+
+def _no_worker_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    asserts.false(env, "supports-workers" in action.execution_info)
+    asserts.true(env, "CARGO_PKG_NAME" in action.env, "expected CARGO_PKG_NAME in the action env")
+    assert_argv_contains_not(env, action, "--env-file")
+
+    return analysistest.end(env)
+
+_no_worker_test = analysistest.make(_no_worker_test_impl)
+
+def _pipelined_worker_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    rlib_action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    metadata_action = [a for a in target.actions if a.mnemonic == "RustcMetadata"][0]
+    rlib = rlib_action.outputs.to_list()[0].path
+
+    # Both actions share one rustc process, which needs them to reach the same worker.
+    for action in [rlib_action, metadata_action]:
+        assert_list_contains_adjacent_elements(env, action.argv, ["--rustc-pipelining-key", rlib])
+    asserts.equals(env, "Rustc", metadata_action.execution_info.get("worker-key-mnemonic"))
+    assert_list_contains_adjacent_elements(env, rlib_action.argv, ["--rustc-pipelined-output", rlib])
+    assert_argv_contains_not(env, metadata_action, "--rustc-pipelined-output")
+
+    return analysistest.end(env)
+
+_pipelined_worker_test = analysistest.make(
+    _pipelined_worker_test_impl,
+    config_settings = dict(_USE_WORKER, **{
+        str(Label("//rust/settings:pipelined_compilation")): True,
+    }),
+)
+
+def _pipelined_no_worker_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    for action in target.actions:
+        if action.mnemonic in ["Rustc", "RustcMetadata"]:
+            assert_argv_contains_not(env, action, "--rustc-pipelining-key")
+
+    return analysistest.end(env)
+
+_pipelined_no_worker_test = analysistest.make(
+    _pipelined_no_worker_test_impl,
+    config_settings = {
+        str(Label("//rust/settings:pipelined_compilation")): True,
+    },
+)
+
+def process_wrapper_worker_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "crate_lib",
+        out = "lib.rs",
+        content = [
+            "#[allow(dead_code)]",
+            "fn add() {}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    _worker_test(
+        name = "worker_test",
+        target_under_test = ":lib",
+    )
+
+    _no_worker_test(
+        name = "no_worker_test",
+        target_under_test = ":lib",
+    )
+
+    _pipelined_worker_test(
+        name = "pipelined_worker_test",
+        target_under_test = ":lib",
+        target_compatible_with = _NO_WINDOWS,
+    )
+
+    _pipelined_no_worker_test(
+        name = "pipelined_no_worker_test",
+        target_under_test = ":lib",
+        target_compatible_with = _NO_WINDOWS,
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":no_worker_test",
+            ":pipelined_no_worker_test",
+            ":pipelined_worker_test",
+            ":worker_test",
+        ],
+    )
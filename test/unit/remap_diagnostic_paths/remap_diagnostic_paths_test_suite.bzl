@@ -0,0 +1,105 @@
+"""Starlark tests for `//rust/settings:experimental_remap_diagnostic_paths` and
+`//rust/settings:experimental_remap_diagnostic_path_prefixes`"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_library")
+load(
+    "//test/unit:common.bzl",
+    "assert_argv_contains_not",
+    "assert_list_contains_adjacent_elements",
+)
+
+def _remap_diagnostic_paths_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_list_contains_adjacent_elements(env, action.argv, ["--remap-diagnostic-paths", "true"])
+    assert_argv_contains_not(env, action, "--remap-diagnostic-path-prefix")
+
+    # The paths are rewritten in the json output of rustc.
+    assert_list_contains_adjacent_elements(env, action.argv, ["--rustc-output-format", "rendered"])
+
+    return analysistest.end(env)
+
+_remap_diagnostic_paths_test = analysistest.make(
+    _remap_diagnostic_paths_test_impl,
+    config_settings = {str(Label("//rust/settings:experimental_remap_diagnostic_paths")): True},
+)
+
+def _remap_diagnostic_path_prefixes_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains_not(env, action, "--remap-diagnostic-paths")
+    assert_list_contains_adjacent_elements(env, action.argv, ["--remap-diagnostic-path-prefix", "${pwd}/=/src/"])
+    assert_list_contains_adjacent_elements(env, action.argv, ["--remap-diagnostic-path-prefix", "external/=/deps/"])
+
+    return analysistest.end(env)
+
+_remap_diagnostic_path_prefixes_test = analysistest.make(
+    _remap_diagnostic_path_prefixes_test_impl,
+    config_settings = {
+        str(Label("//rust/settings:experimental_remap_diagnostic_path_prefixes")): ["${pwd}/=/src/", "external/=/deps/"],
+    },
+)
+
+def _no_remap_diagnostic_paths_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains_not(env, action, "--remap-diagnostic-paths")
+    assert_argv_contains_not(env, action, "--remap-diagnostic-path-prefix")
+
+    return analysistest.end(env)
+
+_no_remap_diagnostic_paths_test = analysistest.make(_no_remap_diagnostic_paths_test_impl)
+
+def remap_diagnostic_paths_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "crate_lib",
+        out = "lib.rs",
+        content = [
+            "#[allow(dead_code)]",
+            "fn add() {}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    _remap_diagnostic_paths_test(
+        name = "remap_diagnostic_paths_test",
+        target_under_test = ":lib",
+    )
+
+    _remap_diagnostic_path_prefixes_test(
+        name = "remap_diagnostic_path_prefixes_test",
+        target_under_test = ":lib",
+    )
+
+    _no_remap_diagnostic_paths_test(
+        name = "no_remap_diagnostic_paths_test",
+        target_under_test = ":lib",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":no_remap_diagnostic_paths_test",
+            ":remap_diagnostic_path_prefixes_test",
+            ":remap_diagnostic_paths_test",
+        ],
+    )
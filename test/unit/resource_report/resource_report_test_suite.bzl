@@ -0,0 +1,122 @@
+"""Starlark tests for `//rust/settings:experimental_resource_report`"""
+
+load("@bazel_skylib//lib:unittest.bzl", "analysistest", "asserts")
+load("@bazel_skylib//rules:write_file.bzl", "write_file")
+load("//rust:defs.bzl", "rust_library")
+load(
+    "//test/unit:common.bzl",
+    "assert_argv_contains_not",
+    "assert_list_contains_adjacent_elements",
+)
+
+_ENABLE_RESOURCE_REPORT = {
+    str(Label("//rust/settings:experimental_resource_report")): True,
+}
+
+# TODO: Fix pipeline compilation on windows
+# https://github.com/bazelbuild/rules_rust/issues/3383
+_NO_WINDOWS = select({
+    "@platforms//os:windows": ["@platforms//:incompatible"],
+    "//conditions:default": [],
+})
+
+def _assert_resource_report(env, action):
+    reports = [o for o in action.outputs.to_list() if o.basename.endswith(".resources.json")]
+    asserts.equals(env, 1, len(reports), "expected a .resources.json output of " + action.mnemonic)
+    assert_list_contains_adjacent_elements(env, action.argv, ["--resource-report", reports[0].path])
+    return reports[0]
+
+def _resource_report_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    report = _assert_resource_report(env, action)
+    asserts.equals(env, [report], target[OutputGroupInfo].rust_resource_reports.to_list())
+
+    return analysistest.end(env)
+
+_resource_report_test = analysistest.make(
+    _resource_report_test_impl,
+    config_settings = _ENABLE_RESOURCE_REPORT,
+)
+
+def _pipelined_resource_report_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    rlib_report = _assert_resource_report(env, [a for a in target.actions if a.mnemonic == "Rustc"][0])
+    rmeta_report = _assert_resource_report(env, [a for a in target.actions if a.mnemonic == "RustcMetadata"][0])
+    asserts.true(env, rlib_report != rmeta_report, "expected each action to write its own report")
+    asserts.equals(
+        env,
+        sorted([rlib_report.path, rmeta_report.path]),
+        sorted([f.path for f in target[OutputGroupInfo].rust_resource_reports.to_list()]),
+    )
+
+    return analysistest.end(env)
+
+_pipelined_resource_report_test = analysistest.make(
+    _pipelined_resource_report_test_impl,
+    config_settings = dict(_ENABLE_RESOURCE_REPORT, **{
+        str(Label("//rust/settings:pipelined_compilation")): True,
+    }),
+)
+
+def _no_resource_report_test_impl(ctx):
+    env = analysistest.begin(ctx)
+    target = analysistest.target_under_test(env)
+
+    action = [a for a in target.actions if a.mnemonic == "Rustc"][0]
+    assert_argv_contains_not(env, action, "--resource-report")
+
+    return analysistest.end(env)
+
+_no_resource_report_test = analysistest.make(_no_resource_report_test_impl)
+
+def resource_report_test_suite(name):
+    """Entry-point macro called from the BUILD file.
+
+    Args:
+        name (str): The name of the test suite.
+    """
+    write_file(
+        name = "crate_lib",
+        out = "lib.rs",
+        content = [
+            "#[allow(dead_code)]",
+            "fn add() {}",
+            "",
+        ],
+    )
+
+    rust_library(
+        name = "lib",
+        srcs = [":lib.rs"],
+        edition = "2021",
+    )
+
+    _resource_report_test(
+        name = "resource_report_test",
+        target_under_test = ":lib",
+    )
+
+    _pipelined_resource_report_test(
+        name = "pipelined_resource_report_test",
+        target_under_test = ":lib",
+        target_compatible_with = _NO_WINDOWS,
+    )
+
+    _no_resource_report_test(
+        name = "no_resource_report_test",
+        target_under_test = ":lib",
+    )
+
+    native.test_suite(
+        name = name,
+        tests = [
+            ":no_resource_report_test",
+            ":pipelined_resource_report_test",
+            ":resource_report_test",
+        ],
+    )
@@ -0,0 +1,457 @@
+//! Utilities for merging the resource reports process_wrapper writes with
+//! `--resource-report` into a build profile.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+
+use tinyjson::JsonValue;
+
+/// The suffix resource reports are expected to have when searching for them.
+pub const REPORT_FILE_SUFFIX: &str = ".resources.json";
+
+/// A section of a compilation reported by rustc's `--json=timings`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// The name of the section, e.g. `codegen`.
+    pub name: String,
+
+    /// Microseconds since rustc started.
+    pub start_us: u64,
+
+    /// Microseconds since rustc started.
+    pub end_us: u64,
+}
+
+/// The resource report of a single action.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    /// The file name of the executable, e.g. `rustc`.
+    pub executable: String,
+
+    /// The `--crate-name` passed to rustc, if any.
+    pub crate_name: Option<String>,
+
+    /// The `--crate-type`s passed to rustc.
+    pub crate_types: Vec<String>,
+
+    /// The `--emit` flag passed to rustc, if any.
+    pub emit: Option<String>,
+
+    /// Microseconds since the unix epoch at which the action started.
+    pub start_time_us: u64,
+
+    /// The wall time of the action in microseconds.
+    pub wall_time_us: u64,
+
+    /// The exit code of the action.
+    pub exit_code: Option<i32>,
+
+    /// The user CPU time in microseconds, if known.
+    pub user_time_us: Option<u64>,
+
+    /// The system CPU time in microseconds, if known.
+    pub system_time_us: Option<u64>,
+
+    /// The peak resident set size in bytes, if known.
+    pub max_rss_bytes: Option<u64>,
+
+    /// The sections reported by rustc's `--json=timings`.
+    pub sections: Vec<Section>,
+
+    /// The passes reported by rustc's `-Z time-passes`, with their duration
+    /// in seconds.
+    pub passes: Vec<(String, f64)>,
+}
+
+fn get_str(map: &HashMap<String, JsonValue>, key: &str) -> Option<String> {
+    match map.get(key) {
+        Some(JsonValue::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn get_number(map: &HashMap<String, JsonValue>, key: &str) -> Option<f64> {
+    match map.get(key) {
+        Some(JsonValue::Number(n)) => Some(*n),
+        _ => None,
+    }
+}
+
+fn get_u64(map: &HashMap<String, JsonValue>, key: &str) -> Option<u64> {
+    get_number(map, key).map(|n| n.max(0.0) as u64)
+}
+
+fn get_array<'a>(map: &'a HashMap<String, JsonValue>, key: &str) -> &'a [JsonValue] {
+    match map.get(key) {
+        Some(JsonValue::Array(array)) => array,
+        _ => &[],
+    }
+}
+
+impl Report {
+    /// Parses a resource report written by process_wrapper.
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let value: JsonValue = content.parse().map_err(|e| format!("{}", e))?;
+        let map: &HashMap<String, JsonValue> = value
+            .get()
+            .ok_or_else(|| "report is not an object".to_owned())?;
+        Ok(Self {
+            executable: get_str(map, "executable")
+                .ok_or_else(|| "missing string field \"executable\"".to_owned())?,
+            crate_name: get_str(map, "crate_name"),
+            crate_types: get_array(map, "crate_types")
+                .iter()
+                .filter_map(|t| t.get::<String>().cloned())
+                .collect(),
+            emit: get_str(map, "emit"),
+            start_time_us: get_u64(map, "start_time_us")
+                .ok_or_else(|| "missing number field \"start_time_us\"".to_owned())?,
+            wall_time_us: get_u64(map, "wall_time_us")
+                .ok_or_else(|| "missing number field \"wall_time_us\"".to_owned())?,
+            exit_code: get_number(map, "exit_code").map(|c| c as i32),
+            user_time_us: get_u64(map, "user_time_us"),
+            system_time_us: get_u64(map, "system_time_us"),
+            max_rss_bytes: get_u64(map, "max_rss_bytes"),
+            sections: get_array(map, "sections")
+                .iter()
+                .filter_map(|s| {
+                    let s: &HashMap<String, JsonValue> = s.get()?;
+                    Some(Section {
+                        name: get_str(s, "name")?,
+                        start_us: get_u64(s, "start_us")?,
+                        end_us: get_u64(s, "end_us")?,
+                    })
+                })
+                .collect(),
+            passes: get_array(map, "passes")
+                .iter()
+                .filter_map(|p| {
+                    let p: &HashMap<String, JsonValue> = p.get()?;
+                    Some((get_str(p, "name")?, get_number(p, "seconds")?))
+                })
+                .collect(),
+        })
+    }
+
+    /// Returns whether this is the report of a pipelined rmeta action.
+    fn is_metadata_only(&self) -> bool {
+        self.emit.as_deref().is_some_and(|emit| {
+            emit.split(',').any(|e| e == "metadata") && !emit.split(',').any(|e| e == "link")
+        })
+    }
+
+    /// The name of the action in the build profile.
+    pub fn name(&self) -> String {
+        match &self.crate_name {
+            Some(crate_name) if self.is_metadata_only() => format!("{} (metadata)", crate_name),
+            Some(crate_name) => crate_name.clone(),
+            None => self.executable.clone(),
+        }
+    }
+}
+
+fn object(entries: Vec<(&str, JsonValue)>) -> JsonValue {
+    JsonValue::Object(
+        entries
+            .into_iter()
+            .map(|(key, value)| (key.to_owned(), value))
+            .collect(),
+    )
+}
+
+fn string(s: &str) -> JsonValue {
+    JsonValue::String(s.to_owned())
+}
+
+fn number(n: u64) -> JsonValue {
+    JsonValue::Number(n as f64)
+}
+
+fn complete_event(
+    name: &str,
+    category: &str,
+    ts: u64,
+    dur: u64,
+    tid: usize,
+) -> Vec<(&'static str, JsonValue)> {
+    vec![
+        ("name", string(name)),
+        ("cat", string(category)),
+        ("ph", string("X")),
+        ("ts", number(ts)),
+        ("dur", number(dur)),
+        ("pid", number(1)),
+        ("tid", number(tid as u64)),
+    ]
+}
+
+/// Builds a Chrome trace (`chrome://tracing`, Perfetto) of `reports`.
+///
+/// Each action becomes a complete event, placed on the first row that is
+/// free at the time it started so that concurrent actions don't overlap. The
+/// sections reported by rustc are nested within the event of their action.
+pub fn chrome_trace(reports: &[Report]) -> JsonValue {
+    let mut sorted: Vec<&Report> = reports.iter().collect();
+    sorted.sort_by_key(|r| (r.start_time_us, r.wall_time_us));
+    let origin = sorted.first().map_or(0, |r| r.start_time_us);
+
+    // The time at which the last action on each row ends.
+    let mut rows: Vec<u64> = Vec::new();
+    let mut events = Vec::new();
+    for report in sorted {
+        let ts = report.start_time_us - origin;
+        let row = match rows.iter().position(|end| *end <= ts) {
+            Some(row) => row,
+            None => {
+                rows.push(0);
+                rows.len() - 1
+            }
+        };
+        rows[row] = ts + report.wall_time_us;
+
+        let mut args = vec![
+            (
+                "crate_types",
+                JsonValue::Array(report.crate_types.iter().map(|t| string(t)).collect()),
+            ),
+            (
+                "exit_code",
+                report
+                    .exit_code
+                    .map_or(JsonValue::Null, |c| JsonValue::Number(c as f64)),
+            ),
+        ];
+        if let Some(emit) = &report.emit {
+            args.push(("emit", string(emit)));
+        }
+        if let Some(user_time_us) = report.user_time_us {
+            args.push((
+                "user_time_ms",
+                JsonValue::Number(user_time_us as f64 / 1000.0),
+            ));
+        }
+        if let Some(system_time_us) = report.system_time_us {
+            args.push((
+                "system_time_ms",
+                JsonValue::Number(system_time_us as f64 / 1000.0),
+            ));
+        }
+        if let Some(max_rss_bytes) = report.max_rss_bytes {
+            args.push((
+                "max_rss_mb",
+                JsonValue::Number(max_rss_bytes as f64 / (1024.0 * 1024.0)),
+            ));
+        }
+        if !report.passes.is_empty() {
+            let mut passes: BTreeMap<&str, f64> = BTreeMap::new();
+            for (name, seconds) in &report.passes {
+                *passes.entry(name).or_default() += seconds;
+            }
+            args.push((
+                "passes_s",
+                object(
+                    passes
+                        .into_iter()
+                        .map(|(name, seconds)| (name, JsonValue::Number(seconds)))
+                        .collect(),
+                ),
+            ));
+        }
+
+        let mut event = complete_event(
+            &report.name(),
+            &report.executable,
+            ts,
+            report.wall_time_us,
+            row,
+        );
+        event.push(("args", object(args)));
+        events.push(object(event));
+
+        for section in &report.sections {
+            events.push(object(complete_event(
+                &section.name,
+                "section",
+                ts + section.start_us,
+                section.end_us.saturating_sub(section.start_us),
+                row,
+            )));
+        }
+    }
+
+    object(vec![
+        ("traceEvents", JsonValue::Array(events)),
+        ("displayTimeUnit", string("ms")),
+    ])
+}
+
+/// The resources used by all actions of a crate.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CrateSummary {
+    /// The name of the crate, or of the executable for non-rustc actions.
+    pub name: String,
+
+    /// The number of actions.
+    pub actions: usize,
+
+    /// The total wall time of all actions in microseconds.
+    pub wall_time_us: u64,
+
+    /// The total user and system CPU time of all actions in microseconds.
+    pub cpu_time_us: u64,
+
+    /// The largest peak resident set size of all actions in bytes.
+    pub max_rss_bytes: u64,
+}
+
+/// Sums up the resources used by each crate, ordered by descending wall time.
+pub fn crate_summaries(reports: &[Report]) -> Vec<CrateSummary> {
+    let mut summaries: BTreeMap<String, CrateSummary> = BTreeMap::new();
+    for report in reports {
+        let name = report
+            .crate_name
+            .clone()
+            .unwrap_or_else(|| report.executable.clone());
+        let summary = summaries
+            .entry(name.clone())
+            .or_insert_with(|| CrateSummary {
+                name,
+                ..CrateSummary::default()
+            });
+        summary.actions += 1;
+        summary.wall_time_us += report.wall_time_us;
+        summary.cpu_time_us +=
+            report.user_time_us.unwrap_or_default() + report.system_time_us.unwrap_or_default();
+        summary.max_rss_bytes = summary
+            .max_rss_bytes
+            .max(report.max_rss_bytes.unwrap_or_default());
+    }
+    let mut summaries: Vec<CrateSummary> = summaries.into_values().collect();
+    summaries.sort_by_key(|s| Reverse(s.wall_time_us));
+    summaries
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RUSTC_REPORT: &str = r#"{"executable":"rustc","crate_name":"foo","crate_types":["rlib"],"emit":"dep-info,metadata,link","start_time_us":1000,"wall_time_us":5000,"exit_code":0,"user_time_us":3000,"system_time_us":1000,"max_rss_bytes":1048576,"sections":[{"name":"codegen","start_us":1000,"end_us":4000}],"passes":[{"name":"typeck","seconds":0.5},{"name":"typeck","seconds":0.25}]}"#;
+
+    fn report(crate_name: &str, emit: &str, start_time_us: u64, wall_time_us: u64) -> Report {
+        Report {
+            executable: "rustc".to_owned(),
+            crate_name: Some(crate_name.to_owned()),
+            crate_types: vec!["rlib".to_owned()],
+            emit: Some(emit.to_owned()),
+            start_time_us,
+            wall_time_us,
+            exit_code: Some(0),
+            user_time_us: None,
+            system_time_us: None,
+            max_rss_bytes: None,
+            sections: Vec::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_parse_report() {
+        let report = Report::parse(RUSTC_REPORT).unwrap();
+        assert_eq!(report.name(), "foo");
+        assert_eq!(report.user_time_us, Some(3000));
+        assert_eq!(report.max_rss_bytes, Some(1048576));
+        assert_eq!(
+            report.sections,
+            vec![Section {
+                name: "codegen".to_owned(),
+                start_us: 1000,
+                end_us: 4000,
+            }]
+        );
+        assert_eq!(report.passes.len(), 2);
+
+        let report = Report::parse(
+            r#"{"executable":"build_script_build","crate_name":null,"start_time_us":0,"wall_time_us":1,"user_time_us":null}"#,
+        )
+        .unwrap();
+        assert_eq!(report.name(), "build_script_build");
+        assert_eq!(report.user_time_us, None);
+
+        assert!(Report::parse("{}").is_err());
+    }
+
+    #[test]
+    fn test_chrome_trace_rows() {
+        let trace = chrome_trace(&[
+            report("bar", "dep-info,metadata,link", 2000, 1000),
+            report("foo", "dep-info,metadata", 1000, 500),
+            report("baz", "link", 1200, 100),
+        ]);
+        let events: &Vec<JsonValue> = trace["traceEvents"].get().unwrap();
+        let summary: Vec<(String, f64, f64)> = events
+            .iter()
+            .map(|e| {
+                (
+                    e["name"].get::<String>().unwrap().clone(),
+                    *e["ts"].get::<f64>().unwrap(),
+                    *e["tid"].get::<f64>().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("foo (metadata)".to_owned(), 0.0, 0.0),
+                // Overlaps with foo.
+                ("baz".to_owned(), 200.0, 1.0),
+                // foo finished, so the first row is free again.
+                ("bar".to_owned(), 1000.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chrome_trace_sections_and_args() {
+        let trace = chrome_trace(&[Report::parse(RUSTC_REPORT).unwrap()]);
+        let events: &Vec<JsonValue> = trace["traceEvents"].get().unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["args"]["max_rss_mb"], JsonValue::Number(1.0));
+        assert_eq!(
+            events[0]["args"]["passes_s"]["typeck"],
+            JsonValue::Number(0.75)
+        );
+        assert_eq!(events[1]["name"], JsonValue::String("codegen".to_owned()));
+        assert_eq!(events[1]["ts"], JsonValue::Number(1000.0));
+        assert_eq!(events[1]["dur"], JsonValue::Number(3000.0));
+    }
+
+    #[test]
+    fn test_crate_summaries() {
+        let mut full = Report::parse(RUSTC_REPORT).unwrap();
+        full.max_rss_bytes = Some(10);
+        let summaries = crate_summaries(&[
+            report("foo", "dep-info,metadata", 0, 2000),
+            full,
+            report("bar", "link", 0, 1000),
+        ]);
+        assert_eq!(
+            summaries,
+            vec![
+                CrateSummary {
+                    name: "foo".to_owned(),
+                    actions: 2,
+                    wall_time_us: 7000,
+                    cpu_time_us: 4000,
+                    max_rss_bytes: 10,
+                },
+                CrateSummary {
+                    name: "bar".to_owned(),
+                    actions: 1,
+                    wall_time_us: 1000,
+                    cpu_time_us: 0,
+                    max_rss_bytes: 0,
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//! A tool for merging the resource reports of a Bazel build into a build
+//! profile.
+//!
+//! Reports are written by process_wrapper when building with
+//! `--@rules_rust//rust/settings:experimental_resource_report`. This tool
+//! searches `bazel-out` (or the given files and directories) for files ending
+//! in `.resources.json`, writes a Chrome trace of all actions and prints the
+//! crates which took the longest to build.
+
+use std::fs;
+use std::process;
+
+use bazel_out::{collect_files, parse_args};
+use build_profile_lib::{chrome_trace, crate_summaries, Report, REPORT_FILE_SUFFIX};
+
+const USAGE: &str = "Usage: build_profile [--output FILE] [--top N] [PATH...]
+
+Merges the process_wrapper resource reports found in the given files, or in all
+files ending in `.resources.json` within the given directories, into a Chrome
+trace. Defaults to searching the `bazel-out` directory of the workspace.
+
+Options:
+    --output FILE    Where to write the trace. Defaults to `build_profile.json`.
+    --top N          The number of crates to print a summary for. Defaults to 20.";
+
+fn main() {
+    let mut output = None;
+    let mut top = 20;
+    let config = parse_args(USAGE, |arg, args| match arg {
+        "--output" => {
+            output = Some(args.next().ok_or("--output requires a value")?);
+            Ok(())
+        }
+        "--top" => {
+            top = args
+                .next()
+                .and_then(|n| n.parse().ok())
+                .ok_or("--top requires a number")?;
+            Ok(())
+        }
+        _ => Err(format!("Unknown option: {}", arg)),
+    });
+    let output = config
+        .workspace
+        .join(output.as_deref().unwrap_or("build_profile.json"));
+
+    let report_files = collect_files(&config.paths, REPORT_FILE_SUFFIX);
+
+    let mut reports = Vec::new();
+    for report_file in &report_files {
+        let content = fs::read_to_string(report_file)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", report_file.display(), e));
+        match Report::parse(&content) {
+            Ok(report) => reports.push(report),
+            Err(e) => eprintln!("Skipping {}: {}", report_file.display(), e),
+        }
+    }
+    if reports.is_empty() {
+        eprintln!(
+            "No `*{}` files found. Build with process_wrapper's `--resource-report` flag first.",
+            REPORT_FILE_SUFFIX
+        );
+        process::exit(1);
+    }
+
+    let trace = chrome_trace(&reports)
+        .stringify()
+        .expect("Failed to serialize the trace");
+    fs::write(&output, trace)
+        .unwrap_or_else(|e| panic!("Failed to write {}: {}", output.display(), e));
+
+    println!(
+        "{:<40} {:>8} {:>12} {:>12} {:>10}",
+        "crate", "actions", "wall (s)", "cpu (s)", "rss (MB)"
+    );
+    for summary in crate_summaries(&reports).iter().take(top) {
+        println!(
+            "{:<40} {:>8} {:>12.3} {:>12.3} {:>10.1}",
+            summary.name,
+            summary.actions,
+            summary.wall_time_us as f64 / 1e6,
+            summary.cpu_time_us as f64 / 1e6,
+            summary.max_rss_bytes as f64 / (1024.0 * 1024.0),
+        );
+    }
+    println!(
+        "Wrote a trace of {} action(s) to {}",
+        reports.len(),
+        output.display()
+    );
+}
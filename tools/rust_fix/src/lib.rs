@@ -0,0 +1,384 @@
+//! Utilities for applying the machine applicable suggestions of rustc that
+//! process_wrapper writes with `--fixes-output`.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Component, Path, PathBuf};
+
+use tinyjson::JsonValue;
+
+/// The suffix fixes files are expected to have when searching for them.
+pub const FIXES_FILE_SUFFIX: &str = ".fixes.jsonl";
+
+/// A single text replacement within a source file.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Replacement {
+    /// The path of the file as reported by rustc.
+    pub file_name: String,
+
+    /// The byte offset at which the replaced text starts.
+    pub byte_start: usize,
+
+    /// The byte offset at which the replaced text ends.
+    pub byte_end: usize,
+
+    /// The text to insert in place of the replaced range.
+    pub replacement: String,
+
+    /// The text of the replaced range when rustc reported the fix.
+    pub original_text: String,
+}
+
+/// A set of replacements suggested by one diagnostic which have to be applied
+/// together.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fix {
+    /// The replacements of this fix, ordered by position.
+    pub replacements: Vec<Replacement>,
+
+    /// The lint or error code of the diagnostic, if any.
+    pub code: Option<String>,
+
+    /// The message describing the suggestion.
+    pub message: String,
+}
+
+fn get_str(map: &HashMap<String, JsonValue>, key: &str) -> Result<String, String> {
+    match map.get(key) {
+        Some(JsonValue::String(s)) => Ok(s.clone()),
+        _ => Err(format!("missing string field \"{}\"", key)),
+    }
+}
+
+fn get_offset(map: &HashMap<String, JsonValue>, key: &str) -> Result<usize, String> {
+    match map.get(key) {
+        Some(JsonValue::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        _ => Err(format!("missing byte offset \"{}\"", key)),
+    }
+}
+
+impl Replacement {
+    fn from_json(value: &JsonValue) -> Result<Self, String> {
+        let map: &HashMap<String, JsonValue> = value
+            .get()
+            .ok_or_else(|| "replacement is not an object".to_owned())?;
+        let replacement = Self {
+            file_name: get_str(map, "file_name")?,
+            byte_start: get_offset(map, "byte_start")?,
+            byte_end: get_offset(map, "byte_end")?,
+            replacement: get_str(map, "replacement")?,
+            original_text: get_str(map, "original_text")?,
+        };
+        if replacement.byte_start > replacement.byte_end {
+            return Err(format!(
+                "invalid byte range {}..{} in {}",
+                replacement.byte_start, replacement.byte_end, replacement.file_name
+            ));
+        }
+        Ok(replacement)
+    }
+}
+
+impl Fix {
+    fn from_json(value: &JsonValue) -> Result<Self, String> {
+        let map: &HashMap<String, JsonValue> = value
+            .get()
+            .ok_or_else(|| "fix is not an object".to_owned())?;
+        let code = match map.get("code") {
+            Some(JsonValue::String(code)) => Some(code.clone()),
+            _ => None,
+        };
+        let mut replacements = match map.get("replacements") {
+            Some(JsonValue::Array(replacements)) => replacements
+                .iter()
+                .map(Replacement::from_json)
+                .collect::<Result<Vec<_>, _>>()?,
+            _ => return Err("missing array field \"replacements\"".to_owned()),
+        };
+        replacements.sort();
+        Ok(Self {
+            replacements,
+            code,
+            message: get_str(map, "message")?,
+        })
+    }
+
+    /// Returns the file this fix applies to. Fixes spanning multiple files are
+    /// not supported and return `None`.
+    fn file_name(&self) -> Option<&str> {
+        let first = &self.replacements.first()?.file_name;
+        self.replacements
+            .iter()
+            .all(|r| &r.file_name == first)
+            .then_some(first.as_str())
+    }
+}
+
+/// Parses the contents of a fixes file, one json object per line.
+pub fn parse_fixes(content: &str) -> Result<Vec<Fix>, String> {
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            line.parse::<JsonValue>()
+                .map_err(|e| e.to_string())
+                .and_then(|value| Fix::from_json(&value))
+                .map_err(|e| format!("line {}: {}", index + 1, e))
+        })
+        .collect()
+}
+
+/// Maps a path reported by rustc to a path relative to the workspace root.
+///
+/// rustc reports paths relative to the exec root in which it ran, which may
+/// be a sandbox. Absolute paths are mapped by stripping everything up to the
+/// workspace directory of the exec root. Paths of generated files and of
+/// external repositories are not part of the workspace and return `None`.
+pub fn workspace_relative_path(file_name: &str, workspace: &Path) -> Option<PathBuf> {
+    let path = Path::new(file_name);
+    let relative = if path.is_absolute() {
+        if let Ok(relative) = path.strip_prefix(workspace) {
+            relative.to_path_buf()
+        } else {
+            let mut components = path.components();
+            components.find(|c| c.as_os_str() == "execroot")?;
+            // Skip the name of the main repository.
+            components.next()?;
+            components.as_path().to_path_buf()
+        }
+    } else {
+        path.to_path_buf()
+    };
+
+    let mut result = PathBuf::new();
+    for component in relative.components() {
+        match component {
+            Component::CurDir => {}
+            Component::Normal(name) => {
+                if result.as_os_str().is_empty() {
+                    let name = name.to_str()?;
+                    if name == "external" || name.starts_with("bazel-") {
+                        return None;
+                    }
+                }
+                result.push(name);
+            }
+            _ => return None,
+        }
+    }
+    (!result.as_os_str().is_empty()).then_some(result)
+}
+
+/// Groups `fixes` by the workspace relative path of the file they apply to,
+/// dropping duplicates and fixes for files outside of the workspace.
+///
+/// The same fix is usually reported more than once, e.g. when a crate is
+/// built in multiple configurations or by both the library and its tests.
+pub fn group_fixes(fixes: Vec<Fix>, workspace: &Path) -> BTreeMap<PathBuf, Vec<Fix>> {
+    let mut grouped: BTreeMap<PathBuf, Vec<Fix>> = BTreeMap::new();
+    for mut fix in fixes {
+        let path = match fix
+            .file_name()
+            .and_then(|file_name| workspace_relative_path(file_name, workspace))
+        {
+            Some(path) => path,
+            None => continue,
+        };
+        // The same file may be reported under different paths.
+        let file_name = path.to_string_lossy().into_owned();
+        for replacement in &mut fix.replacements {
+            replacement.file_name = file_name.clone();
+        }
+        grouped.entry(path).or_default().push(fix);
+    }
+    for fixes in grouped.values_mut() {
+        fixes.sort();
+        fixes.dedup_by(|a, b| a.replacements == b.replacements);
+    }
+    grouped
+}
+
+/// The result of applying fixes to the contents of a file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct AppliedFixes {
+    /// The fixed contents of the file.
+    pub content: String,
+
+    /// The fixes which were applied.
+    pub applied: Vec<Fix>,
+
+    /// The fixes which were skipped because they conflict with another fix
+    /// or the file changed since they were reported.
+    pub skipped: Vec<Fix>,
+}
+
+fn overlaps(a: &Replacement, b: &Replacement) -> bool {
+    (a.byte_start < b.byte_end && b.byte_start < a.byte_end) || a.byte_start == b.byte_start
+}
+
+/// Applies `fixes` to `content`. Fixes are applied atomically: a fix is only
+/// applied if every one of its replacements still matches the text rustc
+/// reported and none of them overlap a previously accepted fix.
+pub fn apply_fixes(content: &str, fixes: Vec<Fix>) -> AppliedFixes {
+    let mut applied: Vec<Fix> = Vec::new();
+    let mut skipped = Vec::new();
+    for fix in fixes {
+        let valid = fix.replacements.iter().enumerate().all(|(index, r)| {
+            content.get(r.byte_start..r.byte_end) == Some(r.original_text.as_str())
+                && !fix.replacements[..index].iter().any(|o| overlaps(o, r))
+                && !applied
+                    .iter()
+                    .flat_map(|f| &f.replacements)
+                    .any(|o| overlaps(o, r))
+        });
+        if valid {
+            applied.push(fix);
+        } else {
+            skipped.push(fix);
+        }
+    }
+
+    let mut replacements: Vec<&Replacement> =
+        applied.iter().flat_map(|f| &f.replacements).collect();
+    // Replace from the end of the file so earlier offsets stay valid.
+    replacements.sort_by_key(|r| Reverse(r.byte_start));
+    let mut fixed = content.to_owned();
+    for r in replacements {
+        fixed.replace_range(r.byte_start..r.byte_end, &r.replacement);
+    }
+
+    AppliedFixes {
+        content: fixed,
+        applied,
+        skipped,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn fix(file_name: &str, edits: &[(usize, usize, &str, &str)]) -> Fix {
+        Fix {
+            replacements: edits
+                .iter()
+                .map(
+                    |(byte_start, byte_end, original_text, replacement)| Replacement {
+                        file_name: file_name.to_owned(),
+                        byte_start: *byte_start,
+                        byte_end: *byte_end,
+                        replacement: (*replacement).to_owned(),
+                        original_text: (*original_text).to_owned(),
+                    },
+                )
+                .collect(),
+            code: None,
+            message: "fix".to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_parse_fixes() {
+        let content = r#"{"code":"unused_variables","message":"if this is intentional, prefix it with an underscore","replacements":[{"file_name":"src/lib.rs","byte_start":20,"byte_end":21,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","replacement":"_x","original_text":"x"}]}
+
+{"code":null,"message":"remove this","replacements":[]}
+"#;
+        let fixes = parse_fixes(content).unwrap();
+        assert_eq!(fixes.len(), 2);
+        assert_eq!(fixes[0].code.as_deref(), Some("unused_variables"));
+        assert_eq!(
+            fixes[0].replacements,
+            fix("src/lib.rs", &[(20, 21, "x", "_x")]).replacements
+        );
+        assert_eq!(fixes[1].code, None);
+
+        let err = parse_fixes("{}\n{\"message\":\"m\"}").unwrap_err();
+        assert!(err.starts_with("line 1: "), "{}", err);
+    }
+
+    #[test]
+    fn test_workspace_relative_path() {
+        let workspace = Path::new("/home/user/project");
+        let map = |p| workspace_relative_path(p, workspace);
+        assert_eq!(map("src/lib.rs"), Some(PathBuf::from("src/lib.rs")));
+        assert_eq!(map("./src/lib.rs"), Some(PathBuf::from("src/lib.rs")));
+        assert_eq!(
+            map("/home/user/project/src/lib.rs"),
+            Some(PathBuf::from("src/lib.rs"))
+        );
+        assert_eq!(
+            map("/home/user/.cache/bazel/_bazel_user/1234/sandbox/linux-sandbox/7/execroot/_main/pkg/src/lib.rs"),
+            Some(PathBuf::from("pkg/src/lib.rs"))
+        );
+        assert_eq!(map("external/crate_index__serde-1.0.0/src/lib.rs"), None);
+        assert_eq!(map("bazel-out/k8-fastbuild/bin/pkg/generated.rs"), None);
+        assert_eq!(
+            map("/usr/lib/rustlib/src/rust/library/core/src/lib.rs"),
+            None
+        );
+        assert_eq!(map("../outside.rs"), None);
+    }
+
+    #[test]
+    fn test_group_fixes_dedupes() {
+        let workspace = Path::new("/ws");
+        let grouped = group_fixes(
+            vec![
+                fix("src/lib.rs", &[(4, 5, "x", "_x")]),
+                fix("./src/lib.rs", &[(4, 5, "x", "_x")]),
+                fix("external/dep/src/lib.rs", &[(0, 1, "l", "")]),
+                fix("src/main.rs", &[(0, 1, "f", "")]),
+            ],
+            workspace,
+        );
+        assert_eq!(
+            grouped.keys().collect::<Vec<_>>(),
+            vec![Path::new("src/lib.rs"), Path::new("src/main.rs")]
+        );
+        assert_eq!(grouped[Path::new("src/lib.rs")].len(), 1);
+    }
+
+    #[test]
+    fn test_apply_fixes() {
+        let content = "let x = 1;\nlet y = 2;\n";
+        let result = apply_fixes(
+            content,
+            vec![
+                fix("lib.rs", &[(4, 5, "x", "_x")]),
+                fix("lib.rs", &[(15, 16, "y", "_y")]),
+                // Conflicts with the first fix.
+                fix("lib.rs", &[(4, 5, "x", "z"), (0, 3, "let", "const")]),
+                // Out of bounds.
+                fix("lib.rs", &[(100, 101, "", "")]),
+            ],
+        );
+        assert_eq!(result.content, "let _x = 1;\nlet _y = 2;\n");
+        assert_eq!(result.applied.len(), 2);
+        assert_eq!(result.skipped.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_fixes_skips_outdated_fixes() {
+        let content = "let x = 1;\nlet y = 2;\n";
+        let result = apply_fixes(
+            content,
+            vec![
+                // The second replacement no longer matches the file, so the
+                // first one must not be applied either.
+                fix("lib.rs", &[(4, 5, "x", "_x"), (15, 16, "z", "_z")]),
+                fix("lib.rs", &[(0, 3, "var", "const")]),
+            ],
+        );
+        assert_eq!(result.content, content);
+        assert!(result.applied.is_empty());
+        assert_eq!(result.skipped.len(), 2);
+    }
+
+    #[test]
+    fn test_apply_fixes_rejects_invalid_char_boundaries() {
+        let result = apply_fixes("\"é\"", vec![fix("lib.rs", &[(1, 2, "é", "e")])]);
+        assert_eq!(result.content, "\"é\"");
+        assert_eq!(result.skipped.len(), 1);
+    }
+}
@@ -0,0 +1,90 @@
+//! A tool for applying the machine applicable suggestions of rustc collected
+//! during a Bazel build to the sources of the workspace.
+//!
+//! Fixes are written by process_wrapper when building with
+//! `--@rules_rust//rust/settings:experimental_fixes_output`. This tool
+//! searches `bazel-out` (or the given files and directories) for files ending
+//! in `.fixes.jsonl` and applies the fixes they contain.
+
+use std::fs;
+
+use bazel_out::{collect_files, parse_args};
+use rust_fix_lib::{apply_fixes, group_fixes, parse_fixes, FIXES_FILE_SUFFIX};
+
+const USAGE: &str = "Usage: rust_fix [--dry-run] [PATH...]
+
+Applies the machine applicable rustc suggestions found in the given fixes
+files, or in all files ending in `.fixes.jsonl` within the given directories.
+Defaults to searching the `bazel-out` directory of the workspace.
+
+Options:
+    --dry-run    Print the fixes which would be applied without modifying any files.";
+
+fn main() {
+    let mut dry_run = false;
+    let config = parse_args(USAGE, |arg, _| match arg {
+        "--dry-run" => {
+            dry_run = true;
+            Ok(())
+        }
+        _ => Err(format!("Unknown option: {}", arg)),
+    });
+
+    let fixes_files = collect_files(&config.paths, FIXES_FILE_SUFFIX);
+    if fixes_files.is_empty() {
+        eprintln!(
+            "No `*{}` files found. Build with process_wrapper's `--fixes-output` flag first.",
+            FIXES_FILE_SUFFIX
+        );
+        return;
+    }
+
+    let mut fixes = Vec::new();
+    for fixes_file in &fixes_files {
+        let content = fs::read_to_string(fixes_file)
+            .unwrap_or_else(|e| panic!("Failed to read {}: {}", fixes_file.display(), e));
+        match parse_fixes(&content) {
+            Ok(parsed) => fixes.extend(parsed),
+            Err(e) => eprintln!("Skipping {}: {}", fixes_file.display(), e),
+        }
+    }
+
+    let mut applied = 0;
+    let mut skipped = 0;
+    for (path, fixes) in group_fixes(fixes, &config.workspace) {
+        let source = config.workspace.join(&path);
+        let content = match fs::read_to_string(&source) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Skipping {}: {}", path.display(), e);
+                skipped += fixes.len();
+                continue;
+            }
+        };
+        let result = apply_fixes(&content, fixes);
+        for fix in &result.applied {
+            let code = fix.code.as_deref().unwrap_or("suggestion");
+            println!("{}: [{}] {}", path.display(), code, fix.message);
+        }
+        for fix in &result.skipped {
+            eprintln!(
+                "{}: skipped conflicting or outdated fix: {}",
+                path.display(),
+                fix.message
+            );
+        }
+        applied += result.applied.len();
+        skipped += result.skipped.len();
+        if !dry_run && !result.applied.is_empty() {
+            fs::write(&source, result.content)
+                .unwrap_or_else(|e| panic!("Failed to write {}: {}", source.display(), e));
+        }
+    }
+
+    println!(
+        "{} {} fix(es), skipped {}.",
+        if dry_run { "Would apply" } else { "Applied" },
+        applied,
+        skipped
+    );
+}
@@ -1,9 +1,10 @@
 //! Utilities for parsing [Args](https://bazel.build/rules/lib/builtins/Args.html) param files.
 
-use std::path::Path;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
 
 /// The format for an [Args param file[(https://bazel.build/rules/lib/builtins/Args.html#set_param_file_format).
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ActionArgsFormat {
     /// Each item (argument name or value) is written verbatim to the param
     /// file with a newline character following it.
@@ -28,20 +29,72 @@ impl Default for ActionArgsFormat {
 /// Parsed [`ctx.action.args`](https://bazel.build/rules/lib/builtins/Args.html) params.
 type ActionArgv = Vec<String>;
 
+/// Splits off the first line of `text`, returning it without its line ending
+/// along with the remaining text.
+fn split_line(text: &str) -> (&str, &str) {
+    let (line, rest) = text.split_once('\n').unwrap_or((text, ""));
+    (line.strip_suffix('\r').unwrap_or(line), rest)
+}
+
+/// Unquotes a shell-quoted item at the start of `text` as written by Bazel,
+/// i.e. single quoted strings joined by `\'` for each literal quote. The item
+/// may span multiple lines. Returns the item and the text following it, or
+/// `None` if `text` doesn't start with a complete quoted item.
+fn unquote(text: &str) -> Option<(String, &str)> {
+    let mut arg = String::new();
+    let mut rest = text;
+    loop {
+        if let Some(quoted) = rest.strip_prefix('\'') {
+            let end = quoted.find('\'')?;
+            arg.push_str(&quoted[..end]);
+            rest = &quoted[end + 1..];
+        } else if let Some(unquoted) = rest.strip_prefix("\\'") {
+            arg.push('\'');
+            rest = unquoted;
+        } else {
+            break;
+        }
+    }
+    let (line, next) = split_line(rest);
+    line.is_empty().then_some((arg, next))
+}
+
+/// Parses the items of a [ActionArgsFormat::Shell] param file. Items which
+/// aren't quoted the way Bazel quotes them are kept verbatim.
+fn parse_shell(text: &str) -> ActionArgv {
+    let mut args = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some((arg, next)) = rest.starts_with('\'').then(|| unquote(rest)).flatten() {
+            args.push(arg);
+            rest = next;
+            continue;
+        }
+        let (line, next) = split_line(rest);
+        args.push(line.to_owned());
+        rest = next;
+    }
+    args
+}
+
 /// Parse an [Args](https://bazel.build/rules/lib/builtins/Args.html) param file string into an argv list.
+///
+/// [ActionArgsFormat::FlagPerLine] lines of the form `--flag=value` are split
+/// into the flag and its value.
 pub fn parse_args_with_fmt(text: String, fmt: ActionArgsFormat) -> ActionArgv {
-    text.lines()
-        .map(|s| match fmt {
-            ActionArgsFormat::Shell => {
-                if s.starts_with('\'') && s.ends_with('\'') {
-                    s[1..s.len() - 1].to_owned()
-                } else {
-                    s.to_owned()
+    match fmt {
+        ActionArgsFormat::Multiline => text.lines().map(str::to_owned).collect(),
+        ActionArgsFormat::Shell => parse_shell(&text),
+        ActionArgsFormat::FlagPerLine => text
+            .lines()
+            .flat_map(|line| match line.split_once('=') {
+                Some((flag, value)) if line.starts_with("--") => {
+                    vec![flag.to_owned(), value.to_owned()]
                 }
-            }
-            _ => s.to_owned(),
-        })
-        .collect()
+                _ => vec![line.to_owned()],
+            })
+            .collect(),
+    }
 }
 
 /// Parse an [Args](https://bazel.build/rules/lib/builtins/Args.html) param file string into an argv list.
@@ -64,6 +117,96 @@ pub fn try_parse_args(path: &Path) -> Result<ActionArgv, std::io::Error> {
     Ok(parse_args(text))
 }
 
+/// Shell-quotes `arg` the way Bazel does: arguments made up of safe characters
+/// are written as is, anything else is wrapped in single quotes.
+fn shell_escape(arg: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "@%-_+:,./".contains(c);
+    if arg.is_empty() {
+        Cow::Borrowed("''")
+    } else if arg.chars().all(is_safe) {
+        Cow::Borrowed(arg)
+    } else {
+        Cow::Owned(format!("'{}'", arg.replace('\'', "'\\''")))
+    }
+}
+
+/// Write an argv list as the contents of an [Args](https://bazel.build/rules/lib/builtins/Args.html)
+/// param file, matching what Bazel writes for `fmt`.
+///
+/// [ActionArgsFormat::Multiline] can't represent arguments containing line
+/// breaks and [ActionArgsFormat::FlagPerLine] joins each flag with the value
+/// following it, so only [ActionArgsFormat::Shell] round-trips every argv.
+pub fn write_args_with_fmt<S: AsRef<str>>(args: &[S], fmt: ActionArgsFormat) -> String {
+    let mut text = String::new();
+    let mut args = args.iter().map(AsRef::as_ref).peekable();
+    while let Some(arg) = args.next() {
+        match fmt {
+            ActionArgsFormat::Multiline => text.push_str(arg),
+            ActionArgsFormat::Shell => text.push_str(&shell_escape(arg)),
+            ActionArgsFormat::FlagPerLine => {
+                text.push_str(arg);
+                if arg.starts_with("--") {
+                    if let Some(value) = args.next_if(|value| !value.starts_with("--")) {
+                        text.push('=');
+                        text.push_str(value);
+                    }
+                }
+            }
+        }
+        text.push('\n');
+    }
+    text
+}
+
+/// Write an argv list as the contents of an [Args](https://bazel.build/rules/lib/builtins/Args.html) param file.
+pub fn write_args<S: AsRef<str>>(args: &[S]) -> String {
+    write_args_with_fmt(args, ActionArgsFormat::default())
+}
+
+/// Replace each `@path` argument with the arguments parsed from the param file
+/// at `path`, recursively expanding any param files it references in turn.
+pub fn expand_param_files(
+    args: ActionArgv,
+    fmt: ActionArgsFormat,
+) -> Result<ActionArgv, std::io::Error> {
+    let mut expanded = Vec::new();
+    expand_param_files_into(args, fmt, &mut Vec::new(), &mut expanded)?;
+    Ok(expanded)
+}
+
+fn expand_param_files_into(
+    args: ActionArgv,
+    fmt: ActionArgsFormat,
+    stack: &mut Vec<PathBuf>,
+    expanded: &mut ActionArgv,
+) -> Result<(), std::io::Error> {
+    for arg in args {
+        let path = match arg.strip_prefix('@') {
+            Some(path) if !path.is_empty() => PathBuf::from(path),
+            _ => {
+                expanded.push(arg);
+                continue;
+            }
+        };
+        if stack.contains(&path) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("param file {} references itself", path.display()),
+            ));
+        }
+        let nested = try_parse_args_with_fmt(&path, fmt).map_err(|e| {
+            std::io::Error::new(
+                e.kind(),
+                format!("failed to read param file {}: {}", path.display(), e),
+            )
+        })?;
+        stack.push(path);
+        expand_param_files_into(nested, fmt, stack, expanded)?;
+        stack.pop();
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use std::path::PathBuf;
@@ -100,11 +243,79 @@ mod test {
 
         let args = parse_args_with_fmt(text, ActionArgsFormat::FlagPerLine);
         assert_eq!(
-            vec!["foo", "-bar", "'baz'", "'--qux=quux'", "--quuz='corge'"],
+            vec!["foo", "-bar", "'baz'", "'--qux=quux'", "--quuz", "'corge'"],
             args
         )
     }
 
+    #[test]
+    fn test_shell_quoting() {
+        // The expected output matches Bazel's `ShellEscaper`.
+        let args = [
+            "--flag=a/b.rs",
+            "",
+            "with space",
+            "it's",
+            "'quoted'",
+            "multi\nline",
+            "$HOME",
+        ];
+        let text = write_args_with_fmt(&args, ActionArgsFormat::Shell);
+        assert_eq!(
+            text,
+            "'--flag=a/b.rs'\n''\n'with space'\n'it'\\''s'\n''\\''quoted'\\'''\n'multi\nline'\n'$HOME'\n"
+        );
+        assert_eq!(parse_args_with_fmt(text, ActionArgsFormat::Shell), args);
+    }
+
+    #[test]
+    fn test_shell_unterminated_quote() {
+        let text = "'foo\nbar\n'baz' qux\n".to_owned();
+
+        let args = parse_args_with_fmt(text, ActionArgsFormat::Shell);
+        assert_eq!(vec!["'foo", "bar", "'baz' qux"], args)
+    }
+
+    #[test]
+    fn test_write_args() {
+        let args = [
+            "--crate-name",
+            "foo",
+            "--cfg=feature=\"bar\"",
+            "--test",
+            "src/lib.rs",
+        ];
+
+        assert_eq!(
+            write_args_with_fmt(&args, ActionArgsFormat::Multiline),
+            "--crate-name\nfoo\n--cfg=feature=\"bar\"\n--test\nsrc/lib.rs\n"
+        );
+        assert_eq!(
+            write_args_with_fmt(&args, ActionArgsFormat::FlagPerLine),
+            "--crate-name=foo\n--cfg=feature=\"bar\"\n--test=src/lib.rs\n"
+        );
+        assert_eq!(
+            parse_args_with_fmt(
+                write_args_with_fmt(&args, ActionArgsFormat::FlagPerLine),
+                ActionArgsFormat::FlagPerLine
+            ),
+            vec![
+                "--crate-name",
+                "foo",
+                "--cfg",
+                "feature=\"bar\"",
+                "--test",
+                "src/lib.rs"
+            ]
+        );
+        for fmt in [ActionArgsFormat::Multiline, ActionArgsFormat::Shell] {
+            assert_eq!(
+                parse_args_with_fmt(write_args_with_fmt(&args, fmt), fmt),
+                args
+            );
+        }
+    }
+
     #[test]
     fn test_from_file() {
         let text = TEST_ARGS.join("\n");
@@ -122,4 +333,54 @@ mod test {
             args
         )
     }
+
+    #[test]
+    fn test_expand_param_files() {
+        let test_tempdir = PathBuf::from(std::env::var("TEST_TMPDIR").unwrap());
+        let outer = test_tempdir.join("test_expand_outer.params");
+        let inner = test_tempdir.join("test_expand_inner.params");
+        let cycle = test_tempdir.join("test_expand_cycle.params");
+
+        let outer_args = [
+            "--outer".to_owned(),
+            "with space".to_owned(),
+            format!("@{}", inner.display()),
+        ];
+        std::fs::write(&outer, write_args(&outer_args)).unwrap();
+        std::fs::write(&inner, write_args(&["--inner", "it's"])).unwrap();
+        std::fs::write(&cycle, write_args(&[format!("@{}", cycle.display())])).unwrap();
+
+        let args = expand_param_files(
+            vec![
+                "first".to_owned(),
+                format!("@{}", outer.display()),
+                "@".to_owned(),
+                "last".to_owned(),
+            ],
+            ActionArgsFormat::Shell,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                "first",
+                "--outer",
+                "with space",
+                "--inner",
+                "it's",
+                "@",
+                "last"
+            ],
+            args
+        );
+
+        let err = expand_param_files(
+            vec![format!("@{}", cycle.display())],
+            ActionArgsFormat::Shell,
+        )
+        .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        let missing = format!("@{}", test_tempdir.join("missing.params").display());
+        assert!(expand_param_files(vec![missing], ActionArgsFormat::Shell).is_err());
+    }
 }
@@ -0,0 +1,134 @@
+//! Utilities for tools which read the files process_wrapper writes to
+//! `bazel-out` during a build.
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// The paths given to a tool on its command line.
+#[derive(Debug)]
+pub struct Paths {
+    /// The path of the Bazel workspace root.
+    pub workspace: PathBuf,
+
+    /// Files, or directories to search for them. Defaults to the `bazel-out`
+    /// directory of the workspace.
+    pub paths: Vec<PathBuf>,
+}
+
+/// Prints `message` followed by `usage` and exits with an error.
+fn usage_error(message: &str, usage: &str) -> ! {
+    eprintln!("{}\n\n{}", message, usage);
+    process::exit(1);
+}
+
+/// Parse command line arguments and environment variables.
+///
+/// Paths are relative to the workspace, which is `BUILD_WORKSPACE_DIRECTORY`
+/// when run with `bazel run`. Options other than `--help` are passed to
+/// `option` along with the remaining arguments, from which it may take its
+/// value.
+pub fn parse_args<F>(usage: &str, mut option: F) -> Paths
+where
+    F: FnMut(&str, &mut dyn Iterator<Item = String>) -> Result<(), String>,
+{
+    let workspace = env::var_os("BUILD_WORKSPACE_DIRECTORY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| env::current_dir().expect("Failed to get the current directory"));
+
+    let mut paths = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", usage);
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') => {
+                if let Err(message) = option(&arg, &mut args) {
+                    usage_error(&message, usage);
+                }
+            }
+            _ => paths.push(workspace.join(arg)),
+        }
+    }
+    if paths.is_empty() {
+        paths.push(workspace.join("bazel-out"));
+    }
+
+    Paths { workspace, paths }
+}
+
+/// Recursively finds the files within `dir` whose names end in `suffix`.
+/// Symlinks are not followed to avoid descending into the external
+/// repositories of the output base.
+pub fn find_files(dir: &Path, suffix: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir)? {
+            let entry = entry?;
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                pending.push(entry.path());
+            } else if file_type.is_file()
+                && entry
+                    .file_name()
+                    .to_str()
+                    .is_some_and(|name| name.ends_with(suffix))
+            {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns the files of `paths`, searching those which are directories for
+/// files whose names end in `suffix`.
+pub fn collect_files(paths: &[PathBuf], suffix: &str) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(find_files(path, suffix).unwrap_or_else(|e| {
+                panic!(
+                    "Failed to search {} for `*{}` files: {}",
+                    path.display(),
+                    suffix,
+                    e
+                )
+            }));
+        } else {
+            files.push(path.clone());
+        }
+    }
+    files
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_collect_files() {
+        let dir = env::temp_dir().join(format!("bazel_out_test_{}", process::id()));
+        fs::create_dir_all(dir.join("k8-fastbuild/bin/pkg")).unwrap();
+        fs::write(dir.join("k8-fastbuild/bin/pkg/b.fixes.jsonl"), "").unwrap();
+        fs::write(dir.join("k8-fastbuild/bin/a.fixes.jsonl"), "").unwrap();
+        fs::write(dir.join("k8-fastbuild/bin/a.resources.json"), "").unwrap();
+        let explicit = dir.join("explicit.json");
+
+        assert_eq!(
+            collect_files(&[dir.clone(), explicit.clone()], ".fixes.jsonl"),
+            [
+                dir.join("k8-fastbuild/bin/a.fixes.jsonl"),
+                dir.join("k8-fastbuild/bin/pkg/b.fixes.jsonl"),
+                explicit,
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -15,15 +15,48 @@
 //! - `RUNFILES_DIR`: Location of the test's runfiles.
 //! - `VERBOSE_COVERAGE`: Print debug info from the coverage scripts
 //!
+//! The following optional environment variables configure which source files
+//! are reported, as regexes evaluated by `llvm-cov`:
+//! - `RUST_COVERAGE_INCLUDE_REGEX`: Only report files whose path matches.
+//! - `RUST_COVERAGE_EXCLUDE_REGEX`: Don't report files whose path matches.
+//!   Defaults to files of external repositories and under `/tmp/`, set it to
+//!   an empty string to report them.
+//!
 //! The script looks in $COVERAGE_DIR for the Rust metadata coverage files
 //! (profraw) and uses lcov to get the coverage data. The coverage data
 //! is placed in $COVERAGE_DIR as a `coverage.dat` file.
+//!
+//! Besides the test binary, the instrumented binaries and libraries of the
+//! test's runfiles which have coverage data, like helpers the test executes,
+//! are reported. Branch and MC/DC coverage are reported for crates which
+//! `//rust/settings:experimental_coverage_options` instruments for them.
+//!
+//! The paths of the source files are made relative to the execution root.
+//! Generated source files, under `bazel-out/`, are dropped if
+//! `RUST_COVERAGE_EXCLUDE_GENERATED` is set to `1` or `true`.
 
+mod lcov;
+
+use std::collections::BTreeSet;
 use std::env;
 use std::fs;
+use std::io::{self, Read};
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
+use std::thread;
+
+use crate::lcov::Report;
+
+/// The default of `RUST_COVERAGE_EXCLUDE_REGEX`.
+const DEFAULT_EXCLUDE_REGEX: &str = ".*external/.+|/tmp/.+";
+
+/// Names of the sections holding coverage mappings, in ELF and Mach-O
+/// (`__llvm_covmap`) and COFF (`.lcovmap`) objects.
+const COVERAGE_MAPPING_SECTIONS: [&[u8]; 2] = [b"__llvm_covmap", b".lcovmap"];
+
+/// The size of the chunks in which objects are scanned for coverage mappings.
+const SCAN_CHUNK_SIZE: usize = 64 * 1024;
 
 macro_rules! debug_log {
     ($($arg:tt)*) => {
@@ -46,15 +79,30 @@ fn find_metadata_file(execroot: &Path, runfiles_dir: &Path, path: &str) -> PathB
     runfiles_dir.join(path)
 }
 
-fn find_test_binary(execroot: &Path, runfiles_dir: &Path) -> PathBuf {
+/// Returns the value of the environment variable `name`, which Bazel sets when
+/// running coverage.
+fn required_env(name: &str) -> Result<String, String> {
+    env::var(name).map_err(|_| {
+        format!("`{name}` is not set, collect_coverage must be run by `bazel coverage`")
+    })
+}
+
+fn find_test_binary(execroot: &Path, runfiles_dir: &Path) -> Result<PathBuf, String> {
     let test_binary = runfiles_dir
-        .join(env::var("TEST_WORKSPACE").unwrap())
-        .join(env::var("TEST_BINARY").unwrap());
+        .join(required_env("TEST_WORKSPACE")?)
+        .join(required_env("TEST_BINARY")?);
 
     if !test_binary.exists() {
         let configuration = runfiles_dir
             .strip_prefix(execroot)
-            .expect("RUNFILES_DIR should be relative to ROOT")
+            .map_err(|_| {
+                format!(
+                    "TEST_BINARY was not found in the runfiles and RUNFILES_DIR ({}) isn't \
+                    under ROOT ({}) to look for it in the output tree",
+                    runfiles_dir.display(),
+                    execroot.display()
+                )
+            })?
             .components()
             .enumerate()
             .filter_map(|(i, part)| {
@@ -72,23 +120,220 @@ fn find_test_binary(execroot: &Path, runfiles_dir: &Path) -> PathBuf {
 
         let test_binary = execroot
             .join(configuration)
-            .join(env::var("TEST_BINARY").unwrap());
+            .join(required_env("TEST_BINARY")?);
 
         debug_log!(
             "TEST_BINARY is not found in runfiles. Falling back to: {}",
             test_binary.display()
         );
 
-        test_binary
+        Ok(test_binary)
     } else {
-        test_binary
+        Ok(test_binary)
+    }
+}
+
+/// Returns whether `path` is an executable or a shared library.
+#[cfg(unix)]
+fn is_object_file(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Returns whether `path` is an executable or a shared library.
+#[cfg(windows)]
+fn is_object_file(path: &Path) -> bool {
+    path.is_file()
+        && path
+            .extension()
+            .is_some_and(|ext| ext == "exe" || ext == "dll")
+}
+
+/// Returns whether `object` is instrumented for coverage. The object is
+/// scanned in chunks so that only a bounded part of it is held in memory.
+fn has_coverage_mapping(mut object: impl Read) -> io::Result<bool> {
+    // Keep the end of the previous chunk to find names spanning two chunks.
+    let overlap = COVERAGE_MAPPING_SECTIONS
+        .iter()
+        .map(|section| section.len() - 1)
+        .max()
+        .unwrap_or_default();
+    let mut buffer = vec![0; overlap + SCAN_CHUNK_SIZE];
+    let mut kept = 0;
+    loop {
+        let read = match object.read(&mut buffer[kept..]) {
+            Ok(0) => return Ok(false),
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let filled = kept + read;
+        let contents = &buffer[..filled];
+        if COVERAGE_MAPPING_SECTIONS.iter().any(|section| {
+            contents
+                .windows(section.len())
+                .any(|window| window == *section)
+        }) {
+            return Ok(true);
+        }
+        kept = overlap.min(filled);
+        buffer.copy_within(filled - kept..filled, 0);
+    }
+}
+
+/// Returns the instrumented objects under `dir`, following symlinks. Each
+/// directory is only visited once, as symlinks may form cycles.
+fn find_instrumented_objects(dir: &Path) -> Vec<PathBuf> {
+    let mut objects = Vec::new();
+    let mut visited = BTreeSet::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        if !fs::canonicalize(&dir).is_ok_and(|canonical| visited.insert(canonical)) {
+            continue;
+        }
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.is_dir() {
+                dirs.push(path);
+            } else if is_object_file(&path)
+                && fs::File::open(&path)
+                    .and_then(has_coverage_mapping)
+                    .unwrap_or(false)
+            {
+                objects.push(path);
+            }
+        }
+    }
+    objects.sort();
+    objects
+}
+
+/// Returns whether an lcov report has any line which was hit.
+fn has_line_hits(report: &str) -> bool {
+    report.lines().any(|line| {
+        line.strip_prefix("LH:")
+            .and_then(|hits| hits.trim().parse::<u64>().ok())
+            .is_some_and(|hits| hits > 0)
+    })
+}
+
+/// Returns the `objects` which the profile has coverage data for. Each object
+/// is probed by its own `llvm-cov` process, in batches of one per CPU.
+fn objects_with_coverage(
+    llvm_cov: &Path,
+    profdata_file: &Path,
+    objects: &[PathBuf],
+    args: &[String],
+) -> Result<Vec<PathBuf>, String> {
+    let batch_size = thread::available_parallelism().map_or(1, |n| n.get());
+    let mut covered = Vec::new();
+    for batch in objects.chunks(batch_size) {
+        let summaries: Vec<Result<String, String>> = thread::scope(|scope| {
+            let probes: Vec<_> = batch
+                .iter()
+                .map(|object| {
+                    scope.spawn(move || {
+                        llvm_cov_export(llvm_cov, profdata_file, std::slice::from_ref(object), args)
+                    })
+                })
+                .collect();
+            probes
+                .into_iter()
+                .map(|probe| {
+                    probe
+                        .join()
+                        .unwrap_or_else(|_| Err("llvm-cov probe panicked".to_owned()))
+                })
+                .collect()
+        });
+        for (object, summary) in batch.iter().zip(summaries) {
+            if has_line_hits(&summary?) {
+                debug_log!("Reporting coverage of {}", object.display());
+                covered.push(object.clone());
+            } else {
+                debug_log!("No coverage data for {}", object.display());
+            }
+        }
+    }
+    Ok(covered)
+}
+
+/// Runs `llvm-cov export` of `objects` to lcov and returns its output.
+fn llvm_cov_export(
+    llvm_cov: &Path,
+    profdata_file: &Path,
+    objects: &[PathBuf],
+    args: &[String],
+) -> Result<String, String> {
+    let mut llvm_cov_cmd = process::Command::new(llvm_cov);
+    llvm_cov_cmd
+        .arg("export")
+        .arg("-format=lcov")
+        .arg("-instr-profile")
+        .arg(profdata_file)
+        .args(args);
+    for (i, object) in objects.iter().enumerate() {
+        if i > 0 {
+            llvm_cov_cmd.arg("-object");
+        }
+        llvm_cov_cmd.arg(object);
+    }
+    llvm_cov_cmd.stdout(process::Stdio::piped());
+
+    let output = run(&mut llvm_cov_cmd)?;
+
+    // Parse the child process's stdout to a string now that it's complete.
+    debug_log!("Parsing llvm-cov output");
+    String::from_utf8(output.stdout).map_err(|e| format!("llvm-cov printed invalid UTF-8: {e}"))
+}
+
+/// Runs `command` to completion, failing if it doesn't succeed. Its stderr is
+/// inherited so that its errors are shown.
+fn run(command: &mut process::Command) -> Result<process::Output, String> {
+    debug_log!("Spawning {:#?}", command);
+    let output = command
+        .output()
+        .map_err(|e| format!("failed to spawn {:?}: {e}", command.get_program()))?;
+    if !output.status.success() {
+        return Err(format!(
+            "{:?} failed with {}, see its output above\ncommand: {:?}",
+            command.get_program(),
+            output.status,
+            command
+        ));
     }
+    Ok(output)
+}
+
+/// Returns the path of a source file relative to the execution root.
+fn normalize_source_file(path: &str, execroot: &str) -> String {
+    // Sources are compiled with their paths remapped under `/proc/self/cwd`,
+    // which llvm-cov may prefix with its compilation directory.
+    let path = match path.find("/proc/self/cwd/") {
+        Some(i) => &path[i + "/proc/self/cwd/".len()..],
+        None => path,
+    };
+    path.strip_prefix(execroot)
+        .and_then(|path| path.strip_prefix('/'))
+        .unwrap_or(path)
+        .to_owned()
 }
 
 fn main() {
-    let coverage_dir = PathBuf::from(env::var("COVERAGE_DIR").unwrap());
-    let execroot = PathBuf::from(env::var("ROOT").unwrap());
-    let mut runfiles_dir = PathBuf::from(env::var("RUNFILES_DIR").unwrap());
+    if let Err(e) = collect_coverage() {
+        eprintln!("collect_coverage: error: {e}");
+        process::exit(1);
+    }
+}
+
+fn collect_coverage() -> Result<(), String> {
+    let coverage_dir = PathBuf::from(required_env("COVERAGE_DIR")?);
+    let execroot = PathBuf::from(required_env("ROOT")?);
+    let mut runfiles_dir = PathBuf::from(required_env("RUNFILES_DIR")?);
 
     if !runfiles_dir.is_absolute() {
         runfiles_dir = execroot.join(runfiles_dir);
@@ -99,19 +344,20 @@ fn main() {
 
     let coverage_output_file = coverage_dir.join("coverage.dat");
     let profdata_file = coverage_dir.join("coverage.profdata");
-    let llvm_cov = find_metadata_file(
-        &execroot,
-        &runfiles_dir,
-        &env::var("RUST_LLVM_COV").unwrap(),
-    );
+    let llvm_cov = find_metadata_file(&execroot, &runfiles_dir, &required_env("RUST_LLVM_COV")?);
     let llvm_profdata = find_metadata_file(
         &execroot,
         &runfiles_dir,
-        &env::var("RUST_LLVM_PROFDATA").unwrap(),
+        &required_env("RUST_LLVM_PROFDATA")?,
     );
-    let test_binary = find_test_binary(&execroot, &runfiles_dir);
-    let profraw_files: Vec<PathBuf> = fs::read_dir(coverage_dir)
-        .unwrap()
+    let test_binary = find_test_binary(&execroot, &runfiles_dir)?;
+    let profraw_files: Vec<PathBuf> = fs::read_dir(&coverage_dir)
+        .map_err(|e| {
+            format!(
+                "failed to read COVERAGE_DIR {}: {e}",
+                coverage_dir.display()
+            )
+        })?
         .flatten()
         .filter_map(|entry| {
             let path = entry.path();
@@ -123,6 +369,12 @@ fn main() {
             None
         })
         .collect();
+    if profraw_files.is_empty() {
+        return Err(format!(
+            "no .profraw files in {}, was the test built with coverage instrumentation?",
+            coverage_dir.display()
+        ));
+    }
 
     let mut llvm_profdata_cmd = process::Command::new(llvm_profdata);
     llvm_profdata_cmd
@@ -131,51 +383,157 @@ fn main() {
         .args(profraw_files)
         .arg("--output")
         .arg(&profdata_file);
+    run(&mut llvm_profdata_cmd)?;
 
-    debug_log!("Spawning {:#?}", llvm_profdata_cmd);
-    let status = llvm_profdata_cmd
-        .status()
-        .expect("Failed to spawn llvm-profdata process");
-
-    if !status.success() {
-        process::exit(status.code().unwrap_or(1));
+    let exclude_regex = env::var("RUST_COVERAGE_EXCLUDE_REGEX")
+        .unwrap_or_else(|_| DEFAULT_EXCLUDE_REGEX.to_owned());
+    let include_regex = env::var("RUST_COVERAGE_INCLUDE_REGEX")
+        .ok()
+        .filter(|regex| !regex.is_empty());
+    let exclude_generated = env::var("RUST_COVERAGE_EXCLUDE_GENERATED")
+        .is_ok_and(|value| value == "1" || value == "true");
+    let mut filter_args = Vec::new();
+    if !exclude_regex.is_empty() {
+        filter_args.push(format!("-ignore-filename-regex={exclude_regex}"));
     }
 
-    let mut llvm_cov_cmd = process::Command::new(llvm_cov);
-    llvm_cov_cmd
-        .arg("export")
-        .arg("-format=lcov")
-        .arg("-instr-profile")
-        .arg(&profdata_file)
-        .arg("-ignore-filename-regex='.*external/.+'")
-        .arg("-ignore-filename-regex='/tmp/.+'")
-        .arg(format!("-path-equivalence=.,'{}'", execroot.display()))
-        .arg(test_binary)
-        .stdout(process::Stdio::piped());
+    // Report the other instrumented objects of the runfiles, like binaries
+    // the test spawned or those of other repositories, when the profile has
+    // data for them.
+    let mut seen: BTreeSet<PathBuf> = fs::canonicalize(&test_binary).into_iter().collect();
+    let candidates: Vec<PathBuf> = find_instrumented_objects(&runfiles_dir)
+        .into_iter()
+        .filter(|object| seen.insert(fs::canonicalize(object).unwrap_or_else(|_| object.clone())))
+        .collect();
+    let summary_args = [filter_args.clone(), vec!["-summary-only".to_owned()]].concat();
+    let mut objects = vec![test_binary.clone()];
+    objects.extend(objects_with_coverage(
+        &llvm_cov,
+        &profdata_file,
+        &candidates,
+        &summary_args,
+    )?);
 
-    debug_log!("Spawning {:#?}", llvm_cov_cmd);
-    let child = llvm_cov_cmd
-        .spawn()
-        .expect("Failed to spawn llvm-cov process");
+    let mut export_args = filter_args.clone();
+    export_args.push(format!("-path-equivalence=.,'{}'", execroot.display()));
+    let report_str = llvm_cov_export(&llvm_cov, &profdata_file, &objects, &export_args)?;
+    let mut report = Report::parse(&report_str)
+        .map_err(|e| format!("failed to parse the lcov output of llvm-cov: {e}"))?;
 
-    let output = child.wait_with_output().expect("llvm-cov process failed");
+    // llvm-cov can only ignore files, so files which don't match the include
+    // regex are found by ignoring those which do.
+    if let Some(include_regex) = include_regex {
+        let mut not_included_args = summary_args;
+        not_included_args.push(format!("-ignore-filename-regex={include_regex}"));
+        let not_included =
+            llvm_cov_export(&llvm_cov, &profdata_file, &objects, &not_included_args)?;
+        let not_included = Report::parse(&not_included)
+            .map_err(|e| format!("failed to parse the lcov output of llvm-cov: {e}"))?;
+        let not_included: BTreeSet<_> = not_included.paths().map(str::to_owned).collect();
+        report.retain(|path| !not_included.contains(path));
+    }
 
-    // Parse the child process's stdout to a string now that it's complete.
-    debug_log!("Parsing llvm-cov output");
-    let report_str = std::str::from_utf8(&output.stdout).expect("Failed to parse llvm-cov output");
+    let execroot_str = execroot.display().to_string();
+    report.rename_files(|path| normalize_source_file(path, &execroot_str));
+    if exclude_generated {
+        report.retain(|path| !path.starts_with("bazel-out/"));
+    }
 
     debug_log!("Writing output to {}", coverage_output_file.display());
-    fs::write(
-        coverage_output_file,
-        report_str
-            .replace("#/proc/self/cwd/", "")
-            .replace(&execroot.display().to_string(), ""),
-    )
-    .unwrap();
+    fs::write(&coverage_output_file, report.to_lcov()).map_err(|e| {
+        format!(
+            "failed to write the coverage report to {}: {e}",
+            coverage_output_file.display()
+        )
+    })?;
 
     // Destroy the intermediate binary file so lcov_merger doesn't parse it twice.
     debug_log!("Cleaning up {}", profdata_file.display());
-    fs::remove_file(profdata_file).unwrap();
+    fs::remove_file(&profdata_file)
+        .map_err(|e| format!("failed to remove {}: {e}", profdata_file.display()))?;
 
     debug_log!("Success!");
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const REPORT: &str = "\
+SF:src/lib.rs
+DA:1,1
+LF:1
+LH:1
+end_of_record
+SF:src/generated.rs
+DA:1,0
+LF:1
+LH:0
+end_of_record
+";
+
+    #[test]
+    fn test_has_coverage_mapping() {
+        let scan = |contents: &[u8]| has_coverage_mapping(contents).unwrap();
+        assert!(scan(b"\x7fELF...__llvm_covmap..."));
+        assert!(scan(b"MZ....lcovmap$M..."));
+        assert!(!scan(b"\x7fELF...__llvm_prf_cnts..."));
+
+        // Section names spanning two chunks are found as well.
+        let mut object = vec![0; SCAN_CHUNK_SIZE - 4];
+        object.extend_from_slice(b"__llvm_covmap");
+        object.resize(3 * SCAN_CHUNK_SIZE, 0);
+        assert!(scan(&object));
+        object.truncate(SCAN_CHUNK_SIZE - 4);
+        object.resize(3 * SCAN_CHUNK_SIZE, 0);
+        assert!(!scan(&object));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_instrumented_objects_symlink_cycle() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = env::temp_dir().join(format!("collect_coverage_test_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("pkg")).unwrap();
+        for (name, contents) in [
+            ("pkg/test", "__llvm_covmap"),
+            ("pkg/tool", "__llvm_prf_cnts"),
+        ] {
+            fs::write(dir.join(name), contents).unwrap();
+            fs::set_permissions(dir.join(name), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        symlink(&dir, dir.join("pkg/loop")).unwrap();
+
+        assert_eq!(find_instrumented_objects(&dir), [dir.join("pkg/test")]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_has_line_hits() {
+        assert!(has_line_hits(REPORT));
+        assert!(!has_line_hits("SF:src/lib.rs\nLF:3\nLH:0\nend_of_record\n"));
+    }
+
+    #[test]
+    fn test_normalize_source_file() {
+        assert_eq!(
+            normalize_source_file("/proc/self/cwd/src/lib.rs", "/execroot/_main"),
+            "src/lib.rs"
+        );
+        assert_eq!(
+            normalize_source_file("#/proc/self/cwd/src/lib.rs", "/execroot/_main"),
+            "src/lib.rs"
+        );
+        assert_eq!(
+            normalize_source_file("/execroot/_main/bazel-out/bin/gen.rs", "/execroot/_main"),
+            "bazel-out/bin/gen.rs"
+        );
+        assert_eq!(
+            normalize_source_file("/execroot/_main_other/lib.rs", "/execroot/_main"),
+            "/execroot/_main_other/lib.rs"
+        );
+    }
 }
@@ -0,0 +1,410 @@
+//! Parsing and writing of [lcov](https://github.com/linux-test-project/lcov)
+//! tracefiles, as exported by `llvm-cov export -format=lcov`.
+//!
+//! Records of the same source file are merged, summing their hit counts, and
+//! the summary lines (`LF`, `LH`, `FNF`, `FNH`, `BRF`, `BRH`) are recomputed
+//! when writing. Lines of unknown kinds, like MC/DC records, are kept as they
+//! are.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// An error in a tracefile, at a 1-based line number.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// A function of a source file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+struct Function {
+    line: Option<u32>,
+    end_line: Option<u32>,
+    hits: u64,
+}
+
+/// The coverage of a source file.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    functions: BTreeMap<String, Function>,
+    /// Hits by line number.
+    lines: BTreeMap<u32, u64>,
+    /// Times taken by line, block and branch, `None` if the block was never
+    /// executed.
+    branches: BTreeMap<(u32, String, String), Option<u64>>,
+    other: Vec<String>,
+}
+
+/// Adds the times a branch was taken, which is `None` only if neither block
+/// was executed.
+fn add_taken(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or_default() + b.unwrap_or_default()),
+    }
+}
+
+impl SourceFile {
+    fn merge(&mut self, other: SourceFile) {
+        for (name, function) in other.functions {
+            let merged = self.functions.entry(name).or_default();
+            merged.line = merged.line.or(function.line);
+            merged.end_line = merged.end_line.or(function.end_line);
+            merged.hits += function.hits;
+        }
+        for (line, hits) in other.lines {
+            *self.lines.entry(line).or_default() += hits;
+        }
+        for (branch, taken) in other.branches {
+            let merged = self.branches.entry(branch).or_default();
+            *merged = add_taken(*merged, taken);
+        }
+        for line in other.other {
+            if !self.other.contains(&line) {
+                self.other.push(line);
+            }
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by_key(|(name, function)| (function.line, *name));
+        for (name, function) in &functions {
+            match (function.line, function.end_line) {
+                (Some(line), Some(end_line)) => {
+                    out.push_str(&format!("FN:{line},{end_line},{name}\n"))
+                }
+                (Some(line), None) => out.push_str(&format!("FN:{line},{name}\n")),
+                _ => {}
+            }
+        }
+        for (name, function) in &functions {
+            out.push_str(&format!("FNDA:{},{name}\n", function.hits));
+        }
+        out.push_str(&format!("FNF:{}\n", functions.len()));
+        out.push_str(&format!(
+            "FNH:{}\n",
+            functions.iter().filter(|(_, f)| f.hits > 0).count()
+        ));
+        for (line, hits) in &self.lines {
+            out.push_str(&format!("DA:{line},{hits}\n"));
+        }
+        if !self.branches.is_empty() {
+            for ((line, block, branch), taken) in &self.branches {
+                let taken = taken.map_or_else(|| "-".to_owned(), |taken| taken.to_string());
+                out.push_str(&format!("BRDA:{line},{block},{branch},{taken}\n"));
+            }
+            out.push_str(&format!("BRF:{}\n", self.branches.len()));
+            out.push_str(&format!(
+                "BRH:{}\n",
+                self.branches
+                    .values()
+                    .filter(|taken| taken.is_some_and(|taken| taken > 0))
+                    .count()
+            ));
+        }
+        for line in &self.other {
+            out.push_str(line);
+            out.push('\n');
+        }
+        out.push_str(&format!("LF:{}\n", self.lines.len()));
+        out.push_str(&format!(
+            "LH:{}\n",
+            self.lines.values().filter(|hits| **hits > 0).count()
+        ));
+    }
+}
+
+/// The coverage of source files, by path.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    test_name: Option<String>,
+    files: BTreeMap<String, SourceFile>,
+}
+
+fn parse_number<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid {what} `{value}`"))
+}
+
+/// Parses a record line of a source file into `file`, returns `false` for
+/// lines of unknown kinds.
+fn parse_record_line(file: &mut SourceFile, kind: &str, value: &str) -> Result<bool, String> {
+    match kind {
+        "FN" => {
+            let (line, rest) = value
+                .split_once(',')
+                .ok_or_else(|| format!("expected `FN:<line>,<name>`, got `FN:{value}`"))?;
+            // lcov 2 also records the end line: `FN:<line>,<end line>,<name>`.
+            let (end_line, name) = match rest.split_once(',') {
+                Some((end_line, name)) if end_line.parse::<u32>().is_ok() => {
+                    (end_line.parse().ok(), name)
+                }
+                _ => (None, rest),
+            };
+            let function = file.functions.entry(name.to_owned()).or_default();
+            function.line = Some(parse_number(line, "line number")?);
+            function.end_line = end_line;
+        }
+        "FNDA" => {
+            let (hits, name) = value
+                .split_once(',')
+                .ok_or_else(|| format!("expected `FNDA:<hits>,<name>`, got `FNDA:{value}`"))?;
+            file.functions.entry(name.to_owned()).or_default().hits +=
+                parse_number::<u64>(hits, "hit count")?;
+        }
+        "DA" => {
+            // A checksum may follow the hits.
+            let mut parts = value.split(',');
+            let (line, hits) = match (parts.next(), parts.next()) {
+                (Some(line), Some(hits)) => (line, hits),
+                _ => return Err(format!("expected `DA:<line>,<hits>`, got `DA:{value}`")),
+            };
+            *file
+                .lines
+                .entry(parse_number(line, "line number")?)
+                .or_default() += parse_number::<u64>(hits, "hit count")?;
+        }
+        "BRDA" => {
+            let parts: Vec<_> = value.splitn(4, ',').collect();
+            let (line, block, branch, taken) = match parts[..] {
+                [line, block, branch, taken] => (line, block, branch, taken),
+                _ => {
+                    return Err(format!(
+                        "expected `BRDA:<line>,<block>,<branch>,<taken>`, got `BRDA:{value}`"
+                    ))
+                }
+            };
+            let taken = match taken {
+                "-" => None,
+                taken => Some(parse_number(taken, "branch count")?),
+            };
+            let key = (
+                parse_number(line, "line number")?,
+                block.to_owned(),
+                branch.to_owned(),
+            );
+            let merged = file.branches.entry(key).or_default();
+            *merged = add_taken(*merged, taken);
+        }
+        // Summaries are recomputed when writing.
+        "FNF" | "FNH" | "BRF" | "BRH" | "LF" | "LH" => {}
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+impl Report {
+    /// Parses a tracefile, merging the records of the same source file.
+    pub fn parse(text: &str) -> Result<Report, ParseError> {
+        let mut report = Report::default();
+        let mut current: Option<(String, SourceFile)> = None;
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: String| ParseError {
+                line: i + 1,
+                message,
+            };
+            let line = line.trim_end_matches('\r');
+            if line.is_empty() {
+                continue;
+            }
+            if line == "end_of_record" {
+                let (path, file) = current
+                    .take()
+                    .ok_or_else(|| error("`end_of_record` without `SF`".to_owned()))?;
+                report.add(path, file);
+                continue;
+            }
+            let (kind, value) = line.split_once(':').unwrap_or((line, ""));
+            match (kind, &mut current) {
+                ("TN", _) => report.test_name = Some(value.to_owned()),
+                ("SF", None) => current = Some((value.to_owned(), SourceFile::default())),
+                ("SF", Some((path, _))) => {
+                    return Err(error(format!(
+                        "`SF:{value}` before the `end_of_record` of `{path}`"
+                    )))
+                }
+                (_, None) => return Err(error(format!("`{line}` outside of a record"))),
+                (_, Some((_, file))) => {
+                    if !parse_record_line(file, kind, value).map_err(error)? {
+                        file.other.push(line.to_owned());
+                    }
+                }
+            }
+        }
+        match current {
+            Some((path, _)) => Err(ParseError {
+                line: text.lines().count(),
+                message: format!("missing `end_of_record` of `{path}`"),
+            }),
+            None => Ok(report),
+        }
+    }
+
+    /// Adds the coverage of a source file, merging it with any coverage
+    /// already recorded for it.
+    fn add(&mut self, path: String, file: SourceFile) {
+        match self.files.get_mut(&path) {
+            Some(existing) => existing.merge(file),
+            None => {
+                self.files.insert(path, file);
+            }
+        }
+    }
+
+    /// Returns an iterator over the source file paths.
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.files.keys().map(String::as_str)
+    }
+
+    /// Keeps the source files for which `f` returns `true`.
+    pub fn retain(&mut self, mut f: impl FnMut(&str) -> bool) {
+        self.files.retain(|path, _| f(path));
+    }
+
+    /// Rewrites the path of each source file, merging files whose paths
+    /// become the same.
+    pub fn rename_files(&mut self, mut f: impl FnMut(&str) -> String) {
+        let files = std::mem::take(&mut self.files);
+        for (path, file) in files {
+            self.add(f(&path), file);
+        }
+    }
+
+    /// Writes the report as a tracefile.
+    pub fn to_lcov(&self) -> String {
+        let mut out = String::new();
+        for (path, file) in &self.files {
+            if let Some(test_name) = &self.test_name {
+                out.push_str(&format!("TN:{test_name}\n"));
+            }
+            out.push_str(&format!("SF:{path}\n"));
+            file.write(&mut out);
+            out.push_str("end_of_record\n");
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let tracefile = "\
+SF:src/lib.rs
+FN:3,_RNvCs1_3foo3bar
+FN:8,<foo::Bar<A, B> as core::fmt::Debug>::fmt
+FNDA:2,_RNvCs1_3foo3bar
+FNDA:0,<foo::Bar<A, B> as core::fmt::Debug>::fmt
+FNF:2
+FNH:1
+DA:3,2
+DA:4,0
+DA:8,0
+BRDA:4,0,0,2
+BRDA:4,0,1,-
+BRF:2
+BRH:1
+MCDC:4,2,t,1
+LF:3
+LH:1
+end_of_record
+";
+        let report = Report::parse(tracefile).unwrap();
+        assert_eq!(report.to_lcov(), tracefile);
+    }
+
+    #[test]
+    fn test_merge_records() {
+        let report = Report::parse(
+            "\
+SF:src/lib.rs
+FN:1,foo
+FNDA:1,foo
+DA:1,1
+DA:2,0
+BRDA:2,0,0,-
+end_of_record
+SF:src/lib.rs
+FN:1,foo
+FNDA:2,foo
+DA:2,3
+BRDA:2,0,0,1
+end_of_record
+",
+        )
+        .unwrap();
+        assert_eq!(
+            report.to_lcov(),
+            "\
+SF:src/lib.rs
+FN:1,foo
+FNDA:3,foo
+FNF:1
+FNH:1
+DA:1,1
+DA:2,3
+BRDA:2,0,0,1
+BRF:1
+BRH:1
+LF:2
+LH:2
+end_of_record
+"
+        );
+    }
+
+    #[test]
+    fn test_rename_and_retain() {
+        let mut report = Report::parse(
+            "\
+SF:/proc/self/cwd/src/lib.rs
+DA:1,1
+end_of_record
+SF:src/lib.rs
+DA:1,1
+end_of_record
+SF:bazel-out/k8-fastbuild/bin/gen.rs
+DA:1,0
+end_of_record
+",
+        )
+        .unwrap();
+        report.rename_files(|path| path.trim_start_matches("/proc/self/cwd/").to_owned());
+        report.retain(|path| !path.starts_with("bazel-out/"));
+        assert_eq!(
+            report.to_lcov(),
+            "SF:src/lib.rs\nFNF:0\nFNH:0\nDA:1,2\nLF:1\nLH:1\nend_of_record\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Report::parse("SF:a.rs\nDA:x,1\nend_of_record\n"),
+            Err(ParseError {
+                line: 2,
+                message: "invalid line number `x`".to_owned(),
+            })
+        );
+        assert_eq!(
+            Report::parse("DA:1,1\n").unwrap_err().to_string(),
+            "line 1: `DA:1,1` outside of a record"
+        );
+        assert_eq!(
+            Report::parse("SF:a.rs\nDA:1,1\n").unwrap_err().to_string(),
+            "line 2: missing `end_of_record` of `a.rs`"
+        );
+    }
+}
@@ -1,13 +1,18 @@
 //! Bazel label parsing library.
 //!
 //! USAGE: `label::analyze("//foo/bar:baz")
+//!
+//! Labels borrow from the parsed string, [OwnedLabel] owns it. Target patterns
+//! like `//foo/...` are parsed with [pattern::analyze].
 mod label_error;
-use label_error::LabelError;
+pub mod pattern;
+
+pub use label_error::LabelError;
+
+use std::fmt;
+use std::str::FromStr;
 
 /// Parse and analyze given str.
-///
-/// TODO: validate . and .. in target name
-/// TODO: validate used characters in target name
 pub fn analyze(input: &'_ str) -> Result<Label<'_>> {
     Label::analyze(input)
 }
@@ -29,7 +34,15 @@ impl<'s> Repository<'s> {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+impl fmt::Display for Repository<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Repository::Canonical(name) | Repository::Apparent(name) => f.write_str(name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Label<'s> {
     Relative {
         target_name: &'s str,
@@ -61,7 +74,10 @@ impl<'s> Label<'s> {
                     label,
                     "Relative packages must have a name.",
                 ))),
-                Some(name) => Ok(Label::Relative { target_name: name }),
+                Some(name) => {
+                    validate_target_name(name, label)?;
+                    Ok(Label::Relative { target_name: name })
+                }
             };
         }
 
@@ -97,6 +113,7 @@ impl<'s> Label<'s> {
             (Some(package_name), None) => name_from_package(package_name),
             (_, Some(name)) => name,
         };
+        validate_target_name(name, label)?;
 
         Ok(Label::Absolute {
             repository,
@@ -139,6 +156,128 @@ impl<'s> Label<'s> {
             Label::Absolute { target_name, .. } => target_name,
         }
     }
+
+    /// Resolves the label as written in `package_name` of `repository`.
+    ///
+    /// Relative labels become absolute in that package, and absolute labels
+    /// without a repository, like `//foo:bar`, refer to `repository`.
+    pub fn resolve(&self, repository: Option<Repository<'s>>, package_name: &'s str) -> Label<'s> {
+        match self {
+            Label::Relative { target_name } => Label::Absolute {
+                repository,
+                package_name,
+                target_name,
+            },
+            Label::Absolute {
+                repository: None,
+                package_name,
+                target_name,
+            } => Label::Absolute {
+                repository,
+                package_name,
+                target_name,
+            },
+            absolute => absolute.clone(),
+        }
+    }
+
+    /// Returns the label with its apparent repository name replaced by the
+    /// canonical one `mapping` returns for it, as found in a repo mapping.
+    ///
+    /// Labels without a repository, or with a canonical one, are unchanged.
+    /// An empty canonical name is the main repository, written `@//`.
+    pub fn to_canonical<'m>(
+        &self,
+        mapping: impl FnOnce(&str) -> Option<&'m str>,
+    ) -> Result<OwnedLabel> {
+        let apparent_name = match self.repo() {
+            Some(Repository::Apparent(_)) => self.repo_name().unwrap_or_default(),
+            _ => return Ok(self.to_owned_label()),
+        };
+        let canonical_name = mapping(apparent_name).ok_or_else(|| {
+            LabelError(format!(
+                "{self} must be a legal label; no repository is visible as '@{apparent_name}'."
+            ))
+        })?;
+        let repository = if canonical_name.is_empty() {
+            "@".to_owned()
+        } else {
+            format!("@@{canonical_name}")
+        };
+        Ok(OwnedLabel {
+            label: format!(
+                "{repository}//{}:{}",
+                self.package().unwrap_or_default(),
+                self.name()
+            ),
+        })
+    }
+
+    /// Returns an owned copy of the label.
+    pub fn to_owned_label(&self) -> OwnedLabel {
+        OwnedLabel {
+            label: self.to_string(),
+        }
+    }
+}
+
+/// Formats the label in its canonical form, which [Label::analyze] parses back
+/// to the same label: `:name` for relative labels, `[@repo]//package:name`
+/// for absolute ones.
+impl fmt::Display for Label<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Label::Relative { target_name } => write!(f, ":{target_name}"),
+            Label::Absolute {
+                repository,
+                package_name,
+                target_name,
+            } => {
+                if let Some(repository) = repository {
+                    write!(f, "{repository}")?;
+                }
+                write!(f, "//{package_name}:{target_name}")
+            }
+        }
+    }
+}
+
+/// A [Label] which owns its string.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone)]
+pub struct OwnedLabel {
+    /// The canonical form of a valid label.
+    label: String,
+}
+
+impl OwnedLabel {
+    /// Returns the label borrowing from this one.
+    pub fn as_label(&self) -> Label<'_> {
+        Label::analyze(&self.label).expect("An OwnedLabel is always a valid label")
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.label
+    }
+}
+
+impl FromStr for OwnedLabel {
+    type Err = LabelError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Label::analyze(s)?.to_owned_label())
+    }
+}
+
+impl fmt::Display for OwnedLabel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.label)
+    }
+}
+
+impl<'s> From<&Label<'s>> for OwnedLabel {
+    fn from(label: &Label<'s>) -> Self {
+        label.to_owned_label()
+    }
 }
 
 fn err<'s>(label: &'s str, msg: &'s str) -> String {
@@ -328,6 +467,41 @@ fn consume_name<'s>(input: &'s str, label: &'s str) -> Result<Option<&'s str>> {
     Ok(Some(name))
 }
 
+/// Checks `name` is a valid target name, as described in
+/// <https://bazel.build/concepts/labels#target-names>.
+fn validate_target_name<'s>(name: &'s str, label: &'s str) -> Result<()> {
+    const PUNCTUATION: &str = "!%-@^_\"#$&'()*+,;<=>?[]{|}~/.";
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || PUNCTUATION.contains(c))
+    {
+        return Err(LabelError(err(
+            label,
+            "target names may contain only A-Z, a-z, 0-9, and the punctuation \
+            symbols !%-@^_\"#$&'()*+,;<=>?[]{|}~/.",
+        )));
+    }
+    if name.ends_with('/') {
+        return Err(LabelError(err(label, "target names may not end with '/'.")));
+    }
+    if name.split('/').any(|segment| segment.is_empty()) {
+        return Err(LabelError(err(
+            label,
+            "target names may not contain empty path segments.",
+        )));
+    }
+    if name
+        .split('/')
+        .any(|segment| segment == "." || segment == "..")
+    {
+        return Err(LabelError(err(
+            label,
+            "target names may not contain '.' or '..' path segments.",
+        )));
+    }
+    Ok(())
+}
+
 fn name_from_package(package_name: &str) -> &str {
     package_name
         .rsplit_once('/')
@@ -606,4 +780,144 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_name_validation() -> Result<()> {
+        assert_eq!(
+            analyze("//foo:a!%-@^_#$&'()*+,;<=>?[]{|}~.b")?.name(),
+            "a!%-@^_#$&'()*+,;<=>?[]{|}~.b"
+        );
+        assert_eq!(analyze("//foo:.hidden/..file")?.name(), ".hidden/..file");
+
+        assert_eq!(
+            analyze("//foo:bar baz"),
+            Err(LabelError(
+                "//foo:bar baz must be a legal label; target names may contain only A-Z, a-z, 0-9, \
+                and the punctuation symbols !%-@^_\"#$&'()*+,;<=>?[]{|}~/."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze(":bar/"),
+            Err(LabelError(
+                ":bar/ must be a legal label; target names may not end with '/'.".to_string()
+            ))
+        );
+        assert_eq!(
+            analyze("//foo:bar/../baz"),
+            Err(LabelError(
+                "//foo:bar/../baz must be a legal label; target names may not contain '.' or '..' path segments."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze("//foo:."),
+            Err(LabelError(
+                "//foo:. must be a legal label; target names may not contain '.' or '..' path segments."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze("//foo/.."),
+            Err(LabelError(
+                "//foo/.. must be a legal label; target names may not contain '.' or '..' path segments."
+                    .to_string()
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_round_trip() -> Result<()> {
+        for (input, expected) in [
+            (":foo", ":foo"),
+            ("//:foo", "//:foo"),
+            ("//foo/bar", "//foo/bar:bar"),
+            ("//foo/bar:baz/qux", "//foo/bar:baz/qux"),
+            ("@repo", "@repo//:repo"),
+            ("@//foo", "@//foo:foo"),
+            ("@@repo+1.0//foo:bar", "@@repo+1.0//foo:bar"),
+        ] {
+            let label = analyze(input)?;
+            assert_eq!(label.to_string(), expected);
+            assert_eq!(analyze(expected)?, label);
+
+            let owned: OwnedLabel = input.parse()?;
+            assert_eq!(owned.as_str(), expected);
+            assert_eq!(owned.as_label(), label);
+        }
+
+        assert!("foo".parse::<OwnedLabel>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve() -> Result<()> {
+        let repository = Some(Repository::Apparent("@repo"));
+        assert_eq!(
+            analyze(":bar")?
+                .resolve(repository.clone(), "foo")
+                .to_string(),
+            "@repo//foo:bar"
+        );
+        assert_eq!(
+            analyze("//baz:qux")?
+                .resolve(repository.clone(), "foo")
+                .to_string(),
+            "@repo//baz:qux"
+        );
+        assert_eq!(
+            analyze("@other//baz:qux")?
+                .resolve(repository, "foo")
+                .to_string(),
+            "@other//baz:qux"
+        );
+        assert_eq!(
+            analyze(":bar")?.resolve(None, "foo").to_string(),
+            "//foo:bar"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_canonical() -> Result<()> {
+        let mapping = |apparent: &str| match apparent {
+            "" => Some(""),
+            "my_deps" => Some("rules_rust++crate+crates"),
+            _ => None,
+        };
+
+        assert_eq!(
+            analyze("@my_deps//:serde")?
+                .to_canonical(mapping)?
+                .to_string(),
+            "@@rules_rust++crate+crates//:serde"
+        );
+        assert_eq!(
+            analyze("@//foo")?.to_canonical(mapping)?.to_string(),
+            "@//foo:foo"
+        );
+        assert_eq!(
+            analyze("@@canonical//foo")?
+                .to_canonical(mapping)?
+                .to_string(),
+            "@@canonical//foo:foo"
+        );
+        assert_eq!(
+            analyze("//foo")?.to_canonical(mapping)?.to_string(),
+            "//foo:foo"
+        );
+        assert_eq!(
+            analyze("@unknown//:foo")?.to_canonical(mapping),
+            Err(LabelError(
+                "@unknown//:foo must be a legal label; no repository is visible as '@unknown'."
+                    .to_string()
+            ))
+        );
+
+        Ok(())
+    }
 }
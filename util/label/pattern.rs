@@ -0,0 +1,344 @@
+//! Bazel target pattern parsing library.
+//!
+//! USAGE: `label::pattern::analyze("//foo/...")
+//!
+//! See <https://bazel.build/run/build#specifying-build-targets>.
+
+use std::fmt;
+
+use crate::{err, Label, LabelError, Repository, Result};
+
+/// Parse and analyze given str as a target pattern.
+pub fn analyze(input: &'_ str) -> Result<TargetPattern<'_>> {
+    TargetPattern::analyze(input)
+}
+
+/// The targets of the packages a [TargetPattern] matches.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Targets<'s> {
+    /// All the rules of the packages, written `:all`.
+    AllRules,
+    /// All the targets of the packages, including files, written `:*` or
+    /// `:all-targets`.
+    AllTargets,
+    /// A single target.
+    Named(&'s str),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TargetPattern<'s> {
+    /// Whether the pattern is prefixed with `-`, excluding the targets it
+    /// matches from those of the patterns before it.
+    pub negative: bool,
+    pub repository: Option<Repository<'s>>,
+    /// The package of the pattern, `None` for patterns relative to the
+    /// current package like `:all`.
+    pub package_name: Option<&'s str>,
+    /// Whether the pattern also matches the subpackages of its package,
+    /// written `/...`.
+    pub recursive: bool,
+    pub targets: Targets<'s>,
+}
+
+impl<'s> TargetPattern<'s> {
+    /// Parse and analyze given str as a target pattern.
+    pub fn analyze(input: &'s str) -> Result<TargetPattern<'s>> {
+        let (negative, pattern) = match input.strip_prefix('-') {
+            Some(pattern) => (true, pattern),
+            None => (false, input),
+        };
+
+        let (package_part, target_part) = match pattern.find(':') {
+            Some(colon_pos) => (&pattern[..colon_pos], Some(&pattern[colon_pos + 1..])),
+            None => (pattern, None),
+        };
+
+        let recursive_base = if package_part == "//..." || package_part.ends_with("//...") {
+            Some(&package_part[..package_part.len() - "...".len()])
+        } else {
+            package_part.strip_suffix("/...")
+        };
+
+        let targets = match target_part {
+            Some("all") => Some(Targets::AllRules),
+            Some("*") | Some("all-targets") => Some(Targets::AllTargets),
+            _ => None,
+        };
+
+        let (recursive, targets, label) = match (recursive_base, targets) {
+            (Some(base), targets) => {
+                if target_part.is_some() && targets.is_none() {
+                    return Err(LabelError(err(
+                        input,
+                        "recursive patterns may only match ':all', ':*' or ':all-targets'.",
+                    )));
+                }
+                let invalid = || LabelError(err(input, "invalid package in recursive pattern."));
+                let parts = match base.strip_suffix("//") {
+                    // The root package, like `//...` or `@repo//...`.
+                    Some("") => (None, Some("")),
+                    Some(repository @ "@") => (Some(Repository::Apparent(repository)), Some("")),
+                    Some(repository) => {
+                        let label = Label::analyze(repository).map_err(|_| invalid())?;
+                        (label.repo().cloned(), Some(""))
+                    }
+                    None => {
+                        let label = Label::analyze(base).map_err(|_| invalid())?;
+                        label_parts(input, &label, base)?
+                    }
+                };
+                (true, targets.unwrap_or(Targets::AllRules), parts)
+            }
+            (None, Some(targets)) => {
+                let label = if package_part.is_empty() {
+                    None
+                } else {
+                    let label = Label::analyze(package_part)?;
+                    Some(label_parts(input, &label, package_part)?)
+                };
+                (false, targets, label.unwrap_or((None, None)))
+            }
+            (None, None) => {
+                let label = Label::analyze(pattern)?;
+                let target_name = label.name();
+                (
+                    false,
+                    Targets::Named(target_name),
+                    (label.repo().cloned(), label.package()),
+                )
+            }
+        };
+        let (repository, package_name) = label;
+
+        Ok(TargetPattern {
+            negative,
+            repository,
+            package_name,
+            recursive,
+            targets,
+        })
+    }
+
+    /// Whether the pattern matches `label`, ignoring whether it's negative.
+    ///
+    /// Labels of the patterns' package which aren't rules match `:all` too, as
+    /// whether a target is a rule isn't known from its label. Patterns and
+    /// labels without a repository only match each other. Repositories only
+    /// match if they are of the same kind, as an apparent name may refer to
+    /// a different canonical repository.
+    pub fn matches(&self, label: &Label<'_>) -> bool {
+        let (label_package, label_name) = match label {
+            Label::Absolute {
+                package_name,
+                target_name,
+                ..
+            } => (*package_name, *target_name),
+            Label::Relative { .. } => return false,
+        };
+        let package_name = match self.package_name {
+            Some(package_name) => package_name,
+            None => return false,
+        };
+        if self.repository.as_ref() != label.repo() {
+            return false;
+        }
+
+        let package_matches = if self.recursive {
+            package_name.is_empty()
+                || label_package == package_name
+                || label_package
+                    .strip_prefix(package_name)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        } else {
+            label_package == package_name
+        };
+
+        package_matches
+            && match self.targets {
+                Targets::AllRules | Targets::AllTargets => true,
+                Targets::Named(name) => name == label_name,
+            }
+    }
+}
+
+/// Returns the repository and package of `label`, which was parsed from the
+/// package part of a pattern.
+fn label_parts<'s>(
+    input: &'s str,
+    label: &Label<'s>,
+    package_part: &'s str,
+) -> Result<(Option<Repository<'s>>, Option<&'s str>)> {
+    // A package part like `//foo` is parsed as the label `//foo:foo`, but a
+    // package part with a name, like `//foo:bar`, isn't a package.
+    if package_part.contains(':') || label.is_relative() {
+        return Err(LabelError(err(input, "invalid package in pattern.")));
+    }
+    Ok((label.repo().cloned(), label.package()))
+}
+
+/// Returns whether `label` is matched by `patterns`, evaluated in order: a
+/// label is included by the positive patterns which match it and excluded by
+/// the negative ones, the last matching pattern winning.
+pub fn matches_all(patterns: &[TargetPattern<'_>], label: &Label<'_>) -> bool {
+    patterns
+        .iter()
+        .rev()
+        .find(|pattern| pattern.matches(label))
+        .is_some_and(|pattern| !pattern.negative)
+}
+
+/// Formats the pattern so that [TargetPattern::analyze] parses it back to the
+/// same pattern.
+impl fmt::Display for TargetPattern<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            f.write_str("-")?;
+        }
+        if let Some(repository) = &self.repository {
+            write!(f, "{repository}")?;
+        }
+        if let Some(package_name) = self.package_name {
+            write!(f, "//{package_name}")?;
+            if self.recursive {
+                if package_name.is_empty() {
+                    f.write_str("...")?;
+                } else {
+                    f.write_str("/...")?;
+                }
+            }
+        }
+        match self.targets {
+            Targets::AllRules => f.write_str(":all"),
+            Targets::AllTargets => f.write_str(":*"),
+            Targets::Named(name) => write!(f, ":{name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::analyze as label;
+
+    #[test]
+    fn test_pattern_parsing() -> Result<()> {
+        assert_eq!(
+            analyze("//foo/...")?,
+            TargetPattern {
+                negative: false,
+                repository: None,
+                package_name: Some("foo"),
+                recursive: true,
+                targets: Targets::AllRules,
+            }
+        );
+        assert_eq!(
+            analyze("-@repo//...:*")?,
+            TargetPattern {
+                negative: true,
+                repository: Some(Repository::Apparent("@repo")),
+                package_name: Some(""),
+                recursive: true,
+                targets: Targets::AllTargets,
+            }
+        );
+        assert_eq!(
+            analyze(":all")?,
+            TargetPattern {
+                negative: false,
+                repository: None,
+                package_name: None,
+                recursive: false,
+                targets: Targets::AllRules,
+            }
+        );
+        assert_eq!(analyze("//foo:all-targets")?.targets, Targets::AllTargets);
+        assert_eq!(
+            analyze("//foo/bar")?,
+            TargetPattern {
+                negative: false,
+                repository: None,
+                package_name: Some("foo/bar"),
+                recursive: false,
+                targets: Targets::Named("bar"),
+            }
+        );
+
+        assert_eq!(
+            analyze("//foo/...:bar"),
+            Err(LabelError(
+                "//foo/...:bar must be a legal label; recursive patterns may only match ':all', ':*' or ':all-targets'."
+                    .to_string()
+            ))
+        );
+        assert_eq!(
+            analyze("//foo:bar:all"),
+            Err(LabelError(
+                "//foo:bar:all must be a legal label; target names may contain only A-Z, a-z, 0-9, \
+                and the punctuation symbols !%-@^_\"#$&'()*+,;<=>?[]{|}~/."
+                    .to_string()
+            ))
+        );
+        assert!(analyze("foo/...").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_display_round_trip() -> Result<()> {
+        for (input, expected) in [
+            ("//...", "//...:all"),
+            ("//foo/...", "//foo/...:all"),
+            ("-@repo//foo/...:all-targets", "-@repo//foo/...:*"),
+            ("//foo:all", "//foo:all"),
+            (":*", ":*"),
+            ("//foo/bar", "//foo/bar:bar"),
+            ("@@repo+//:baz", "@@repo+//:baz"),
+        ] {
+            let pattern = analyze(input)?;
+            assert_eq!(pattern.to_string(), expected);
+            assert_eq!(analyze(expected)?, pattern);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches() -> Result<()> {
+        let foo_bar = label("//foo/bar:baz")?;
+        assert!(analyze("//...")?.matches(&foo_bar));
+        assert!(analyze("//foo/...")?.matches(&foo_bar));
+        assert!(analyze("//foo/bar/...:*")?.matches(&foo_bar));
+        assert!(analyze("//foo/bar:all")?.matches(&foo_bar));
+        assert!(analyze("//foo/bar:baz")?.matches(&foo_bar));
+        assert!(!analyze("//foo:all")?.matches(&foo_bar));
+        assert!(!analyze("//fo/...")?.matches(&foo_bar));
+        assert!(!analyze("//foo/bar:qux")?.matches(&foo_bar));
+        assert!(!analyze("@repo//...")?.matches(&foo_bar));
+        assert!(!analyze(":all")?.matches(&foo_bar));
+
+        assert!(analyze("@repo//...")?.matches(&label("@repo//foo")?));
+        assert!(analyze("@@repo//...")?.matches(&label("@@repo//foo")?));
+        assert!(!analyze("@@repo//...")?.matches(&label("@repo//foo")?));
+        assert!(!analyze("@repo//...")?.matches(&label("@@repo//foo")?));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_matches_all() -> Result<()> {
+        let patterns = vec![
+            analyze("//foo/...")?,
+            analyze("-//foo/bar/...")?,
+            analyze("//foo/bar:keep")?,
+        ];
+        assert!(matches_all(&patterns, &label("//foo:a")?));
+        assert!(!matches_all(&patterns, &label("//foo/bar:b")?));
+        assert!(matches_all(&patterns, &label("//foo/bar:keep")?));
+        assert!(!matches_all(&patterns, &label("//baz:c")?));
+        assert!(!matches_all(&[], &label("//foo:a")?));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,778 @@
+// Copyright 2026 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Collects rustc json diagnostics into a report file, either as SARIF 2.1.0
+//! or as normalized json lines. See
+//! https://doc.rust-lang.org/rustc/json.html for the rustc format.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use tinyjson::JsonValue;
+
+use crate::json::{format_sorted, write_sorted};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum DiagnosticsFormat {
+    Sarif,
+    JsonLines,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Span {
+    pub(crate) file_name: String,
+    pub(crate) byte_start: u64,
+    pub(crate) byte_end: u64,
+    pub(crate) line_start: u64,
+    pub(crate) line_end: u64,
+    pub(crate) column_start: u64,
+    pub(crate) column_end: u64,
+    pub(crate) is_primary: bool,
+    pub(crate) label: Option<String>,
+    pub(crate) suggested_replacement: Option<String>,
+    pub(crate) suggestion_applicability: Option<String>,
+    /// The source text covered by the span, if rustc included it.
+    pub(crate) text: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Diagnostic {
+    pub(crate) level: String,
+    pub(crate) code: Option<String>,
+    pub(crate) message: String,
+    pub(crate) spans: Vec<Span>,
+    pub(crate) children: Vec<Diagnostic>,
+}
+
+/// A set of machine applicable replacements that must be applied together,
+/// together with the diagnostic suggesting them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fix<'a> {
+    pub(crate) code: Option<&'a str>,
+    pub(crate) message: &'a str,
+    pub(crate) replacements: Vec<&'a Span>,
+}
+
+/// A suggested replacement, together with the message of the diagnostic
+/// suggesting it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Suggestion<'a> {
+    pub(crate) message: &'a str,
+    pub(crate) span: &'a Span,
+    pub(crate) replacement: &'a str,
+}
+
+fn get_str(map: &HashMap<String, JsonValue>, key: &str) -> Option<String> {
+    match map.get(key)? {
+        JsonValue::String(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+fn get_u64(map: &HashMap<String, JsonValue>, key: &str) -> u64 {
+    match map.get(key) {
+        Some(JsonValue::Number(n)) => *n as u64,
+        _ => 0,
+    }
+}
+
+/// Reconstructs the source text covered by a span from the `text` lines of
+/// rustc, whose highlight columns are 1-based character offsets.
+fn get_span_text(map: &HashMap<String, JsonValue>) -> Option<String> {
+    let lines = get_array(map, "text");
+    if lines.is_empty() {
+        return None;
+    }
+    let mut text = Vec::with_capacity(lines.len());
+    for line in lines {
+        let JsonValue::Object(line) = line else {
+            return None;
+        };
+        let start = get_u64(line, "highlight_start").checked_sub(1)? as usize;
+        let end = get_u64(line, "highlight_end").checked_sub(1)? as usize;
+        let highlighted: String = get_str(line, "text")?
+            .chars()
+            .skip(start)
+            .take(end.checked_sub(start)?)
+            .collect();
+        text.push(highlighted);
+    }
+    Some(text.join("\n"))
+}
+
+fn get_array<'a>(map: &'a HashMap<String, JsonValue>, key: &str) -> &'a [JsonValue] {
+    match map.get(key) {
+        Some(JsonValue::Array(a)) => a,
+        _ => &[],
+    }
+}
+
+impl Span {
+    fn from_json(value: &JsonValue) -> Option<Self> {
+        let JsonValue::Object(map) = value else {
+            return None;
+        };
+        Some(Self {
+            file_name: get_str(map, "file_name")?,
+            byte_start: get_u64(map, "byte_start"),
+            byte_end: get_u64(map, "byte_end"),
+            line_start: get_u64(map, "line_start"),
+            line_end: get_u64(map, "line_end"),
+            column_start: get_u64(map, "column_start"),
+            column_end: get_u64(map, "column_end"),
+            is_primary: matches!(map.get("is_primary"), Some(JsonValue::Boolean(true))),
+            label: get_str(map, "label"),
+            suggested_replacement: get_str(map, "suggested_replacement"),
+            suggestion_applicability: get_str(map, "suggestion_applicability"),
+            text: get_span_text(map),
+        })
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let mut map = HashMap::from([
+            (
+                "file_name".to_owned(),
+                JsonValue::String(self.file_name.clone()),
+            ),
+            (
+                "line_start".to_owned(),
+                JsonValue::Number(self.line_start as f64),
+            ),
+            (
+                "line_end".to_owned(),
+                JsonValue::Number(self.line_end as f64),
+            ),
+            (
+                "column_start".to_owned(),
+                JsonValue::Number(self.column_start as f64),
+            ),
+            (
+                "column_end".to_owned(),
+                JsonValue::Number(self.column_end as f64),
+            ),
+        ]);
+        if let Some(label) = &self.label {
+            map.insert("label".to_owned(), JsonValue::String(label.clone()));
+        }
+        JsonValue::Object(map)
+    }
+
+    fn to_sarif_region(&self) -> JsonValue {
+        JsonValue::Object(HashMap::from([
+            (
+                "startLine".to_owned(),
+                JsonValue::Number(self.line_start as f64),
+            ),
+            (
+                "startColumn".to_owned(),
+                JsonValue::Number(self.column_start as f64),
+            ),
+            (
+                "endLine".to_owned(),
+                JsonValue::Number(self.line_end as f64),
+            ),
+            (
+                "endColumn".to_owned(),
+                JsonValue::Number(self.column_end as f64),
+            ),
+        ]))
+    }
+
+    fn to_sarif_location(&self) -> JsonValue {
+        let mut location = HashMap::from([(
+            "physicalLocation".to_owned(),
+            JsonValue::Object(HashMap::from([
+                (
+                    "artifactLocation".to_owned(),
+                    sarif_artifact(&self.file_name),
+                ),
+                ("region".to_owned(), self.to_sarif_region()),
+            ])),
+        )]);
+        if let Some(label) = &self.label {
+            location.insert("message".to_owned(), sarif_message(label));
+        }
+        JsonValue::Object(location)
+    }
+}
+
+impl Diagnostic {
+    /// Extracts a diagnostic from a parsed line of rustc json output. Returns
+    /// `None` for other messages such as artifact notifications.
+    pub(crate) fn from_json(value: &JsonValue) -> Option<Self> {
+        let JsonValue::Object(map) = value else {
+            return None;
+        };
+        if let Some(message_type) = get_str(map, "$message_type") {
+            if message_type != "diagnostic" {
+                return None;
+            }
+        }
+        let code = match map.get("code") {
+            Some(JsonValue::Object(code)) => get_str(code, "code"),
+            _ => None,
+        };
+        Some(Self {
+            level: get_str(map, "level")?,
+            code,
+            message: get_str(map, "message")?,
+            spans: get_array(map, "spans")
+                .iter()
+                .filter_map(Span::from_json)
+                .collect(),
+            children: get_array(map, "children")
+                .iter()
+                .filter_map(Diagnostic::from_json)
+                .collect(),
+        })
+    }
+
+    /// Returns the primary span of this diagnostic, if any.
+    pub(crate) fn primary_span(&self) -> Option<&Span> {
+        self.spans.iter().find(|s| s.is_primary)
+    }
+
+    /// Returns true for the "aborting due to..." and "N warnings emitted"
+    /// summaries rustc prints at the end of a compilation.
+    pub(crate) fn is_summary(&self) -> bool {
+        self.code.is_none()
+            && self.spans.is_empty()
+            && self.children.is_empty()
+            && (self.message.starts_with("aborting due to") || self.message.ends_with("emitted"))
+    }
+
+    /// Returns all suggested replacements of this diagnostic and its children.
+    pub(crate) fn suggestions(&self) -> Vec<Suggestion<'_>> {
+        let mut suggestions = Vec::new();
+        self.collect_suggestions(&mut suggestions);
+        suggestions
+    }
+
+    fn collect_suggestions<'a>(&'a self, suggestions: &mut Vec<Suggestion<'a>>) {
+        for span in &self.spans {
+            if let Some(replacement) = &span.suggested_replacement {
+                suggestions.push(Suggestion {
+                    message: &self.message,
+                    span,
+                    replacement,
+                });
+            }
+        }
+        for child in &self.children {
+            child.collect_suggestions(suggestions);
+        }
+    }
+
+    /// Returns the suggestions of this diagnostic and its children that rustc
+    /// marked as `MachineApplicable`. Suggestions spanning multiple locations
+    /// are only returned if all of their replacements are machine applicable
+    /// and the text they replace is known.
+    pub(crate) fn machine_applicable_fixes(&self) -> Vec<Fix<'_>> {
+        let mut fixes = Vec::new();
+        self.collect_fixes(self.code.as_deref(), &mut fixes);
+        fixes
+    }
+
+    fn collect_fixes<'a>(&'a self, code: Option<&'a str>, fixes: &mut Vec<Fix<'a>>) {
+        let replacements: Vec<&Span> = self
+            .spans
+            .iter()
+            .filter(|s| s.suggested_replacement.is_some())
+            .collect();
+        if !replacements.is_empty()
+            && replacements
+                .iter()
+                .all(|s| s.suggestion_applicability.as_deref() == Some("MachineApplicable"))
+            && replacements.iter().all(|s| s.text.is_some())
+        {
+            fixes.push(Fix {
+                code,
+                message: &self.message,
+                replacements,
+            });
+        }
+        for child in &self.children {
+            child.collect_fixes(code, fixes);
+        }
+    }
+
+    fn to_normalized_json(&self, with_suggestions: bool) -> JsonValue {
+        let mut map = HashMap::from([
+            ("level".to_owned(), JsonValue::String(self.level.clone())),
+            (
+                "code".to_owned(),
+                self.code.clone().map_or(JsonValue::Null, JsonValue::String),
+            ),
+            (
+                "message".to_owned(),
+                JsonValue::String(self.message.clone()),
+            ),
+            (
+                "primary_span".to_owned(),
+                self.primary_span().map_or(JsonValue::Null, Span::to_json),
+            ),
+            (
+                "children".to_owned(),
+                JsonValue::Array(
+                    self.children
+                        .iter()
+                        .map(|c| c.to_normalized_json(false))
+                        .collect(),
+                ),
+            ),
+        ]);
+        if with_suggestions {
+            let suggestions = self
+                .suggestions()
+                .into_iter()
+                .map(|s| {
+                    let JsonValue::Object(mut suggestion) = s.span.to_json() else {
+                        unreachable!("spans are serialized as objects");
+                    };
+                    suggestion.remove("label");
+                    suggestion.insert(
+                        "message".to_owned(),
+                        JsonValue::String(s.message.to_owned()),
+                    );
+                    suggestion.insert(
+                        "replacement".to_owned(),
+                        JsonValue::String(s.replacement.to_owned()),
+                    );
+                    suggestion.insert(
+                        "applicability".to_owned(),
+                        s.span
+                            .suggestion_applicability
+                            .clone()
+                            .map_or(JsonValue::Null, JsonValue::String),
+                    );
+                    JsonValue::Object(suggestion)
+                })
+                .collect();
+            map.insert("suggestions".to_owned(), JsonValue::Array(suggestions));
+        }
+        JsonValue::Object(map)
+    }
+
+    fn to_sarif_result(&self) -> JsonValue {
+        let level = match self.level.as_str() {
+            "warning" => "warning",
+            "note" | "help" | "failure-note" => "note",
+            // "error" and "error: internal compiler error"
+            _ => "error",
+        };
+        let mut result = HashMap::from([
+            ("level".to_owned(), JsonValue::String(level.to_owned())),
+            ("message".to_owned(), sarif_message(&self.message)),
+            (
+                "locations".to_owned(),
+                JsonValue::Array(
+                    self.primary_span()
+                        .map(Span::to_sarif_location)
+                        .into_iter()
+                        .collect(),
+                ),
+            ),
+        ]);
+        if let Some(code) = &self.code {
+            result.insert("ruleId".to_owned(), JsonValue::String(code.clone()));
+        }
+        let related: Vec<JsonValue> = self
+            .spans
+            .iter()
+            .filter(|s| !s.is_primary)
+            .map(Span::to_sarif_location)
+            .collect();
+        if !related.is_empty() {
+            result.insert("relatedLocations".to_owned(), JsonValue::Array(related));
+        }
+        let fixes: Vec<JsonValue> = self
+            .suggestions()
+            .into_iter()
+            .map(|s| {
+                JsonValue::Object(HashMap::from([
+                    ("description".to_owned(), sarif_message(s.message)),
+                    (
+                        "artifactChanges".to_owned(),
+                        JsonValue::Array(vec![JsonValue::Object(HashMap::from([
+                            (
+                                "artifactLocation".to_owned(),
+                                sarif_artifact(&s.span.file_name),
+                            ),
+                            (
+                                "replacements".to_owned(),
+                                JsonValue::Array(vec![JsonValue::Object(HashMap::from([
+                                    ("deletedRegion".to_owned(), s.span.to_sarif_region()),
+                                    (
+                                        "insertedContent".to_owned(),
+                                        JsonValue::Object(HashMap::from([(
+                                            "text".to_owned(),
+                                            JsonValue::String(s.replacement.to_owned()),
+                                        )])),
+                                    ),
+                                ]))]),
+                            ),
+                        ]))]),
+                    ),
+                ]))
+            })
+            .collect();
+        if !fixes.is_empty() {
+            result.insert("fixes".to_owned(), JsonValue::Array(fixes));
+        }
+        let notes: Vec<String> = self
+            .children
+            .iter()
+            .map(|c| format!("{}: {}", c.level, c.message))
+            .collect();
+        if !notes.is_empty() {
+            result.insert(
+                "properties".to_owned(),
+                JsonValue::Object(HashMap::from([(
+                    "children".to_owned(),
+                    JsonValue::Array(notes.into_iter().map(JsonValue::String).collect()),
+                )])),
+            );
+        }
+        JsonValue::Object(result)
+    }
+}
+
+fn sarif_message(text: &str) -> JsonValue {
+    JsonValue::Object(HashMap::from([(
+        "text".to_owned(),
+        JsonValue::String(text.to_owned()),
+    )]))
+}
+
+fn sarif_artifact(file_name: &str) -> JsonValue {
+    JsonValue::Object(HashMap::from([(
+        "uri".to_owned(),
+        JsonValue::String(file_name.to_owned()),
+    )]))
+}
+
+/// Collects the diagnostics of a rustc invocation.
+#[derive(Debug, Default)]
+pub(crate) struct DiagnosticsCollector {
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl DiagnosticsCollector {
+    /// Records the diagnostic in a line of rustc json output, if any. Lines
+    /// that aren't json diagnostics are ignored.
+    pub(crate) fn collect_line(&mut self, line: &str) {
+        if let Ok(parsed) = line.parse::<JsonValue>() {
+            self.collect(&parsed);
+        }
+    }
+
+    /// Records `value` if it is a diagnostic.
+    pub(crate) fn collect(&mut self, value: &JsonValue) {
+        if let Some(diagnostic) = Diagnostic::from_json(value) {
+            if !diagnostic.is_summary() {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+    }
+
+    /// Writes the collected diagnostics in the given format.
+    pub(crate) fn write(&self, format: DiagnosticsFormat, out: &mut dyn Write) -> io::Result<()> {
+        match format {
+            DiagnosticsFormat::JsonLines => {
+                for diagnostic in &self.diagnostics {
+                    write_sorted(&diagnostic.to_normalized_json(true), out)?;
+                    writeln!(out)?;
+                }
+            }
+            DiagnosticsFormat::Sarif => {
+                let rules: BTreeSet<&str> = self
+                    .diagnostics
+                    .iter()
+                    .filter_map(|d| d.code.as_deref())
+                    .collect();
+                let driver = JsonValue::Object(HashMap::from([
+                    ("name".to_owned(), JsonValue::String("rustc".to_owned())),
+                    (
+                        "informationUri".to_owned(),
+                        JsonValue::String("https://doc.rust-lang.org/rustc/".to_owned()),
+                    ),
+                    (
+                        "rules".to_owned(),
+                        JsonValue::Array(
+                            rules
+                                .into_iter()
+                                .map(|id| {
+                                    JsonValue::Object(HashMap::from([(
+                                        "id".to_owned(),
+                                        JsonValue::String(id.to_owned()),
+                                    )]))
+                                })
+                                .collect(),
+                        ),
+                    ),
+                ]));
+                let run = JsonValue::Object(HashMap::from([
+                    (
+                        "tool".to_owned(),
+                        JsonValue::Object(HashMap::from([("driver".to_owned(), driver)])),
+                    ),
+                    (
+                        "results".to_owned(),
+                        JsonValue::Array(
+                            self.diagnostics
+                                .iter()
+                                .map(Diagnostic::to_sarif_result)
+                                .collect(),
+                        ),
+                    ),
+                ]));
+                let sarif = JsonValue::Object(HashMap::from([
+                    (
+                        "$schema".to_owned(),
+                        JsonValue::String(
+                            "https://json.schemastore.org/sarif-2.1.0.json".to_owned(),
+                        ),
+                    ),
+                    ("version".to_owned(), JsonValue::String("2.1.0".to_owned())),
+                    ("runs".to_owned(), JsonValue::Array(vec![run])),
+                ]));
+                format_sorted(&sarif, out)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the collected diagnostics to the file at `path`.
+    pub(crate) fn write_file(&self, path: &str, format: DiagnosticsFormat) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write(format, &mut out)?;
+        out.flush()
+    }
+
+    /// Writes the machine applicable fixes of the collected diagnostics as
+    /// json lines, one fix per line. Paths are relative to the directory
+    /// rustc ran in, which is usually the exec root. Each replacement records
+    /// the `original_text` it replaces so outdated fixes can be detected.
+    pub(crate) fn write_fixes(&self, out: &mut dyn Write) -> io::Result<()> {
+        for diagnostic in &self.diagnostics {
+            for fix in diagnostic.machine_applicable_fixes() {
+                let replacements = fix
+                    .replacements
+                    .iter()
+                    .map(|span| {
+                        let JsonValue::Object(mut replacement) = span.to_json() else {
+                            unreachable!("spans are serialized as objects");
+                        };
+                        replacement.remove("label");
+                        replacement.insert(
+                            "byte_start".to_owned(),
+                            JsonValue::Number(span.byte_start as f64),
+                        );
+                        replacement.insert(
+                            "byte_end".to_owned(),
+                            JsonValue::Number(span.byte_end as f64),
+                        );
+                        replacement.insert(
+                            "replacement".to_owned(),
+                            JsonValue::String(
+                                span.suggested_replacement.clone().unwrap_or_default(),
+                            ),
+                        );
+                        replacement.insert(
+                            "original_text".to_owned(),
+                            JsonValue::String(span.text.clone().unwrap_or_default()),
+                        );
+                        JsonValue::Object(replacement)
+                    })
+                    .collect();
+                let line = JsonValue::Object(HashMap::from([
+                    (
+                        "code".to_owned(),
+                        fix.code
+                            .map_or(JsonValue::Null, |c| JsonValue::String(c.to_owned())),
+                    ),
+                    (
+                        "message".to_owned(),
+                        JsonValue::String(fix.message.to_owned()),
+                    ),
+                    ("replacements".to_owned(), JsonValue::Array(replacements)),
+                ]));
+                write_sorted(&line, out)?;
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the machine applicable fixes to the file at `path`.
+    pub(crate) fn write_fixes_file(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        self.write_fixes(&mut out)?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const UNUSED_VARIABLE: &str = r#"{"$message_type":"diagnostic","message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"src/lib.rs","byte_start":20,"byte_end":21,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"`#[warn(unused_variables)]` on by default","code":null,"level":"note","spans":[],"children":[],"rendered":null},{"message":"if this is intentional, prefix it with an underscore","code":null,"level":"help","spans":[{"file_name":"src/lib.rs","byte_start":20,"byte_end":21,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[{"text":"    let x = 1;","highlight_start":9,"highlight_end":10}],"label":null,"suggested_replacement":"_x","suggestion_applicability":"MachineApplicable","expansion":null}],"children":[],"rendered":null}],"rendered":"warning: unused variable: `x`\n"}"#;
+    const SUMMARY: &str = r#"{"$message_type":"diagnostic","message":"1 warning emitted","code":null,"level":"warning","spans":[],"children":[],"rendered":"warning: 1 warning emitted\n\n"}"#;
+    const ARTIFACT: &str =
+        r#"{"$message_type":"artifact","artifact":"libfoo.rmeta","emit":"metadata"}"#;
+
+    fn collect(lines: &[&str]) -> DiagnosticsCollector {
+        let mut collector = DiagnosticsCollector::default();
+        for line in lines {
+            collector.collect_line(line);
+        }
+        collector
+    }
+
+    #[test]
+    fn test_collect() {
+        let collector = collect(&[UNUSED_VARIABLE, SUMMARY, ARTIFACT, "not json"]);
+        assert_eq!(collector.diagnostics.len(), 1);
+        let diagnostic = &collector.diagnostics[0];
+        assert_eq!(diagnostic.level, "warning");
+        assert_eq!(diagnostic.code.as_deref(), Some("unused_variables"));
+        assert_eq!(diagnostic.children.len(), 2);
+        let span = diagnostic.primary_span().unwrap();
+        assert_eq!(
+            (span.file_name.as_str(), span.line_start, span.column_start),
+            ("src/lib.rs", 2, 9)
+        );
+        let suggestions = diagnostic.suggestions();
+        assert_eq!(suggestions.len(), 1);
+        assert_eq!(suggestions[0].replacement, "_x");
+        assert_eq!(
+            suggestions[0].message,
+            "if this is intentional, prefix it with an underscore"
+        );
+    }
+
+    #[test]
+    fn test_write_json_lines() {
+        let collector = collect(&[UNUSED_VARIABLE, UNUSED_VARIABLE]);
+        let mut out = Vec::new();
+        collector
+            .write(DiagnosticsFormat::JsonLines, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<JsonValue> = out.lines().map(|l| l.parse().unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[0]["code"],
+            JsonValue::String("unused_variables".into())
+        );
+        assert_eq!(
+            lines[0]["primary_span"]["file_name"],
+            JsonValue::String("src/lib.rs".into())
+        );
+        assert_eq!(
+            lines[0]["primary_span"]["line_start"],
+            JsonValue::Number(2.0)
+        );
+        assert_eq!(
+            lines[0]["suggestions"][0]["applicability"],
+            JsonValue::String("MachineApplicable".into())
+        );
+        assert_eq!(
+            lines[0]["children"][1]["level"],
+            JsonValue::String("help".into())
+        );
+    }
+
+    #[test]
+    fn test_machine_applicable_fixes() {
+        const MAYBE_INCORRECT: &str = r#"{"$message_type":"diagnostic","message":"cannot find value `foo` in this scope","code":{"code":"E0425","explanation":null},"level":"error","spans":[{"file_name":"src/lib.rs","byte_start":30,"byte_end":33,"line_start":3,"line_end":3,"column_start":5,"column_end":8,"is_primary":true,"text":[],"label":"not found in this scope","suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"a local variable with a similar name exists","code":null,"level":"help","spans":[{"file_name":"src/lib.rs","byte_start":30,"byte_end":33,"line_start":3,"line_end":3,"column_start":5,"column_end":8,"is_primary":true,"text":[],"label":null,"suggested_replacement":"fob","suggestion_applicability":"MaybeIncorrect","expansion":null}],"children":[],"rendered":null}],"rendered":"error[E0425]: cannot find value `foo` in this scope\n"}"#;
+        let collector = collect(&[UNUSED_VARIABLE, MAYBE_INCORRECT]);
+        assert!(collector.diagnostics[1]
+            .machine_applicable_fixes()
+            .is_empty());
+
+        let mut out = Vec::new();
+        collector.write_fixes(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let fixes: Vec<JsonValue> = out.lines().map(|l| l.parse().unwrap()).collect();
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(
+            fixes[0]["code"],
+            JsonValue::String("unused_variables".into())
+        );
+        let replacement = &fixes[0]["replacements"][0];
+        assert_eq!(replacement["byte_start"], JsonValue::Number(20.0));
+        assert_eq!(replacement["byte_end"], JsonValue::Number(21.0));
+        assert_eq!(replacement["replacement"], JsonValue::String("_x".into()));
+        assert_eq!(replacement["original_text"], JsonValue::String("x".into()));
+    }
+
+    #[test]
+    fn test_span_text() {
+        let span = |text: &str| {
+            Span::from_json(
+                &format!(r#"{{"file_name":"src/lib.rs","text":{}}}"#, text)
+                    .parse()
+                    .unwrap(),
+            )
+            .unwrap()
+            .text
+        };
+        assert_eq!(span("[]"), None);
+        assert_eq!(
+            span(r#"[{"text":"let é = 1;","highlight_start":5,"highlight_end":6}]"#).as_deref(),
+            Some("é")
+        );
+        assert_eq!(
+            span(
+                r#"[{"text":"fn f() {","highlight_start":7,"highlight_end":9},{"text":"}","highlight_start":1,"highlight_end":2}]"#
+            )
+            .as_deref(),
+            Some(" {\n}")
+        );
+    }
+
+    #[test]
+    fn test_write_sarif() {
+        let collector = collect(&[UNUSED_VARIABLE]);
+        let mut out = Vec::new();
+        collector.write(DiagnosticsFormat::Sarif, &mut out).unwrap();
+        let sarif: JsonValue = String::from_utf8(out).unwrap().parse().unwrap();
+        assert_eq!(sarif["version"], JsonValue::String("2.1.0".into()));
+        let run = &sarif["runs"][0];
+        assert_eq!(
+            run["tool"]["driver"]["rules"][0]["id"],
+            JsonValue::String("unused_variables".into())
+        );
+        let result = &run["results"][0];
+        assert_eq!(result["level"], JsonValue::String("warning".into()));
+        assert_eq!(
+            result["ruleId"],
+            JsonValue::String("unused_variables".into())
+        );
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(
+            location["artifactLocation"]["uri"],
+            JsonValue::String("src/lib.rs".into())
+        );
+        assert_eq!(location["region"]["startColumn"], JsonValue::Number(9.0));
+        let replacement = &result["fixes"][0]["artifactChanges"][0]["replacements"][0];
+        assert_eq!(
+            replacement["insertedContent"]["text"],
+            JsonValue::String("_x".into())
+        );
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright 2026 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Reproducible json serialization.
+//!
+//! tinyjson objects are hash maps, so `JsonValue::stringify` writes their keys
+//! in a different order on every run. Files declared as action outputs are
+//! written with sorted keys instead so they don't defeat caching.
+
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+
+use tinyjson::JsonValue;
+
+/// Writes `value` like `JsonValue::write_to`, with the keys of objects sorted.
+pub(crate) fn write_sorted(value: &JsonValue, out: &mut dyn Write) -> io::Result<()> {
+    write_value(value, out, None, 0)
+}
+
+/// Writes `value` like `JsonValue::format_to`, with the keys of objects sorted.
+pub(crate) fn format_sorted(value: &JsonValue, out: &mut dyn Write) -> io::Result<()> {
+    write_value(value, out, Some("  "), 0)
+}
+
+fn write_newline(out: &mut dyn Write, indent: Option<&str>, level: usize) -> io::Result<()> {
+    if let Some(indent) = indent {
+        writeln!(out)?;
+        for _ in 0..level {
+            out.write_all(indent.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+fn write_value(
+    value: &JsonValue,
+    mut out: &mut dyn Write,
+    indent: Option<&str>,
+    level: usize,
+) -> io::Result<()> {
+    match value {
+        JsonValue::Array(values) if !values.is_empty() => {
+            out.write_all(b"[")?;
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",")?;
+                }
+                write_newline(out, indent, level + 1)?;
+                write_value(value, out, indent, level + 1)?;
+            }
+            write_newline(out, indent, level)?;
+            out.write_all(b"]")
+        }
+        JsonValue::Object(map) if !map.is_empty() => {
+            out.write_all(b"{")?;
+            let sorted: BTreeMap<&String, &JsonValue> = map.iter().collect();
+            for (i, (key, value)) in sorted.into_iter().enumerate() {
+                if i > 0 {
+                    out.write_all(b",")?;
+                }
+                write_newline(out, indent, level + 1)?;
+                JsonValue::String(key.clone()).write_to(&mut out)?;
+                out.write_all(if indent.is_some() { b": " } else { b":" })?;
+                write_value(value, out, indent, level + 1)?;
+            }
+            write_newline(out, indent, level)?;
+            out.write_all(b"}")
+        }
+        // Scalars, and empty arrays and objects, have no keys to sort.
+        _ => value.write_to(&mut out),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::collections::HashMap;
+
+    fn to_string(
+        write: fn(&JsonValue, &mut dyn Write) -> io::Result<()>,
+        value: &JsonValue,
+    ) -> String {
+        let mut out = Vec::new();
+        write(value, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_sorted_keys() {
+        let value = JsonValue::Object(HashMap::from([
+            ("b".to_owned(), JsonValue::Array(vec![JsonValue::Null])),
+            ("a".to_owned(), JsonValue::String("\"quoted\"\n".to_owned())),
+            ("d".to_owned(), JsonValue::Object(HashMap::new())),
+            ("c".to_owned(), JsonValue::Number(1.5)),
+        ]));
+        assert_eq!(
+            to_string(write_sorted, &value),
+            r#"{"a":"\"quoted\"\n","b":[null],"c":1.5,"d":{}}"#
+        );
+        assert_eq!(
+            to_string(format_sorted, &value),
+            "{\n  \"a\": \"\\\"quoted\\\"\\n\",\n  \"b\": [\n    null\n  ],\n  \"c\": 1.5,\n  \"d\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn test_matches_tinyjson_without_objects() {
+        let value = JsonValue::Array(vec![
+            JsonValue::Boolean(true),
+            JsonValue::Array(vec![]),
+            JsonValue::Array(vec![JsonValue::Number(2.0)]),
+        ]);
+        assert_eq!(to_string(write_sorted, &value), value.stringify().unwrap());
+        assert_eq!(to_string(format_sorted, &value), value.format().unwrap());
+    }
+}
@@ -12,23 +12,45 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod diagnostics;
 mod flags;
+mod json;
 mod options;
 mod output;
+mod pipelining;
+mod policy;
+mod remap;
+mod resources;
 mod rustc;
 mod util;
+mod worker;
+// Shared with //util/workspace_status, which process_wrapper can't depend on
+// as it is built without a process wrapper.
+#[allow(dead_code)]
+#[path = "../workspace_status/workspace_status.rs"]
+mod workspace_status;
 
 use std::collections::HashMap;
+use std::env;
 use std::fmt;
-use std::fs::{copy, OpenOptions};
-use std::io;
+use std::fs::{copy, File, OpenOptions};
+use std::io::{self, Read, Write};
 use std::process::{exit, Command, ExitStatus, Stdio};
+use std::sync::Arc;
+use std::thread;
 
 use tinyjson::JsonValue;
 
-use crate::options::options;
+use crate::diagnostics::{DiagnosticsCollector, DiagnosticsFormat};
+use crate::options::{options, options_from_args, Options};
 use crate::output::{process_output, LineOutput};
-use crate::rustc::ErrorFormat;
+use crate::pipelining::{
+    PipelinedCompilation, PipelinedCompilations, PipelinedResult, ScratchOutputs,
+};
+use crate::policy::{LintPolicy, LintPolicyEnforcer};
+use crate::remap::PathRemapper;
+use crate::resources::ResourceReport;
+use crate::rustc::{ErrorFormat, RustcArgs};
 
 #[cfg(windows)]
 fn status_code(status: ExitStatus, was_killed: bool) -> i32 {
@@ -73,21 +95,24 @@ macro_rules! debug_log {
     };
 }
 
-fn json_warning(line: &str) -> JsonValue {
+fn json_diagnostic(level: &str, message: &str, rendered: &str) -> JsonValue {
     JsonValue::Object(HashMap::from([
         (
             "$message_type".to_string(),
             JsonValue::String("diagnostic".to_string()),
         ),
-        ("message".to_string(), JsonValue::String(line.to_string())),
-        ("code".to_string(), JsonValue::Null),
         (
-            "level".to_string(),
-            JsonValue::String("warning".to_string()),
+            "message".to_string(),
+            JsonValue::String(message.to_string()),
         ),
+        ("code".to_string(), JsonValue::Null),
+        ("level".to_string(), JsonValue::String(level.to_string())),
         ("spans".to_string(), JsonValue::Array(Vec::new())),
         ("children".to_string(), JsonValue::Array(Vec::new())),
-        ("rendered".to_string(), JsonValue::String(line.to_string())),
+        (
+            "rendered".to_string(),
+            JsonValue::String(rendered.to_string()),
+        ),
     ]))
 }
 
@@ -96,94 +121,178 @@ fn process_line(
     quit_on_rmeta: bool,
     format: ErrorFormat,
     metadata_emitted: &mut bool,
+    policy: Option<&mut LintPolicyEnforcer>,
+    collector: Option<&mut DiagnosticsCollector>,
 ) -> Result<LineOutput, String> {
+    // `-Z time-passes` output isn't json, so we forward it as is.
+    if line.starts_with("time:") {
+        return Ok(LineOutput::Message(line));
+    }
     // LLVM can emit lines that look like the following, and these will be interspersed
     // with the regular JSON output. Arguably, rustc should be fixed not to emit lines
     // like these (or to convert them to JSON), but for now we convert them to JSON
     // ourselves.
     if line.contains("is not a recognized feature for this target (ignoring feature)") {
-        if let Ok(json_str) = json_warning(&line).stringify() {
+        if let Ok(json_str) = json_diagnostic("warning", &line, &line).stringify() {
             line = json_str;
         } else {
             return Ok(LineOutput::Skip);
         }
     }
-    if quit_on_rmeta {
-        rustc::stop_on_rmeta_completion(line, format, metadata_emitted)
-    } else {
-        rustc::process_json(line, format)
+    if let Some(policy) = policy {
+        match policy.apply(line) {
+            Some(applied) => line = applied,
+            None => return Ok(LineOutput::Skip),
+        }
+    }
+    // Diagnostics are collected after the policy suppressed or promoted them.
+    if let Some(collector) = collector {
+        collector.collect_line(&line);
     }
+    rustc::process_json(line, format, |emit| {
+        if emit != "metadata" {
+            return LineOutput::Skip;
+        }
+        *metadata_emitted = true;
+        if quit_on_rmeta {
+            LineOutput::Terminate
+        } else {
+            LineOutput::Skip
+        }
+    })
 }
 
-fn main() -> Result<(), ProcessWrapperError> {
-    let opts = options().map_err(|e| ProcessWrapperError(e.to_string()))?;
+/// Creates (or truncates) the file at `path`, returning a descriptive error.
+fn create_file(path: &str, what: &str) -> Result<File, ProcessWrapperError> {
+    OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(path)
+        .map_err(|e| ProcessWrapperError(format!("unable to open {}: {}", what, e)))
+}
 
+/// Where `run` sends the output of the child process.
+struct RunOutput<'a> {
+    // Processed child stderr is written here unless the options redirect it
+    // to a file.
+    stderr: &'a mut dyn io::Write,
+    // Raw rustc output is written here if the options don't specify an output
+    // file.
+    raw_output: Option<&'a mut dyn io::Write>,
+    // If set, child stdout that isn't redirected to a file is written to
+    // `stderr` as well instead of being inherited, as the stdout of a
+    // persistent worker is reserved for its responses.
+    capture_stdout: bool,
+    // Invoked as soon as rustc emitted the rmeta file.
+    on_metadata: Option<&'a mut dyn FnMut()>,
+    // If set, receives the resource report of the child process.
+    resource_report: Option<&'a mut Option<ResourceReport>>,
+}
+
+/// Runs the child process described by `opts` and returns its exit code.
+fn run(opts: Options, out: RunOutput<'_>) -> Result<i32, ProcessWrapperError> {
+    let mut report = (opts.resource_report.is_some() || out.resource_report.is_some())
+        .then(|| ResourceReport::start(&opts.executable, &opts.child_arguments));
+    let mut policy = opts
+        .lint_policy
+        .as_ref()
+        .map(|p| LintPolicyEnforcer::new(p, &RustcArgs::from_args(&opts.child_arguments)));
     let mut command = Command::new(opts.executable);
     command
         .args(opts.child_arguments)
         .env_clear()
         .envs(opts.child_environment)
+        // The stdin of a persistent worker carries its work requests.
+        .stdin(Stdio::null())
         .stdout(if let Some(stdout_file) = opts.stdout_file {
-            OpenOptions::new()
-                .create(true)
-                .truncate(true)
-                .write(true)
-                .open(stdout_file)
-                .map_err(|e| ProcessWrapperError(format!("unable to open stdout file: {}", e)))?
-                .into()
+            create_file(&stdout_file, "stdout file")?.into()
+        } else if out.capture_stdout {
+            Stdio::piped()
         } else {
             Stdio::inherit()
         })
         .stderr(Stdio::piped());
     debug_log!("{:#?}", command);
+    if !opts.used_volatile_stamps.is_empty() {
+        debug_log!("volatile stamps used: {:?}", opts.used_volatile_stamps);
+    }
     let mut child = command
         .spawn()
         .map_err(|e| ProcessWrapperError(format!("failed to spawn child process: {}", e)))?;
 
-    let mut stderr: Box<dyn io::Write> = if let Some(stderr_file) = opts.stderr_file {
-        Box::new(
-            OpenOptions::new()
-                .create(true)
-                .truncate(true)
-                .write(true)
-                .open(stderr_file)
-                .map_err(|e| ProcessWrapperError(format!("unable to open stderr file: {}", e)))?,
-        )
+    let mut stderr: Box<dyn io::Write + '_> = if let Some(stderr_file) = opts.stderr_file {
+        Box::new(create_file(&stderr_file, "stderr file")?)
     } else {
-        Box::new(io::stderr())
+        Box::new(out.stderr)
     };
 
     let mut child_stderr = child.stderr.take().ok_or(ProcessWrapperError(
         "unable to get child stderr".to_string(),
     ))?;
 
-    let mut output_file: Option<std::fs::File> = if let Some(output_file_name) = opts.output_file {
-        Some(
-            OpenOptions::new()
-                .create(true)
-                .truncate(true)
-                .write(true)
-                .open(output_file_name)
-                .map_err(|e| ProcessWrapperError(format!("Unable to open output_file: {}", e)))?,
-        )
-    } else {
-        None
-    };
+    // Drain captured stdout concurrently so the child can't block on a full pipe.
+    let stdout_reader = child.stdout.take().map(|mut child_stdout| {
+        thread::spawn(move || {
+            let mut buf = Vec::new();
+            child_stdout.read_to_end(&mut buf).map(|_| buf)
+        })
+    });
 
+    let mut output_file: Option<Box<dyn io::Write + '_>> =
+        if let Some(output_file_name) = opts.output_file {
+            Some(Box::new(create_file(&output_file_name, "output_file")?))
+        } else {
+            out.raw_output
+                .map(|w| Box::new(w) as Box<dyn io::Write + '_>)
+        };
+
+    let mut diagnostics = (opts.diagnostics_output.is_some() || opts.fixes_output.is_some())
+        .then(DiagnosticsCollector::default);
     let mut was_killed = false;
     let result = if let Some(format) = opts.rustc_output_format {
         let quit_on_rmeta = opts.rustc_quit_on_rmeta;
+        let mut on_metadata = out.on_metadata;
         // Process json rustc output and kill the subprocess when we get a signal
         // that we emitted a metadata file.
         let mut me = false;
         let metadata_emitted = &mut me;
+        let mut collector = diagnostics.as_mut();
+        let mut report = report.as_mut();
+        let mut policy = policy.as_mut();
+        let path_remapper = opts.path_remapper.as_ref();
         let result = process_output(
             &mut child_stderr,
             stderr.as_mut(),
-            output_file.as_mut(),
-            move |line| process_line(line, quit_on_rmeta, format, metadata_emitted),
+            output_file
+                .as_mut()
+                .map(|w| w.as_mut() as &mut dyn io::Write),
+            move |line| {
+                let line = match path_remapper {
+                    Some(remapper) => remapper.remap_line(line),
+                    None => line,
+                };
+                if let Some(report) = report.as_deref_mut() {
+                    report.collect_line(&line);
+                }
+                let already_emitted = *metadata_emitted;
+                let output = process_line(
+                    line,
+                    quit_on_rmeta,
+                    format,
+                    metadata_emitted,
+                    policy.as_deref_mut(),
+                    collector.as_deref_mut(),
+                )?;
+                if *metadata_emitted && !already_emitted {
+                    if let Some(on_metadata) = on_metadata.as_mut() {
+                        on_metadata();
+                    }
+                }
+                Ok(output)
+            },
         );
-        if me {
+        if me && quit_on_rmeta {
             // If recv returns Ok(), a signal was sent in this channel so we should terminate the child process.
             // We can safely ignore the Result from kill() as we don't care if the process already terminated.
             let _ = child.kill();
@@ -192,40 +301,368 @@ fn main() -> Result<(), ProcessWrapperError> {
         result
     } else {
         // Process output normally by forwarding stderr
+        let mut report = report.as_mut();
         process_output(
             &mut child_stderr,
             stderr.as_mut(),
-            output_file.as_mut(),
-            move |line| Ok(LineOutput::Message(line)),
+            output_file
+                .as_mut()
+                .map(|w| w.as_mut() as &mut dyn io::Write),
+            move |line| {
+                if let Some(report) = report.as_deref_mut() {
+                    report.collect_line(&line);
+                }
+                Ok(LineOutput::Message(line))
+            },
         )
     };
+    if let Some(collector) = diagnostics {
+        write_diagnostics(
+            &collector,
+            opts.diagnostics_output.as_deref(),
+            opts.diagnostics_format,
+            opts.fixes_output.as_deref(),
+        )?;
+    }
     result.map_err(|e| ProcessWrapperError(format!("failed to process stderr: {}", e)))?;
 
-    let status = child
-        .wait()
-        .map_err(|e| ProcessWrapperError(format!("failed to wait for child process: {}", e)))?;
+    let (status, usage) = if report.is_some() {
+        resources::wait_with_usage(&mut child)
+    } else {
+        child.wait().map(|status| (status, None))
+    }
+    .map_err(|e| ProcessWrapperError(format!("failed to wait for child process: {}", e)))?;
+    if let Some(reader) = stdout_reader {
+        let captured = reader
+            .join()
+            .map_err(|_| ProcessWrapperError("failed to capture child stdout".to_string()))?
+            .map_err(|e| ProcessWrapperError(format!("failed to read child stdout: {}", e)))?;
+        stderr
+            .write_all(&captured)
+            .map_err(|e| ProcessWrapperError(format!("failed to write child stdout: {}", e)))?;
+    }
     // If the child process is rustc and is killed after metadata generation, that's also a success.
-    let code = status_code(status, was_killed);
+    let mut code = status_code(status, was_killed);
+    if let (Some(policy), Some(format)) = (&policy, opts.rustc_output_format) {
+        for violation in policy.violations() {
+            let rendered = format!("error: {}\n", violation);
+            let line = match format {
+                ErrorFormat::Json => json_diagnostic("error", &violation, &rendered)
+                    .stringify()
+                    .map(|json| json + "\n")
+                    .map_err(|e| {
+                        ProcessWrapperError(format!("failed to serialize policy error: {}", e))
+                    })?,
+                ErrorFormat::Rendered => rendered,
+            };
+            stderr
+                .write_all(line.as_bytes())
+                .map_err(|e| ProcessWrapperError(format!("failed to write policy error: {}", e)))?;
+            if code == 0 {
+                code = 1;
+            }
+        }
+    }
+    if let Some(mut report) = report {
+        report.finish(code, usage);
+        if let Some(path) = &opts.resource_report {
+            write_resource_report(&report, path)?;
+        }
+        if let Some(resource_report) = out.resource_report {
+            *resource_report = Some(report);
+        }
+    }
     let success = code == 0;
     if success {
-        if let Some(tf) = opts.touch_file {
-            OpenOptions::new()
-                .create(true)
-                .truncate(true)
-                .write(true)
-                .open(tf)
-                .map_err(|e| ProcessWrapperError(format!("failed to create touch file: {}", e)))?;
+        touch_and_copy(opts.touch_file, opts.copy_output)?;
+    }
+
+    Ok(code)
+}
+
+/// Creates `touch_file` and copies `copy_output` once the child succeeded.
+fn touch_and_copy(
+    touch_file: Option<String>,
+    copy_output: Option<(String, String)>,
+) -> Result<(), ProcessWrapperError> {
+    if let Some(tf) = touch_file {
+        create_file(&tf, "touch file")?;
+    }
+    if let Some((copy_source, copy_dest)) = copy_output {
+        copy(&copy_source, &copy_dest).map_err(|e| {
+            ProcessWrapperError(format!(
+                "failed to copy {} into {}: {}",
+                copy_source, copy_dest, e
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+fn write_diagnostics(
+    collector: &DiagnosticsCollector,
+    diagnostics_output: Option<&str>,
+    format: DiagnosticsFormat,
+    fixes_output: Option<&str>,
+) -> Result<(), ProcessWrapperError> {
+    if let Some(path) = diagnostics_output {
+        collector.write_file(path, format).map_err(|e| {
+            ProcessWrapperError(format!("failed to write diagnostics output: {}", e))
+        })?;
+    }
+    if let Some(path) = fixes_output {
+        collector
+            .write_fixes_file(path)
+            .map_err(|e| ProcessWrapperError(format!("failed to write fixes output: {}", e)))?;
+    }
+    Ok(())
+}
+
+fn write_resource_report(report: &ResourceReport, path: &str) -> Result<(), ProcessWrapperError> {
+    report
+        .write_file(path)
+        .map_err(|e| ProcessWrapperError(format!("failed to write resource report: {}", e)))
+}
+
+/// The outputs of a pipelined request that are derived from the raw rustc
+/// output.
+struct PipelinedOutputFiles {
+    output_file: Option<String>,
+    diagnostics_output: Option<String>,
+    diagnostics_format: DiagnosticsFormat,
+    fixes_output: Option<String>,
+    resource_report: Option<(String, ResourceReport)>,
+    path_remapper: Option<PathRemapper>,
+    lint_policy: Option<(LintPolicy, RustcArgs)>,
+}
+
+impl PipelinedOutputFiles {
+    /// Moves the output files out of `opts` so that the background rustc
+    /// process doesn't write them.
+    fn take(opts: &mut Options) -> Self {
+        Self {
+            output_file: opts.output_file.take(),
+            diagnostics_output: opts.diagnostics_output.take(),
+            diagnostics_format: opts.diagnostics_format,
+            fixes_output: opts.fixes_output.take(),
+            resource_report: opts.resource_report.take().map(|path| {
+                (
+                    path,
+                    ResourceReport::start(&opts.executable, &opts.child_arguments),
+                )
+            }),
+            // The background process still needs these for its own output.
+            path_remapper: opts.path_remapper.clone(),
+            lint_policy: opts
+                .lint_policy
+                .clone()
+                .map(|policy| (policy, RustcArgs::from_args(&opts.child_arguments))),
         }
-        if let Some((copy_source, copy_dest)) = opts.copy_output {
-            copy(&copy_source, &copy_dest).map_err(|e| {
-                ProcessWrapperError(format!(
-                    "failed to copy {} into {}: {}",
-                    copy_source, copy_dest, e
-                ))
-            })?;
+    }
+
+    fn write(self, result: &PipelinedResult) -> Result<(), ProcessWrapperError> {
+        let raw_output = &result.raw_output;
+        if let Some(output_file) = &self.output_file {
+            create_file(output_file, "output_file")?
+                .write_all(raw_output)
+                .map_err(|e| ProcessWrapperError(format!("failed to write output_file: {}", e)))?;
+        }
+        if self.diagnostics_output.is_some() || self.fixes_output.is_some() {
+            // Collect the diagnostics like `run` does, after remapping their
+            // paths and applying the lint policy.
+            let mut collector = DiagnosticsCollector::default();
+            let mut policy = self
+                .lint_policy
+                .as_ref()
+                .map(|(policy, args)| LintPolicyEnforcer::new(policy, args));
+            for line in String::from_utf8_lossy(raw_output).lines() {
+                let mut line = line.to_owned();
+                if let Some(remapper) = &self.path_remapper {
+                    line = remapper.remap_line(line);
+                }
+                if let Some(policy) = policy.as_mut() {
+                    match policy.apply(line) {
+                        Some(applied) => line = applied,
+                        None => continue,
+                    }
+                }
+                collector.collect_line(&line);
+            }
+            write_diagnostics(
+                &collector,
+                self.diagnostics_output.as_deref(),
+                self.diagnostics_format,
+                self.fixes_output.as_deref(),
+            )?;
+        }
+        if let Some((path, mut report)) = self.resource_report {
+            // The report of the rustc process covers the whole compilation. If
+            // it is still running, report the time until this request was
+            // answered instead.
+            report.collect_raw_output(raw_output);
+            report.finish(result.exit_code, None);
+            write_resource_report(result.resources.as_ref().unwrap_or(&report), &path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Starts the rustc process of a pipelined compilation in the background and
+/// returns as soon as it emitted the rmeta file.
+fn run_pipelined_metadata(
+    key: &str,
+    mut opts: Options,
+    compilations: &PipelinedCompilations,
+    output: &mut Vec<u8>,
+) -> Result<i32, ProcessWrapperError> {
+    let (scratch, child_arguments) = match ScratchOutputs::redirect(&opts.child_arguments)
+        .map_err(|e| ProcessWrapperError(format!("failed to redirect rustc outputs: {}", e)))?
+    {
+        Some(redirected) => redirected,
+        // Run the rmeta request on its own, the full request then does the
+        // same.
+        None => return run(opts, worker_output(output)),
+    };
+    let compilation = compilations.start(key, scratch);
+    // The rmeta request only gets the raw output up to the metadata emit, the
+    // full request gets all of it.
+    let output_files = PipelinedOutputFiles::take(&mut opts);
+    let touch_file = opts.touch_file.take();
+    let copy_output = opts.copy_output.take();
+    // Keep rustc running to produce the outputs of the full request.
+    opts.rustc_quit_on_rmeta = false;
+    opts.child_arguments = child_arguments;
+
+    let background = Arc::clone(&compilation);
+    thread::spawn(move || {
+        let mut stderr = background.output();
+        let mut raw_output = background.raw_output();
+        let mut resources = None;
+        let code = match run(
+            opts,
+            RunOutput {
+                stderr: &mut stderr,
+                raw_output: Some(&mut raw_output),
+                capture_stdout: true,
+                on_metadata: Some(&mut || background.metadata_emitted()),
+                resource_report: Some(&mut resources),
+            },
+        ) {
+            Ok(code) => code,
+            Err(e) => {
+                let _ = writeln!(stderr, "{}", e);
+                1
+            }
+        };
+        background.finish(code, resources);
+    });
+
+    let result = compilation.wait_for_metadata();
+    output.extend_from_slice(&result.output);
+    output_files.write(&result)?;
+    if result.exit_code == 0 {
+        compilation
+            .scratch()
+            .publish_metadata()
+            .map_err(|e| ProcessWrapperError(format!("failed to publish metadata: {}", e)))?;
+        touch_and_copy(touch_file, copy_output)?;
+    }
+    Ok(result.exit_code)
+}
+
+/// Waits for the background rustc process of a pipelined compilation and
+/// reports its result for the full request.
+fn finish_pipelined(
+    compilation: &PipelinedCompilation,
+    mut opts: Options,
+    output: &mut Vec<u8>,
+) -> Result<i32, ProcessWrapperError> {
+    let result = compilation.wait_for_completion();
+    output.extend_from_slice(&result.output);
+    PipelinedOutputFiles::take(&mut opts).write(&result)?;
+    if result.exit_code == 0 {
+        compilation
+            .scratch()
+            .publish(&opts.rustc_pipelined_outputs)
+            .map_err(|e| ProcessWrapperError(format!("failed to publish outputs: {}", e)))?;
+        touch_and_copy(opts.touch_file, opts.copy_output)?;
+    }
+    Ok(result.exit_code)
+}
+
+/// Handles a single persistent worker request, returning the exit code and
+/// the output to report back to Bazel.
+fn handle_work_request(
+    arguments: Vec<String>,
+    request_id: i32,
+    compilations: &PipelinedCompilations,
+) -> (i32, String) {
+    let mut output = Vec::new();
+    // The request arguments don't include the program name.
+    let argv = env::args().take(1).chain(arguments).collect();
+    let result = options_from_args(argv)
+        .map_err(|e| ProcessWrapperError(e.to_string()))
+        .and_then(|opts| match opts.rustc_pipelining_key.clone() {
+            // Singleplex requests may be spread over several worker processes,
+            // so only multiplex requests are pipelined.
+            Some(key) if request_id != 0 && opts.rustc_quit_on_rmeta => {
+                run_pipelined_metadata(&key, opts, compilations, &mut output)
+            }
+            Some(key) if request_id != 0 => match compilations.take(&key) {
+                Some(compilation) => finish_pipelined(&compilation, opts, &mut output),
+                // The rmeta request was never sent to this worker, so run
+                // rustc from scratch.
+                None => run(opts, worker_output(&mut output)),
+            },
+            _ => run(opts, worker_output(&mut output)),
+        });
+    let code = match result {
+        Ok(code) => code,
+        Err(e) => {
+            output.extend_from_slice(format!("{e}\n").as_bytes());
+            1
         }
+    };
+    (code, String::from_utf8_lossy(&output).into_owned())
+}
+
+fn worker_output(output: &mut Vec<u8>) -> RunOutput<'_> {
+    RunOutput {
+        stderr: output,
+        raw_output: None,
+        capture_stdout: true,
+        on_metadata: None,
+        resource_report: None,
+    }
+}
+
+fn main() -> Result<(), ProcessWrapperError> {
+    let argv: Vec<String> = env::args().collect();
+    if worker::is_persistent_worker(&argv) {
+        let protocol =
+            worker::worker_protocol(argv).map_err(|e| ProcessWrapperError(e.to_string()))?;
+        let compilations = PipelinedCompilations::default();
+        let stdin = io::stdin();
+        return worker::run_worker(
+            stdin.lock(),
+            io::stdout(),
+            protocol,
+            |arguments, request_id| handle_work_request(arguments, request_id, &compilations),
+        )
+        .map_err(|e| ProcessWrapperError(e.to_string()));
     }
 
+    let opts = options().map_err(|e| ProcessWrapperError(e.to_string()))?;
+    let code = run(
+        opts,
+        RunOutput {
+            stderr: &mut io::stderr(),
+            raw_output: None,
+            capture_stdout: false,
+            on_metadata: None,
+            resource_report: None,
+        },
+    )?;
     exit(code)
 }
 
@@ -251,6 +688,8 @@ mod test {
             false,
             ErrorFormat::Json,
             &mut metadata_emitted,
+            None,
+            None,
         )?
         else {
             return Err("Expected a LineOutput::Message".to_string());
@@ -283,6 +722,8 @@ mod test {
             /*quit_on_rmeta=*/ false,
             ErrorFormat::Rendered,
             &mut metadata_emitted,
+            None,
+            None,
         )?
         else {
             return Err("Expected a LineOutput::Message".to_string());
@@ -299,6 +740,8 @@ mod test {
             /*quit_on_rmeta=*/ false,
             ErrorFormat::Json,
             &mut metadata_emitted,
+            None,
+            None,
         )?
         else {
             return Err("Expected a LineOutput::Message".to_string());
@@ -322,6 +765,55 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_process_line_time_passes() -> Result<(), String> {
+        let mut metadata_emitted = false;
+        let line = "time:   0.012; rss:   35MB ->   38MB (   +3MB)\tmacro_expand_crate\n";
+        let LineOutput::Message(msg) = process_line(
+            line.to_string(),
+            /*quit_on_rmeta=*/ false,
+            ErrorFormat::Json,
+            &mut metadata_emitted,
+            None,
+            None,
+        )?
+        else {
+            return Err("Expected a LineOutput::Message".to_string());
+        };
+        assert_eq!(msg, line);
+        Ok(())
+    }
+
+    #[test]
+    fn test_process_line_collects_after_policy() -> Result<(), String> {
+        let policy =
+            LintPolicy::parse(r#"{"rules": [{"lints": ["unused_variables"], "level": "allow"}]}"#)?;
+        let mut enforcer = LintPolicyEnforcer::new(&policy, &RustcArgs::default());
+        let mut collector = DiagnosticsCollector::default();
+        let mut metadata_emitted = false;
+        for code in ["unused_variables", "dead_code"] {
+            process_line(
+                format!(
+                    r#"{{"$message_type":"diagnostic","message":"m","code":{{"code":"{}"}},"level":"warning","spans":[],"children":[],"rendered":"warning: m\n"}}"#,
+                    code
+                ),
+                /*quit_on_rmeta=*/ false,
+                ErrorFormat::Json,
+                &mut metadata_emitted,
+                Some(&mut enforcer),
+                Some(&mut collector),
+            )?;
+        }
+        let mut out = Vec::new();
+        collector
+            .write(DiagnosticsFormat::JsonLines, &mut out)
+            .map_err(|e| e.to_string())?;
+        let out = String::from_utf8(out).map_err(|e| e.to_string())?;
+        assert_eq!(out.lines().count(), 1);
+        assert!(out.contains("dead_code"), "{}", out);
+        Ok(())
+    }
+
     #[test]
     fn test_process_line_emit_link() -> Result<(), String> {
         let mut metadata_emitted = false;
@@ -337,6 +829,8 @@ mod test {
                 /*quit_on_rmeta=*/ true,
                 ErrorFormat::Rendered,
                 &mut metadata_emitted,
+                None,
+                None,
             )?,
             LineOutput::Skip
         ));
@@ -359,10 +853,36 @@ mod test {
                 /*quit_on_rmeta=*/ true,
                 ErrorFormat::Rendered,
                 &mut metadata_emitted,
+                None,
+                None,
             )?,
             LineOutput::Terminate
         ));
         assert!(metadata_emitted);
         Ok(())
     }
+
+    #[test]
+    fn test_process_line_emit_metadata_pipelined() -> Result<(), String> {
+        let mut metadata_emitted = false;
+        assert!(matches!(
+            process_line(
+                r#"
+                {
+                    "$message_type": "artifact",
+                    "emit": "metadata"
+                }
+            "#
+                .to_string(),
+                /*quit_on_rmeta=*/ false,
+                ErrorFormat::Rendered,
+                &mut metadata_emitted,
+                None,
+                None,
+            )?,
+            LineOutput::Skip
+        ));
+        assert!(metadata_emitted);
+        Ok(())
+    }
 }
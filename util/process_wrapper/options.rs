@@ -5,9 +5,13 @@ use std::fs::File;
 use std::io::{self, Write};
 use std::process::exit;
 
+use crate::diagnostics::DiagnosticsFormat;
 use crate::flags::{FlagParseError, Flags, ParseOutcome};
+use crate::policy::LintPolicy;
+use crate::remap::PathRemapper;
 use crate::rustc;
 use crate::util::*;
+use crate::workspace_status::{MissingKeys, Substitution, WorkspaceStatus};
 
 #[derive(Debug)]
 pub(crate) enum OptionError {
@@ -47,11 +51,41 @@ pub(crate) struct Options {
     // If set, it configures rustc to emit an rmeta file and then
     // quit.
     pub(crate) rustc_quit_on_rmeta: bool,
+    // If set while running as a persistent worker, the rmeta request and the
+    // full request sharing this key are served by a single rustc process.
+    pub(crate) rustc_pipelining_key: Option<String>,
+    // Outputs of the full request that a pipelined rustc process writes. They
+    // are moved in place from its scratch directory once the full request
+    // claims them.
+    pub(crate) rustc_pipelined_outputs: Vec<String>,
     // This controls the output format of rustc messages.
     pub(crate) rustc_output_format: Option<rustc::ErrorFormat>,
+    // If set, writes a report of all rustc diagnostics to this file.
+    pub(crate) diagnostics_output: Option<String>,
+    // The format of the diagnostics report.
+    pub(crate) diagnostics_format: DiagnosticsFormat,
+    // If set, writes the machine applicable suggestions of rustc to this file.
+    pub(crate) fixes_output: Option<String>,
+    // If set, writes the resource usage and timings of the child process to
+    // this file.
+    pub(crate) resource_report: Option<String>,
+    // If set, the warning and lint policy applied to rustc diagnostics.
+    pub(crate) lint_policy: Option<LintPolicy>,
+    // If set, rewrites the paths in rustc diagnostics.
+    pub(crate) path_remapper: Option<PathRemapper>,
+    // The volatile workspace status keys substituted into the child
+    // environment. Outputs which depend on them are never cached.
+    pub(crate) used_volatile_stamps: Vec<String>,
 }
 
 pub(crate) fn options() -> Result<Options, OptionError> {
+    options_from_args(env::args().collect())
+}
+
+/// Parses the process_wrapper options from `argv`, which includes the
+/// program name. This is used both for the command line of a one-shot
+/// invocation and for the arguments of each persistent worker request.
+pub(crate) fn options_from_args(argv: Vec<String>) -> Result<Options, OptionError> {
     // Process argument list until -- is encountered.
     // Everything after is sent to the child process.
     let mut subst_mapping_raw = None;
@@ -59,13 +93,23 @@ pub(crate) fn options() -> Result<Options, OptionError> {
     let mut volatile_status_file_raw = None;
     let mut env_file_raw = None;
     let mut arg_file_raw = None;
+    let mut bin_arg_file_raw = None;
     let mut touch_file = None;
     let mut copy_output_raw = None;
     let mut stdout_file = None;
     let mut stderr_file = None;
     let mut output_file = None;
     let mut rustc_quit_on_rmeta_raw = None;
+    let mut rustc_pipelining_key = None;
+    let mut rustc_pipelined_outputs_raw = None;
     let mut rustc_output_format_raw = None;
+    let mut diagnostics_output = None;
+    let mut diagnostics_format_raw = None;
+    let mut fixes_output = None;
+    let mut resource_report = None;
+    let mut lint_policy_raw = None;
+    let mut remap_diagnostic_paths_raw = None;
+    let mut remap_diagnostic_path_prefix_raw = None;
     let mut flags = Flags::new();
     flags.define_repeated_flag("--subst", "", &mut subst_mapping_raw);
     flags.define_flag("--stable-status-file", "", &mut stable_status_file_raw);
@@ -80,6 +124,12 @@ pub(crate) fn options() -> Result<Options, OptionError> {
         "File(s) containing command line arguments to pass to the child process.",
         &mut arg_file_raw,
     );
+    flags.define_repeated_flag(
+        "--bin-arg-file",
+        "File(s) containing `BIN=ARG` lines. ARG is passed to the child process if BIN is `*` \
+        or matches the `--crate-name` of the rustc invocation.",
+        &mut bin_arg_file_raw,
+    );
     flags.define_flag(
         "--touch-file",
         "Create this file after the child process runs successfully.",
@@ -106,6 +156,17 @@ pub(crate) fn options() -> Result<Options, OptionError> {
         "If enabled, this wrapper will terminate rustc after rmeta has been emitted.",
         &mut rustc_quit_on_rmeta_raw,
     );
+    flags.define_flag(
+        "--rustc-pipelining-key",
+        "When running as a persistent worker, serve the --rustc-quit-on-rmeta request \
+        and the full request with the same key from a single rustc process.",
+        &mut rustc_pipelining_key,
+    );
+    flags.define_repeated_flag(
+        "--rustc-pipelined-output",
+        "Output(s) of the full request written by a pipelined rustc process.",
+        &mut rustc_pipelined_outputs_raw,
+    );
     flags.define_flag(
         "--rustc-output-format",
         "Controls the rustc output format if --rustc-quit-on-rmeta is set.\n\
@@ -114,9 +175,55 @@ pub(crate) fn options() -> Result<Options, OptionError> {
         Default: `rendered`",
         &mut rustc_output_format_raw,
     );
+    flags.define_flag(
+        "--diagnostics-output",
+        "Write a report of all rustc diagnostics to this file. Requires --rustc-output-format.",
+        &mut diagnostics_output,
+    );
+    flags.define_flag(
+        "--diagnostics-format",
+        "The format of the --diagnostics-output report.\n\
+        'sarif' will write a SARIF 2.1.0 log, \
+        'jsonl' will write one normalized json object per diagnostic.\n\
+        Default: `jsonl`",
+        &mut diagnostics_format_raw,
+    );
+    flags.define_flag(
+        "--fixes-output",
+        "Write the machine applicable suggestions of rustc to this file as json lines. \
+        Requires --rustc-output-format.",
+        &mut fixes_output,
+    );
+    flags.define_flag(
+        "--resource-report",
+        "Write the wall time, CPU time and peak memory usage of the child process to this \
+        file as json, along with the timings rustc reports with `--json=timings` or \
+        `-Z time-passes`.",
+        &mut resource_report,
+    );
+    flags.define_flag(
+        "--lint-policy",
+        "A json file with rules to suppress warnings, promote them to errors or limit their \
+        number. Requires --rustc-output-format.",
+        &mut lint_policy_raw,
+    );
+    flags.define_flag(
+        "--remap-diagnostic-paths",
+        "If set to `true`, rewrites the paths in rustc diagnostics relative to the workspace, \
+        stripping the exec root and mapping sources symlinked into `bazel-out` back to the \
+        source tree. Requires --rustc-output-format.",
+        &mut remap_diagnostic_paths_raw,
+    );
+    flags.define_repeated_flag(
+        "--remap-diagnostic-path-prefix",
+        "A `FROM=TO` mapping rewriting paths in rustc diagnostics starting with `FROM`. \
+        Substitutions from --subst are applied to both sides. The last matching mapping \
+        is used. Requires --rustc-output-format.",
+        &mut remap_diagnostic_path_prefix_raw,
+    );
 
     let mut child_args = match flags
-        .parse(env::args().collect())
+        .parse(expand_flagfiles(argv)?)
         .map_err(OptionError::FlagError)?
     {
         ParseOutcome::Help(help) => {
@@ -146,12 +253,29 @@ pub(crate) fn options() -> Result<Options, OptionError> {
             Ok((key.to_owned(), v))
         })
         .collect::<Result<Vec<(String, String)>, OptionError>>()?;
-    let stable_stamp_mappings =
-        stable_status_file_raw.map_or_else(Vec::new, |s| read_stamp_status_to_array(s).unwrap());
-    let volatile_stamp_mappings =
-        volatile_status_file_raw.map_or_else(Vec::new, |s| read_stamp_status_to_array(s).unwrap());
+    let read_stamps = |path: Option<String>| {
+        path.map_or_else(
+            || Ok(Vec::new()),
+            |path| {
+                read_stamp_status_to_array(path.clone()).map_err(|e| {
+                    OptionError::Generic(format!("failed to read status file {path}: {e}"))
+                })
+            },
+        )
+    };
+    let workspace_status = WorkspaceStatus::from_stamps(
+        read_stamps(stable_status_file_raw)?,
+        read_stamps(volatile_status_file_raw)?,
+    );
     let environment_file_block = env_from_files(env_file_raw.unwrap_or_default())?;
     let mut file_arguments = args_from_file(arg_file_raw.unwrap_or_default())?;
+    if let Some(bin_arg_files) = bin_arg_file_raw {
+        let crate_name = rustc::RustcArgs::from_args(&child_args).crate_name;
+        file_arguments.append(&mut bin_args_from_file(
+            bin_arg_files,
+            crate_name.as_deref(),
+        )?);
+    }
     // Process --copy-output
     let copy_output = copy_output_raw
         .map(|co| {
@@ -182,15 +306,66 @@ pub(crate) fn options() -> Result<Options, OptionError> {
             ))),
         })
         .transpose()?;
+    let diagnostics_format = match diagnostics_format_raw.as_deref() {
+        None | Some("jsonl") => DiagnosticsFormat::JsonLines,
+        Some("sarif") => DiagnosticsFormat::Sarif,
+        Some(v) => {
+            return Err(OptionError::Generic(format!(
+                "invalid --diagnostics-format '{v}'",
+            )))
+        }
+    };
+    if lint_policy_raw.is_some() && rustc_output_format.is_none() {
+        return Err(OptionError::Generic(
+            "\"--lint-policy\" requires \"--rustc-output-format\"".to_owned(),
+        ));
+    }
+    let lint_policy = lint_policy_raw
+        .map(|path| {
+            LintPolicy::read(&path).map_err(|e| {
+                OptionError::Generic(format!("failed to read lint policy {path}: {e}"))
+            })
+        })
+        .transpose()?;
+    let remap_diagnostic_paths = remap_diagnostic_paths_raw.is_some_and(|s| s == "true");
+    let path_remapper = if remap_diagnostic_paths || remap_diagnostic_path_prefix_raw.is_some() {
+        if rustc_output_format.is_none() {
+            return Err(OptionError::Generic(
+                "\"--remap-diagnostic-paths\" and \"--remap-diagnostic-path-prefix\" require \
+                \"--rustc-output-format\""
+                    .to_owned(),
+            ));
+        }
+        let prefixes: Vec<String> = remap_diagnostic_path_prefix_raw
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mapping| prepare_arg(mapping, &subst_mappings))
+            .collect();
+        Some(
+            PathRemapper::new(&prefixes, remap_diagnostic_paths).map_err(|e| {
+                OptionError::Generic(format!("invalid --remap-diagnostic-path-prefix: {e}"))
+            })?,
+        )
+    } else {
+        None
+    };
+    if (diagnostics_output.is_some() || fixes_output.is_some()) && rustc_output_format.is_none() {
+        return Err(OptionError::Generic(
+            "\"--diagnostics-output\" and \"--fixes-output\" require \"--rustc-output-format\""
+                .to_owned(),
+        ));
+    }
+    if rustc_pipelining_key.is_some() && rustc_output_format.is_none() {
+        return Err(OptionError::Generic(
+            "\"--rustc-pipelining-key\" requires \"--rustc-output-format\"".to_owned(),
+        ));
+    }
 
     // Prepare the environment variables, unifying those read from files with the ones
     // of the current process.
-    let vars = environment_block(
-        environment_file_block,
-        &stable_stamp_mappings,
-        &volatile_stamp_mappings,
-        &subst_mappings,
-    );
+    let mut stamps = workspace_status.substitution(MissingKeys::Keep);
+    let vars = environment_block(environment_file_block, &mut stamps, &subst_mappings)?;
+    let used_volatile_stamps = stamps.used_volatile_keys().map(str::to_owned).collect();
     // Append all the arguments fetched from files to those provided via command line.
     child_args.append(&mut file_arguments);
     let child_args = prepare_args(child_args, &subst_mappings)?;
@@ -211,7 +386,16 @@ pub(crate) fn options() -> Result<Options, OptionError> {
         stderr_file,
         output_file,
         rustc_quit_on_rmeta,
+        rustc_pipelining_key,
+        rustc_pipelined_outputs: rustc_pipelined_outputs_raw.unwrap_or_default(),
         rustc_output_format,
+        diagnostics_output,
+        diagnostics_format,
+        fixes_output,
+        resource_report,
+        lint_policy,
+        path_remapper,
+        used_volatile_stamps,
     })
 }
 
@@ -229,6 +413,50 @@ fn args_from_file(paths: Vec<String>) -> Result<Vec<String>, OptionError> {
     Ok(args)
 }
 
+fn bin_args_from_file(
+    paths: Vec<String>,
+    crate_name: Option<&str>,
+) -> Result<Vec<String>, OptionError> {
+    // Cargo binary names may contain dashes, which rustc crate names can't.
+    let normalize = |name: &str| name.replace('-', "_");
+    let crate_name = crate_name.map(normalize);
+    let mut args = vec![];
+    for line in args_from_file(paths)? {
+        let (bin, arg) = line
+            .split_once('=')
+            .ok_or_else(|| OptionError::Generic(format!("invalid bin arg file line: {line}")))?;
+        if bin == "*" || crate_name.as_deref() == Some(normalize(bin).as_str()) {
+            args.push(arg.to_owned());
+        }
+    }
+    Ok(args)
+}
+
+/// Expands the `@flagfile` arguments given to process_wrapper itself, which
+/// contain one argument per line. Bazel only passes flag files to persistent
+/// workers and expands them in their work requests, so this lets the same
+/// command line run without a worker. Arguments after `--` belong to the
+/// child process and are kept as is.
+fn expand_flagfiles(argv: Vec<String>) -> Result<Vec<String>, OptionError> {
+    let mut expanded = Vec::new();
+    let mut child_args = false;
+    for arg in argv {
+        match arg.strip_prefix('@') {
+            Some(flagfile) if !child_args => {
+                for line in read_file_to_array(flagfile).map_err(OptionError::Generic)? {
+                    child_args |= line == "--";
+                    expanded.push(line);
+                }
+            }
+            _ => {
+                child_args |= arg == "--";
+                expanded.push(arg);
+            }
+        }
+    }
+    Ok(expanded)
+}
+
 fn env_from_files(paths: Vec<String>) -> Result<HashMap<String, String>, OptionError> {
     let mut env_vars = HashMap::new();
     for path in paths.into_iter() {
@@ -307,10 +535,9 @@ fn prepare_args(
 
 fn environment_block(
     environment_file_block: HashMap<String, String>,
-    stable_stamp_mappings: &[(String, String)],
-    volatile_stamp_mappings: &[(String, String)],
+    stamps: &mut Substitution<'_>,
     subst_mappings: &[(String, String)],
-) -> HashMap<String, String> {
+) -> Result<HashMap<String, String>, OptionError> {
     // Taking all environment variables from the current process
     // and sending them down to the child process
     let mut environment_variables: HashMap<String, String> = std::env::vars().collect();
@@ -318,12 +545,10 @@ fn environment_block(
     // This is simpler than needing to track duplicates and explicitly override
     // them.
     environment_variables.extend(environment_file_block);
-    for (f, replace_with) in &[stable_stamp_mappings, volatile_stamp_mappings].concat() {
-        for value in environment_variables.values_mut() {
-            let from = format!("{{{f}}}");
-            let new = value.replace(from.as_str(), replace_with);
-            *value = new;
-        }
+    for value in environment_variables.values_mut() {
+        *value = stamps
+            .substitute(value)
+            .map_err(|e| OptionError::Generic(e.to_string()))?;
     }
     for (f, replace_with) in subst_mappings {
         for value in environment_variables.values_mut() {
@@ -332,5 +557,61 @@ fn environment_block(
             *value = new;
         }
     }
-    environment_variables
+    Ok(environment_variables)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_bin_args_from_file() {
+        let path = std::path::PathBuf::from(std::env::var("TEST_TMPDIR").unwrap())
+            .join("test_bin_args_from_file.binlinkflags");
+        std::fs::write(
+            &path,
+            "named-bin=--named-bin-only\n*=--all-bins\nother_bin=--other-bin-only\n",
+        )
+        .unwrap();
+        let paths = vec![path.display().to_string()];
+
+        assert_eq!(
+            bin_args_from_file(paths.clone(), Some("named_bin")).unwrap(),
+            ["--named-bin-only", "--all-bins"]
+        );
+        assert_eq!(
+            bin_args_from_file(paths.clone(), None).unwrap(),
+            ["--all-bins"]
+        );
+    }
+
+    #[test]
+    fn test_environment_block_substitutes_stamps() {
+        let status = WorkspaceStatus::parse(
+            "STABLE_VERSION 1.2.3\nBUILD_USER user",
+            "BUILD_TIMESTAMP 1730574875",
+        )
+        .unwrap();
+        let mut stamps = status.substitution(MissingKeys::Keep);
+        let env = environment_block(
+            HashMap::from([
+                ("VERSION".to_owned(), "{STABLE_VERSION}".to_owned()),
+                ("TIMESTAMP".to_owned(), "{BUILD_TIMESTAMP}".to_owned()),
+                ("UNKNOWN".to_owned(), "{UNKNOWN}".to_owned()),
+                ("PWD_DIR".to_owned(), "${pwd}/dir".to_owned()),
+            ]),
+            &mut stamps,
+            &[("pwd".to_owned(), "/exec_root".to_owned())],
+        )
+        .unwrap();
+
+        assert_eq!(env["VERSION"], "1.2.3");
+        assert_eq!(env["TIMESTAMP"], "1730574875");
+        assert_eq!(env["UNKNOWN"], "{UNKNOWN}");
+        assert_eq!(env["PWD_DIR"], "/exec_root/dir");
+        assert_eq!(
+            stamps.used_volatile_keys().collect::<Vec<_>>(),
+            ["BUILD_TIMESTAMP"]
+        );
+    }
 }
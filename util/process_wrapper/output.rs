@@ -71,7 +71,7 @@ pub(crate) type LineResult = Result<LineOutput, String>;
 pub(crate) fn process_output<F>(
     read_end: &mut dyn Read,
     output_write_end: &mut dyn Write,
-    opt_file_write_end: Option<&mut std::fs::File>,
+    opt_file_write_end: Option<&mut dyn Write>,
     mut process_line: F,
 ) -> ProcessResult
 where
@@ -0,0 +1,542 @@
+// Copyright 2026 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bookkeeping for pipelined compilations in persistent worker mode.
+//!
+//! Without a worker, pipelining runs rustc twice per crate: once for the
+//! rmeta action, which is terminated as soon as metadata is emitted, and once
+//! for the full action. In worker mode the rmeta request instead starts a
+//! single rustc process, responds as soon as metadata is emitted and leaves
+//! rustc running in the background. The full request with the same
+//! pipelining key then waits for that process instead of starting a new one.
+//!
+//! Only multiplex requests are pipelined, as Bazel sends all of them for a
+//! worker key to the same process. The background rustc process writes its
+//! outputs to a private scratch directory, so the outputs of the full action
+//! are only written once its request claims them. If the full request never
+//! arrives, the compilation expires and its outputs are discarded.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::resources::ResourceReport;
+
+/// The result of a (partial) pipelined compilation.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct PipelinedResult {
+    pub(crate) exit_code: i32,
+    /// Processed output that wasn't reported by a previous request.
+    pub(crate) output: Vec<u8>,
+    /// All raw rustc output so far, for `--output-file`.
+    pub(crate) raw_output: Vec<u8>,
+    /// The resource report of rustc once it exited.
+    pub(crate) resources: Option<ResourceReport>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    output: Vec<u8>,
+    raw_output: Vec<u8>,
+    // The length of `output` and `raw_output` when rustc emitted metadata.
+    metadata_emitted: Option<(usize, usize)>,
+    exit_code: Option<i32>,
+    resources: Option<ResourceReport>,
+    finished_at: Option<Instant>,
+}
+
+/// A rustc process shared by the rmeta and the full request of a crate.
+#[derive(Debug)]
+pub(crate) struct PipelinedCompilation {
+    state: Mutex<State>,
+    changed: Condvar,
+    scratch: ScratchOutputs,
+}
+
+impl PipelinedCompilation {
+    fn new(scratch: ScratchOutputs) -> Self {
+        Self {
+            state: Mutex::default(),
+            changed: Condvar::new(),
+            scratch,
+        }
+    }
+
+    /// The outputs of the rustc process.
+    pub(crate) fn scratch(&self) -> &ScratchOutputs {
+        &self.scratch
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // A panic while holding the lock can only happen while appending to a
+        // buffer, so the state is still usable.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait_until(&self, done: impl Fn(&State) -> bool) -> MutexGuard<'_, State> {
+        let mut state = self.lock();
+        while !done(&state) {
+            state = self.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        state
+    }
+
+    /// Returns a writer for the processed rustc output.
+    pub(crate) fn output(self: &Arc<Self>) -> PipelinedWriter {
+        PipelinedWriter {
+            compilation: Arc::clone(self),
+            raw: false,
+        }
+    }
+
+    /// Returns a writer for the raw rustc output.
+    pub(crate) fn raw_output(self: &Arc<Self>) -> PipelinedWriter {
+        PipelinedWriter {
+            compilation: Arc::clone(self),
+            raw: true,
+        }
+    }
+
+    /// Records that rustc emitted the rmeta file.
+    pub(crate) fn metadata_emitted(&self) {
+        let mut state = self.lock();
+        state.metadata_emitted = Some((state.output.len(), state.raw_output.len()));
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    /// Records that rustc exited with `exit_code`.
+    pub(crate) fn finish(&self, exit_code: i32, resources: Option<ResourceReport>) {
+        let mut state = self.lock();
+        state.exit_code = Some(exit_code);
+        state.resources = resources;
+        state.finished_at = Some(Instant::now());
+        drop(state);
+        self.changed.notify_all();
+    }
+
+    fn is_finished(&self) -> bool {
+        self.lock().exit_code.is_some()
+    }
+
+    fn is_expired(&self, now: Instant) -> bool {
+        self.lock()
+            .finished_at
+            .is_some_and(|finished_at| now >= finished_at + UNCLAIMED_TIMEOUT)
+    }
+
+    /// Blocks until rustc emitted the rmeta file or exited and returns the
+    /// output up to that point. The processed output returned is consumed
+    /// unless rustc failed before emitting rmeta, in which case the full
+    /// request reports the same failure.
+    pub(crate) fn wait_for_metadata(&self) -> PipelinedResult {
+        let mut state = self.wait_until(|s| s.metadata_emitted.is_some() || s.exit_code.is_some());
+        if let Some((output_len, raw_output_len)) = state.metadata_emitted {
+            PipelinedResult {
+                exit_code: 0,
+                output: state.output.drain(..output_len).collect(),
+                raw_output: state.raw_output[..raw_output_len].to_vec(),
+                resources: None,
+            }
+        } else {
+            PipelinedResult {
+                exit_code: state.exit_code.unwrap_or(1),
+                output: state.output.clone(),
+                raw_output: state.raw_output.clone(),
+                resources: state.resources.clone(),
+            }
+        }
+    }
+
+    /// Blocks until rustc exited and returns the output that wasn't reported
+    /// by the rmeta request.
+    pub(crate) fn wait_for_completion(&self) -> PipelinedResult {
+        let mut state = self.wait_until(|s| s.exit_code.is_some());
+        PipelinedResult {
+            exit_code: state.exit_code.unwrap_or(1),
+            output: std::mem::take(&mut state.output),
+            raw_output: state.raw_output.clone(),
+            resources: state.resources.take(),
+        }
+    }
+}
+
+/// Appends everything written to the output buffers of a compilation.
+pub(crate) struct PipelinedWriter {
+    compilation: Arc<PipelinedCompilation>,
+    raw: bool,
+}
+
+impl io::Write for PipelinedWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut state = self.compilation.lock();
+        if self.raw {
+            state.raw_output.extend_from_slice(buf);
+        } else {
+            state.output.extend_from_slice(buf);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// How long a finished compilation is kept for its full request. The full
+/// request may never arrive, e.g. when Bazel doesn't need the outputs of the
+/// full action or runs it somewhere else.
+const UNCLAIMED_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// The pipelined compilations of a worker, indexed by pipelining key.
+#[derive(Debug, Default)]
+pub(crate) struct PipelinedCompilations {
+    compilations: Mutex<HashMap<String, Arc<PipelinedCompilation>>>,
+}
+
+impl PipelinedCompilations {
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Arc<PipelinedCompilation>>> {
+        self.compilations.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Registers a new compilation for `key` writing to `scratch`. If a
+    /// previous compilation with the same key is still running, this waits
+    /// for it to exit first.
+    pub(crate) fn start(&self, key: &str, scratch: ScratchOutputs) -> Arc<PipelinedCompilation> {
+        let previous = self.take(key);
+        if let Some(previous) = previous {
+            if !previous.is_finished() {
+                previous.wait_for_completion();
+            }
+        }
+        let compilation = Arc::new(PipelinedCompilation::new(scratch));
+        self.lock().insert(key.to_owned(), Arc::clone(&compilation));
+        compilation
+    }
+
+    /// Removes and returns the compilation for `key`, if any.
+    pub(crate) fn take(&self, key: &str) -> Option<Arc<PipelinedCompilation>> {
+        self.expire(Instant::now());
+        self.lock().remove(key)
+    }
+
+    /// Drops the compilations which weren't claimed in time.
+    fn expire(&self, now: Instant) {
+        self.lock()
+            .retain(|_, compilation| !compilation.is_expired(now));
+    }
+}
+
+/// The outputs of a pipelined rustc process, which are written to a private
+/// scratch directory until a request claims them. The directory is removed
+/// when this is dropped.
+#[derive(Debug)]
+pub(crate) struct ScratchOutputs {
+    dir: PathBuf,
+    // The output directory of the original command line.
+    out_dir: PathBuf,
+}
+
+impl ScratchOutputs {
+    /// Creates a scratch directory for the rustc command line `args` and
+    /// returns the arguments writing to it instead of `--out-dir`. Returns
+    /// `None` if the outputs can't be redirected, i.e. if `args` don't have
+    /// exactly one `--out-dir` or name output files explicitly.
+    pub(crate) fn redirect(args: &[String]) -> io::Result<Option<(Self, Vec<String>)>> {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+        let mut expanded = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('@') {
+                Some(param_file) => {
+                    expanded.extend(fs::read_to_string(param_file)?.lines().map(str::to_owned))
+                }
+                None => expanded.push(arg.clone()),
+            }
+        }
+        let mut out_dirs = Vec::new();
+        let mut iter = expanded.iter().enumerate();
+        while let Some((index, arg)) = iter.next() {
+            if arg == "--out-dir" {
+                out_dirs.push(index + 1);
+                iter.next();
+            } else if arg.starts_with("--out-dir=") {
+                out_dirs.push(index);
+            } else if arg.starts_with("-o") {
+                return Ok(None);
+            } else if let Some(emit) = arg.strip_prefix("--emit") {
+                let emit = match emit.strip_prefix('=') {
+                    Some(emit) => emit,
+                    None => iter.next().map_or("", |(_, emit)| emit.as_str()),
+                };
+                if emit.contains('=') {
+                    return Ok(None);
+                }
+            }
+        }
+        let out_dir_index = match out_dirs[..] {
+            [index] => index,
+            _ => return Ok(None),
+        };
+        let out_dir = match expanded.get(out_dir_index) {
+            Some(arg) => arg.strip_prefix("--out-dir=").unwrap_or(arg).to_owned(),
+            None => return Ok(None),
+        };
+
+        let dir = std::env::temp_dir().join(format!(
+            "process_wrapper_pipelining_{}_{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::create_dir_all(&dir)?;
+        let scratch = Self {
+            dir,
+            out_dir: PathBuf::from(out_dir),
+        };
+        let redirected_out_dir = scratch.dir.join("out");
+        fs::create_dir(&redirected_out_dir)?;
+        expanded[out_dir_index] = if expanded[out_dir_index].starts_with("--out-dir=") {
+            format!("--out-dir={}", redirected_out_dir.display())
+        } else {
+            redirected_out_dir.display().to_string()
+        };
+        let param_file = scratch.dir.join("rustc.params");
+        fs::write(&param_file, expanded.join("\n") + "\n")?;
+        Ok(Some((scratch, vec![format!("@{}", param_file.display())])))
+    }
+
+    fn redirected(&self, output: &Path) -> Option<PathBuf> {
+        Some(self.dir.join("out").join(output.file_name()?))
+    }
+
+    /// Copies the rmeta files written so far to the output directory.
+    pub(crate) fn publish_metadata(&self) -> io::Result<()> {
+        for entry in fs::read_dir(self.dir.join("out"))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "rmeta") {
+                if let Some(file_name) = path.file_name() {
+                    fs::copy(&path, self.out_dir.join(file_name))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Moves the given outputs from the scratch directory in place, ignoring
+    /// outputs that weren't written.
+    pub(crate) fn publish(&self, outputs: &[String]) -> io::Result<()> {
+        for output in outputs {
+            let source = match self.redirected(Path::new(output)) {
+                Some(source) if source.exists() => source,
+                _ => continue,
+            };
+            // The scratch directory may be on another file system.
+            if fs::rename(&source, output).is_err() {
+                fs::copy(&source, output)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ScratchOutputs {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::io::Write;
+    use std::thread;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "process_wrapper_pipelining_test_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn scratch(out_dir: &Path) -> ScratchOutputs {
+        ScratchOutputs::redirect(&[format!("--out-dir={}", out_dir.display())])
+            .unwrap()
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn test_metadata_then_completion() {
+        let compilations = PipelinedCompilations::default();
+        let out_dir = temp_dir("metadata_then_completion");
+        let compilation = compilations.start("//foo:bar", scratch(&out_dir));
+        let background = Arc::clone(&compilation);
+        let rustc = thread::spawn(move || {
+            let (mut output, mut raw) = (background.output(), background.raw_output());
+            raw.write_all(b"{\"emit\":\"metadata\"}\n").unwrap();
+            output.write_all(b"warning: frontend\n").unwrap();
+            background.metadata_emitted();
+            raw.write_all(b"{\"emit\":\"link\"}\n").unwrap();
+            output.write_all(b"warning: codegen\n").unwrap();
+            background.finish(0, None);
+        });
+
+        let metadata = compilation.wait_for_metadata();
+        assert_eq!(metadata.exit_code, 0);
+        assert_eq!(metadata.output, b"warning: frontend\n");
+        rustc.join().unwrap();
+
+        let full = compilations
+            .take("//foo:bar")
+            .unwrap()
+            .wait_for_completion();
+        assert_eq!(
+            full,
+            PipelinedResult {
+                exit_code: 0,
+                output: b"warning: codegen\n".to_vec(),
+                raw_output: b"{\"emit\":\"metadata\"}\n{\"emit\":\"link\"}\n".to_vec(),
+                resources: None,
+            }
+        );
+        assert!(compilations.take("//foo:bar").is_none());
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_failure_before_metadata() {
+        let compilations = PipelinedCompilations::default();
+        let out_dir = temp_dir("failure_before_metadata");
+        let compilation = compilations.start("key", scratch(&out_dir));
+        compilation.output().write_all(b"error: oops\n").unwrap();
+        compilation.finish(1, None);
+
+        let metadata = compilation.wait_for_metadata();
+        assert_eq!(metadata.exit_code, 1);
+        assert_eq!(metadata.output, b"error: oops\n");
+
+        // The full request reports the same failure.
+        let full = compilations.take("key").unwrap().wait_for_completion();
+        assert_eq!(full.exit_code, 1);
+        assert_eq!(full.output, b"error: oops\n");
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_restart_waits_for_running_compilation() {
+        let compilations = PipelinedCompilations::default();
+        let out_dir = temp_dir("restart");
+        let first = compilations.start("key", scratch(&out_dir));
+        let background = Arc::clone(&first);
+        let rustc = thread::spawn(move || background.finish(0, None));
+        let second = compilations.start("key", scratch(&out_dir));
+        rustc.join().unwrap();
+        assert!(first.is_finished());
+        assert!(!second.is_finished());
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_unclaimed_compilations_expire() {
+        let compilations = PipelinedCompilations::default();
+        let out_dir = temp_dir("expire");
+        let running = compilations.start("running", scratch(&out_dir));
+        let finished = compilations.start("finished", scratch(&out_dir));
+        finished.finish(0, None);
+        let scratch_dir = finished.scratch().dir.clone();
+        drop((running, finished));
+
+        compilations.expire(Instant::now());
+        assert_eq!(compilations.lock().len(), 2);
+        compilations.expire(Instant::now() + UNCLAIMED_TIMEOUT);
+        assert!(compilations.take("finished").is_none());
+        assert!(!scratch_dir.exists());
+        assert!(compilations.take("running").is_some());
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn test_redirect_outputs() {
+        let dir = temp_dir("redirect");
+        let param_file = dir.join("rustc.params");
+        fs::write(
+            &param_file,
+            "--out-dir\nbazel-out/bin\n--emit=dep-info,metadata,link\n",
+        )
+        .unwrap();
+        let args = vec!["lib.rs".to_owned(), format!("@{}", param_file.display())];
+        let (scratch, redirected) = ScratchOutputs::redirect(&args).unwrap().unwrap();
+        assert_eq!(scratch.out_dir, Path::new("bazel-out/bin"));
+        let redirected_params = redirected[0].strip_prefix('@').unwrap();
+        assert_eq!(
+            fs::read_to_string(redirected_params).unwrap(),
+            format!(
+                "lib.rs\n--out-dir\n{}\n--emit=dep-info,metadata,link\n",
+                scratch.dir.join("out").display()
+            )
+        );
+
+        // Outputs with explicit paths or without an output directory can't be
+        // redirected.
+        for args in [
+            &["--out-dir=out", "-o", "foo"][..],
+            &["--out-dir=out", "--emit=link=foo"],
+            &["--out-dir=out", "--emit", "link=foo"],
+            &["--emit=link"],
+        ]
+        .iter()
+        {
+            let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+            assert!(ScratchOutputs::redirect(&args).unwrap().is_none());
+        }
+        drop(scratch);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_publish_outputs() {
+        let out_dir = temp_dir("publish");
+        let scratch = scratch(&out_dir);
+        let redirected = scratch.dir.join("out");
+        fs::write(redirected.join("libfoo.rmeta"), "rmeta").unwrap();
+        fs::write(redirected.join("libfoo.rlib"), "rlib").unwrap();
+
+        scratch.publish_metadata().unwrap();
+        assert_eq!(
+            fs::read_to_string(out_dir.join("libfoo.rmeta")).unwrap(),
+            "rmeta"
+        );
+        assert!(!out_dir.join("libfoo.rlib").exists());
+
+        let rlib = out_dir.join("libfoo.rlib").display().to_string();
+        let missing = out_dir.join("libfoo.d").display().to_string();
+        scratch.publish(&[rlib.clone(), missing.clone()]).unwrap();
+        assert_eq!(fs::read_to_string(&rlib).unwrap(), "rlib");
+        assert!(!Path::new(&missing).exists());
+
+        let scratch_dir = scratch.dir.clone();
+        drop(scratch);
+        assert!(!scratch_dir.exists());
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright 2026 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Warning and lint policies applied to rustc diagnostics.
+//!
+//! A policy file is a json object with a list of rules, for example:
+//!
+//! ```json
+//! {
+//!     "rules": [
+//!         {"origin": "first_party", "max_warnings": 0},
+//!         {"crates": ["legacy_*"], "max_warnings": 25},
+//!         {"origin": "first_party", "lints": ["clippy::dbg_macro"], "level": "deny"},
+//!         {"origin": "external", "lints": ["*"], "level": "allow"}
+//!     ]
+//! }
+//! ```
+//!
+//! A rule applies to a crate if it matches all of its `origin` (`first_party`
+//! or `external`) and `crates` selectors. Rules with `lints` set the level of
+//! matching warnings: `allow` suppresses them, `deny` promotes them to errors
+//! and `warn` keeps them as warnings. Rules with `max_warnings` fail the
+//! compilation if the crate emits more warnings than allowed. For each
+//! warning, the last matching rule wins. The "N warnings emitted" summary of
+//! rustc is forwarded unchanged and still counts suppressed warnings.
+
+use std::collections::HashMap;
+use std::fs;
+
+use tinyjson::JsonValue;
+
+use crate::diagnostics::Diagnostic;
+use crate::rustc::RustcArgs;
+
+/// Where the sources of a crate come from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum CrateOrigin {
+    FirstParty,
+    External,
+}
+
+impl CrateOrigin {
+    /// Determines the origin of a crate from the path of its crate root.
+    pub(crate) fn from_crate_root(crate_root: Option<&str>) -> Self {
+        let Some(mut path) = crate_root else {
+            return Self::FirstParty;
+        };
+        path = path.trim_start_matches("./");
+        // Generated crate roots live in `bazel-out/<configuration>/bin`.
+        if let Some(generated) = path.strip_prefix("bazel-out/") {
+            path = generated.splitn(3, '/').nth(2).unwrap_or(generated);
+        }
+        if path.starts_with("external/") || path.starts_with("../") {
+            Self::External
+        } else {
+            Self::FirstParty
+        }
+    }
+}
+
+/// The level a lint policy assigns to a warning.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct LintRule {
+    origin: Option<CrateOrigin>,
+    crates: Vec<String>,
+    lints: Vec<String>,
+    level: Option<LintLevel>,
+    max_warnings: Option<usize>,
+}
+
+/// Matches `value` against a pattern which may contain a single `*`.
+fn matches_pattern(pattern: &str, value: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            value.len() >= prefix.len() + suffix.len()
+                && value.starts_with(prefix)
+                && value.ends_with(suffix)
+        }
+        None => pattern == value,
+    }
+}
+
+fn string_list(value: &JsonValue, key: &str) -> Result<Vec<String>, String> {
+    let JsonValue::Array(values) = value else {
+        return Err(format!("\"{key}\" must be a list of strings"));
+    };
+    values
+        .iter()
+        .map(|v| match v {
+            JsonValue::String(s) => Ok(s.clone()),
+            _ => Err(format!("\"{key}\" must be a list of strings")),
+        })
+        .collect()
+}
+
+impl LintRule {
+    fn from_json(value: &JsonValue) -> Result<Self, String> {
+        let JsonValue::Object(map) = value else {
+            return Err("rule must be an object".to_owned());
+        };
+        let mut rule = Self::default();
+        for (key, value) in map {
+            match (key.as_str(), value) {
+                ("origin", JsonValue::String(origin)) => {
+                    rule.origin = Some(match origin.as_str() {
+                        "first_party" => CrateOrigin::FirstParty,
+                        "external" => CrateOrigin::External,
+                        _ => return Err(format!("unknown origin \"{origin}\"")),
+                    })
+                }
+                ("crates", value) => rule.crates = string_list(value, key)?,
+                ("lints", value) => rule.lints = string_list(value, key)?,
+                ("level", JsonValue::String(level)) => {
+                    rule.level = Some(match level.as_str() {
+                        "allow" => LintLevel::Allow,
+                        "warn" => LintLevel::Warn,
+                        "deny" => LintLevel::Deny,
+                        _ => return Err(format!("unknown level \"{level}\"")),
+                    })
+                }
+                ("max_warnings", JsonValue::Number(max)) if *max >= 0.0 && max.fract() == 0.0 => {
+                    rule.max_warnings = Some(*max as usize)
+                }
+                _ => return Err(format!("invalid key \"{key}\"")),
+            }
+        }
+        if rule.lints.is_empty() != rule.level.is_none() {
+            return Err("\"lints\" and \"level\" must be set together".to_owned());
+        }
+        if rule.level.is_none() && rule.max_warnings.is_none() {
+            return Err("rule must set either \"level\" or \"max_warnings\"".to_owned());
+        }
+        Ok(rule)
+    }
+
+    fn applies_to(&self, crate_name: Option<&str>, origin: CrateOrigin) -> bool {
+        self.origin.unwrap_or(origin) == origin
+            && (self.crates.is_empty()
+                || crate_name
+                    .is_some_and(|name| self.crates.iter().any(|p| matches_pattern(p, name))))
+    }
+
+    fn level_for(&self, code: &str) -> Option<LintLevel> {
+        self.level
+            .filter(|_| self.lints.iter().any(|p| matches_pattern(p, code)))
+    }
+}
+
+/// A warning and lint policy, see the module documentation for the format.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct LintPolicy {
+    rules: Vec<LintRule>,
+}
+
+impl LintPolicy {
+    pub(crate) fn parse(content: &str) -> Result<Self, String> {
+        let value: JsonValue = content.parse().map_err(|e| format!("{e}"))?;
+        let rules = match &value {
+            JsonValue::Object(map) if map.len() == 1 => map.get("rules"),
+            _ => None,
+        };
+        let Some(JsonValue::Array(rules)) = rules else {
+            return Err("expected an object with a list of \"rules\"".to_owned());
+        };
+        let rules = rules
+            .iter()
+            .enumerate()
+            .map(|(index, rule)| {
+                LintRule::from_json(rule).map_err(|e| format!("rule {}: {}", index + 1, e))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { rules })
+    }
+
+    pub(crate) fn read(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{e}"))?;
+        Self::parse(&content)
+    }
+}
+
+/// Applies a lint policy to the diagnostics of a single crate.
+#[derive(Debug)]
+pub(crate) struct LintPolicyEnforcer<'a> {
+    crate_name: String,
+    rules: Vec<&'a LintRule>,
+    max_warnings: Option<usize>,
+    warnings: usize,
+    denied: usize,
+}
+
+impl<'a> LintPolicyEnforcer<'a> {
+    pub(crate) fn new(policy: &'a LintPolicy, args: &RustcArgs) -> Self {
+        let origin = CrateOrigin::from_crate_root(args.crate_root.as_deref());
+        let rules: Vec<&LintRule> = policy
+            .rules
+            .iter()
+            .filter(|r| r.applies_to(args.crate_name.as_deref(), origin))
+            .collect();
+        Self {
+            crate_name: args
+                .crate_name
+                .clone()
+                .unwrap_or_else(|| "this crate".to_owned()),
+            max_warnings: rules.iter().rev().find_map(|r| r.max_warnings),
+            rules,
+            warnings: 0,
+            denied: 0,
+        }
+    }
+
+    /// Applies the policy to a line of rustc json output. Returns `None` if
+    /// the line should be suppressed and the (possibly rewritten) line
+    /// otherwise.
+    pub(crate) fn apply(&mut self, line: String) -> Option<String> {
+        let Ok(JsonValue::Object(mut map)) = line.parse::<JsonValue>() else {
+            return Some(line);
+        };
+        let diagnostic = match Diagnostic::from_json(&JsonValue::Object(map.clone())) {
+            Some(diagnostic) if diagnostic.level == "warning" && !diagnostic.is_summary() => {
+                diagnostic
+            }
+            _ => return Some(line),
+        };
+        let code = diagnostic.code.as_deref().unwrap_or_default();
+        match self.rules.iter().rev().find_map(|r| r.level_for(code)) {
+            Some(LintLevel::Allow) => None,
+            Some(LintLevel::Deny) => {
+                self.denied += 1;
+                Some(promote_to_error(&mut map).unwrap_or(line))
+            }
+            Some(LintLevel::Warn) | None => {
+                self.warnings += 1;
+                Some(line)
+            }
+        }
+    }
+
+    /// Returns the policy violations to report once rustc exited.
+    pub(crate) fn violations(&self) -> Vec<String> {
+        let mut violations = Vec::new();
+        if self.denied > 0 {
+            violations.push(format!(
+                "{} emitted {} warning(s) denied by the lint policy",
+                self.crate_name, self.denied
+            ));
+        }
+        match self.max_warnings {
+            Some(max_warnings) if self.warnings > max_warnings => violations.push(format!(
+                "{} emitted {} warning(s), more than the {} allowed by the lint policy",
+                self.crate_name, self.warnings, max_warnings
+            )),
+            _ => {}
+        }
+        violations
+    }
+}
+
+/// Rewrites a warning into an error, returning the new json line.
+fn promote_to_error(map: &mut HashMap<String, JsonValue>) -> Option<String> {
+    map.insert("level".to_owned(), JsonValue::String("error".to_owned()));
+    if let Some(JsonValue::String(rendered)) = map.get_mut("rendered") {
+        *rendered = rendered.replacen("warning", "error", 1);
+    }
+    let mut line = JsonValue::Object(map.clone()).stringify().ok()?;
+    line.push('\n');
+    Some(line)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn warning(code: &str) -> String {
+        format!(
+            r#"{{"$message_type":"diagnostic","message":"{code}","code":{{"code":"{code}","explanation":null}},"level":"warning","spans":[],"children":[],"rendered":"warning: {code}\n"}}"#
+        )
+    }
+
+    const SUMMARY: &str = r#"{"$message_type":"diagnostic","message":"2 warnings emitted","code":null,"level":"warning","spans":[],"children":[],"rendered":"warning: 2 warnings emitted\n\n"}"#;
+
+    fn rustc_args(crate_name: &str, crate_root: &str) -> RustcArgs {
+        RustcArgs {
+            crate_name: Some(crate_name.to_owned()),
+            crate_root: Some(crate_root.to_owned()),
+            ..RustcArgs::default()
+        }
+    }
+
+    #[test]
+    fn test_crate_origin() {
+        assert_eq!(
+            CrateOrigin::from_crate_root(Some("src/lib.rs")),
+            CrateOrigin::FirstParty
+        );
+        assert_eq!(
+            CrateOrigin::from_crate_root(Some("external/crates__serde-1.0.0/src/lib.rs")),
+            CrateOrigin::External
+        );
+        assert_eq!(
+            CrateOrigin::from_crate_root(Some("bazel-out/k8-fastbuild/bin/external/repo/lib.rs")),
+            CrateOrigin::External
+        );
+        assert_eq!(
+            CrateOrigin::from_crate_root(Some("bazel-out/k8-fastbuild/bin/pkg/lib.rs")),
+            CrateOrigin::FirstParty
+        );
+        assert_eq!(
+            CrateOrigin::from_crate_root(Some("../repo/lib.rs")),
+            CrateOrigin::External
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(LintPolicy::parse("[]").is_err());
+        assert_eq!(
+            LintPolicy::parse(r#"{"rules": [{"lints": ["*"]}]}"#),
+            Err("rule 1: \"lints\" and \"level\" must be set together".to_owned())
+        );
+        assert_eq!(
+            LintPolicy::parse(r#"{"rules": [{"origin": "first_party", "max_warning": 1}]}"#),
+            Err("rule 1: invalid key \"max_warning\"".to_owned())
+        );
+        assert_eq!(
+            LintPolicy::parse(r#"{"rules": [{"origin": "vendored", "max_warnings": 1}]}"#),
+            Err("rule 1: unknown origin \"vendored\"".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_levels() {
+        let policy = LintPolicy::parse(
+            r#"{"rules": [
+                {"lints": ["clippy::*"], "level": "deny"},
+                {"lints": ["clippy::needless_return"], "level": "warn"},
+                {"origin": "external", "lints": ["*"], "level": "allow"}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut first_party = LintPolicyEnforcer::new(&policy, &rustc_args("foo", "foo/lib.rs"));
+        let denied = first_party.apply(warning("clippy::dbg_macro")).unwrap();
+        let denied: JsonValue = denied.parse().unwrap();
+        assert_eq!(denied["level"], JsonValue::String("error".to_owned()));
+        assert_eq!(
+            denied["rendered"],
+            JsonValue::String("error: clippy::dbg_macro\n".to_owned())
+        );
+        assert_eq!(
+            first_party.apply(warning("clippy::needless_return")),
+            Some(warning("clippy::needless_return"))
+        );
+        assert_eq!(
+            first_party.apply(warning("unused_variables")),
+            Some(warning("unused_variables"))
+        );
+        assert_eq!(
+            first_party.violations(),
+            vec!["foo emitted 1 warning(s) denied by the lint policy".to_owned()]
+        );
+
+        let mut external =
+            LintPolicyEnforcer::new(&policy, &rustc_args("serde", "external/serde/lib.rs"));
+        assert_eq!(external.apply(warning("clippy::dbg_macro")), None);
+        assert_eq!(external.apply(warning("unused_variables")), None);
+        assert!(external.violations().is_empty());
+    }
+
+    #[test]
+    fn test_max_warnings() {
+        let policy = LintPolicy::parse(
+            r#"{"rules": [
+                {"origin": "first_party", "max_warnings": 0},
+                {"crates": ["legacy_*"], "max_warnings": 2}
+            ]}"#,
+        )
+        .unwrap();
+
+        let mut legacy = LintPolicyEnforcer::new(&policy, &rustc_args("legacy_foo", "lib.rs"));
+        legacy.apply(warning("unused_variables"));
+        legacy.apply(warning("dead_code"));
+        // Neither the summary nor other messages count as warnings.
+        legacy.apply(SUMMARY.to_owned());
+        legacy.apply(r#"{"$message_type":"artifact","emit":"metadata"}"#.to_owned());
+        assert!(legacy.violations().is_empty());
+        legacy.apply(warning("dead_code"));
+        assert_eq!(
+            legacy.violations(),
+            vec![
+                "legacy_foo emitted 3 warning(s), more than the 2 allowed by the lint policy"
+                    .to_owned()
+            ]
+        );
+
+        let mut bar = LintPolicyEnforcer::new(&policy, &rustc_args("bar", "lib.rs"));
+        bar.apply(warning("dead_code"));
+        assert_eq!(bar.violations().len(), 1);
+
+        let mut external = LintPolicyEnforcer::new(&policy, &rustc_args("bar", "external/lib.rs"));
+        external.apply(warning("dead_code"));
+        assert!(external.violations().is_empty());
+    }
+}
@@ -0,0 +1,298 @@
+// Copyright 2026 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Remapping of the file paths in rustc diagnostics.
+//!
+//! rustc reports paths as it sees them in the exec root, which may be a
+//! sandbox. This rewrites the `file_name` of every span and the paths in the
+//! rendered text so that they point to workspace sources, or to
+//! `external/<repo>` for the sources of external repositories.
+
+use std::fs;
+use std::path::Path;
+
+use tinyjson::JsonValue;
+
+/// Rewrites the paths in rustc's json diagnostics.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct PathRemapper {
+    // Explicit (from, to) prefix mappings, the last matching one is applied.
+    prefixes: Vec<(String, String)>,
+    // If set, also strips exec root and sandbox prefixes and maps sources
+    // symlinked into the output tree back to the source tree.
+    normalize: bool,
+}
+
+/// Strips the absolute path of the exec root, which contains an `execroot`
+/// directory followed by the name of the main repository.
+fn strip_exec_root(path: &str) -> Option<&str> {
+    if !Path::new(path).is_absolute() {
+        return None;
+    }
+    let (_, rest) = path.rsplit_once("/execroot/")?;
+    rest.split_once('/').map(|(_, relative)| relative)
+}
+
+/// Maps `bazel-out/<configuration>/bin/<path>` to `<path>` if it is a symlink
+/// to a source file, which rules_rust creates for crates mixing generated and
+/// regular sources.
+fn strip_output_tree(path: &str) -> Option<&str> {
+    let rest = path.strip_prefix("bazel-out/")?;
+    let (_, rest) = rest.split_once('/')?;
+    let source = rest.strip_prefix("bin/")?;
+    let is_symlink = fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink());
+    (is_symlink && Path::new(source).is_file()).then_some(source)
+}
+
+/// Strips the ANSI color escapes at the end of `text`, which rustc emits with
+/// `--json=diagnostic-rendered-ansi`.
+fn strip_trailing_ansi(mut text: &str) -> &str {
+    while let Some(index) = text.rfind("\u{1b}[") {
+        let escape = &text[index + 2..];
+        let is_sgr = escape
+            .strip_suffix('m')
+            .is_some_and(|codes| codes.chars().all(|c| c.is_ascii_digit() || c == ';'));
+        if !is_sgr {
+            break;
+        }
+        text = &text[..index];
+    }
+    text
+}
+
+/// Replaces the occurrences of `from` in `text` which aren't part of a longer
+/// path.
+fn replace_path(text: &str, from: &str, to: &str) -> String {
+    let is_path_char = |c: char| c.is_alphanumeric() || matches!(c, '/' | '\\' | '.' | '_' | '-');
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find(from) {
+        let (before, after) = (&rest[..index], &rest[index + from.len()..]);
+        result.push_str(before);
+        let preceded = strip_trailing_ansi(&result)
+            .chars()
+            .next_back()
+            .is_some_and(is_path_char);
+        let followed = after.chars().next().is_some_and(is_path_char);
+        result.push_str(if preceded || followed { from } else { to });
+        rest = after;
+    }
+    result.push_str(rest);
+    result
+}
+
+impl PathRemapper {
+    /// Creates a remapper from `FROM=TO` prefix mappings. `FROM` may contain
+    /// `=` as the mapping is split on the last one.
+    pub(crate) fn new(prefixes: &[String], normalize: bool) -> Result<Self, String> {
+        let prefixes = prefixes
+            .iter()
+            .map(|mapping| {
+                mapping
+                    .rsplit_once('=')
+                    .map(|(from, to)| (from.to_owned(), to.to_owned()))
+                    .ok_or_else(|| format!("expected FROM=TO, got \"{mapping}\""))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            prefixes,
+            normalize,
+        })
+    }
+
+    /// Returns the remapped `path`, or `None` if it doesn't change.
+    pub(crate) fn remap_path(&self, path: &str) -> Option<String> {
+        let mut remapped = match self
+            .prefixes
+            .iter()
+            .rev()
+            .find_map(|(from, to)| Some((path.strip_prefix(from.as_str())?, to)))
+        {
+            Some((rest, to)) => format!("{to}{rest}"),
+            None => path.to_owned(),
+        };
+        if self.normalize {
+            if let Some(relative) = strip_exec_root(&remapped) {
+                remapped = relative.to_owned();
+            }
+            while let Some(relative) = remapped.strip_prefix("./") {
+                remapped = relative.to_owned();
+            }
+            if let Some(source) = strip_output_tree(&remapped) {
+                remapped = source.to_owned();
+            }
+        }
+        (remapped != path).then_some(remapped)
+    }
+
+    // Remaps all `file_name` fields within `value`, recording the changes.
+    fn remap_value(&self, value: &mut JsonValue, changes: &mut Vec<(String, String)>) {
+        match value {
+            JsonValue::Object(map) => {
+                for (key, value) in map.iter_mut() {
+                    match value {
+                        JsonValue::String(path) if key == "file_name" => {
+                            if let Some(remapped) = self.remap_path(path) {
+                                let original = std::mem::replace(path, remapped.clone());
+                                if !changes.iter().any(|(from, _)| *from == original) {
+                                    changes.push((original, remapped));
+                                }
+                            }
+                        }
+                        _ => self.remap_value(value, changes),
+                    }
+                }
+            }
+            JsonValue::Array(values) => {
+                for value in values {
+                    self.remap_value(value, changes);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Remaps the paths of a line of rustc json output. Lines which aren't
+    /// diagnostics are returned unchanged.
+    pub(crate) fn remap_line(&self, line: String) -> String {
+        let Ok(mut value) = line.parse::<JsonValue>() else {
+            return line;
+        };
+        let is_diagnostic = match &value {
+            JsonValue::Object(map) => matches!(
+                map.get("$message_type"),
+                Some(JsonValue::String(message_type)) if message_type == "diagnostic"
+            ),
+            _ => false,
+        };
+        if !is_diagnostic {
+            return line;
+        }
+        let mut changes = Vec::new();
+        self.remap_value(&mut value, &mut changes);
+        if changes.is_empty() {
+            return line;
+        }
+        // Replace longer paths first in case one is a prefix of another.
+        changes.sort_by_key(|(from, _)| std::cmp::Reverse(from.len()));
+        if let JsonValue::Object(map) = &mut value {
+            if let Some(JsonValue::String(rendered)) = map.get_mut("rendered") {
+                for (from, to) in &changes {
+                    *rendered = replace_path(rendered, from, to);
+                }
+            }
+        }
+        match value.stringify() {
+            Ok(mut remapped) => {
+                if line.ends_with('\n') {
+                    remapped.push('\n');
+                }
+                remapped
+            }
+            Err(_) => line,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_remap_path() {
+        let remapper = PathRemapper::new(
+            &[
+                "/exec/root/=".to_owned(),
+                "/exec/root/external/=/output_base/external/".to_owned(),
+            ],
+            false,
+        )
+        .unwrap();
+        assert_eq!(
+            remapper.remap_path("/exec/root/pkg/lib.rs").as_deref(),
+            Some("pkg/lib.rs")
+        );
+        // The last matching mapping wins.
+        assert_eq!(
+            remapper
+                .remap_path("/exec/root/external/repo/lib.rs")
+                .as_deref(),
+            Some("/output_base/external/repo/lib.rs")
+        );
+        assert_eq!(remapper.remap_path("pkg/lib.rs"), None);
+        assert!(PathRemapper::new(&["no mapping".to_owned()], false).is_err());
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let remapper = PathRemapper::new(&[], true).unwrap();
+        assert_eq!(
+            remapper
+                .remap_path("/home/user/.cache/bazel/_bazel_user/abc/sandbox/linux-sandbox/4/execroot/_main/external/repo/src/lib.rs")
+                .as_deref(),
+            Some("external/repo/src/lib.rs")
+        );
+        assert_eq!(
+            remapper.remap_path("./pkg/lib.rs").as_deref(),
+            Some("pkg/lib.rs")
+        );
+        // Generated files are left in the output tree.
+        assert_eq!(
+            remapper.remap_path("bazel-out/k8-fastbuild/bin/pkg/generated.rs"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_remap_line() {
+        let remapper = PathRemapper::new(&["/sandbox/execroot/_main/=".to_owned()], false).unwrap();
+        let line = r#"{"$message_type":"diagnostic","message":"unused variable: `x`","code":{"code":"unused_variables","explanation":null},"level":"warning","spans":[{"file_name":"/sandbox/execroot/_main/pkg/lib.rs","byte_start":20,"byte_end":21,"line_start":2,"line_end":2,"column_start":9,"column_end":10,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[{"message":"macro defined here","code":null,"level":"note","spans":[{"file_name":"/sandbox/execroot/_main/pkg/macros.rs","byte_start":0,"byte_end":1,"line_start":1,"line_end":1,"column_start":1,"column_end":2,"is_primary":true,"text":[],"label":null,"suggested_replacement":null,"suggestion_applicability":null,"expansion":null}],"children":[],"rendered":null}],"rendered":"warning: unused variable: `x`\n --> /sandbox/execroot/_main/pkg/lib.rs:2:9\n  |\n  ::: /sandbox/execroot/_main/pkg/macros.rs:1:1\n"}"#;
+        let remapped: JsonValue = remapper.remap_line(line.to_owned()).parse().unwrap();
+        assert_eq!(
+            remapped["spans"][0]["file_name"],
+            JsonValue::String("pkg/lib.rs".to_owned())
+        );
+        assert_eq!(
+            remapped["children"][0]["spans"][0]["file_name"],
+            JsonValue::String("pkg/macros.rs".to_owned())
+        );
+        assert_eq!(
+            remapped["rendered"],
+            JsonValue::String(
+                "warning: unused variable: `x`\n --> pkg/lib.rs:2:9\n  |\n  ::: pkg/macros.rs:1:1\n"
+                    .to_owned()
+            )
+        );
+
+        let artifact = r#"{"$message_type":"artifact","artifact":"/sandbox/execroot/_main/libfoo.rmeta","emit":"metadata"}"#;
+        assert_eq!(remapper.remap_line(artifact.to_owned()), artifact);
+    }
+
+    #[test]
+    fn test_replace_path() {
+        assert_eq!(
+            replace_path(
+                " --> a/lib.rs:1:1 and xa/lib.rs:2:2",
+                "a/lib.rs",
+                "b/lib.rs"
+            ),
+            " --> b/lib.rs:1:1 and xa/lib.rs:2:2"
+        );
+        // Paths preceded by ANSI escapes are still replaced.
+        assert_eq!(
+            replace_path("\u{1b}[0ma/lib.rs:1:1", "a/lib.rs", "b.rs"),
+            "\u{1b}[0mb.rs:1:1"
+        );
+    }
+}
@@ -0,0 +1,421 @@
+// Copyright 2026 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Resource usage and timing reports of the child process.
+//!
+//! A report records the wall time of the child and, where `wait4` is
+//! available, its user and system CPU time and peak resident set size. If
+//! rustc runs with `--json=timings` or `-Z time-passes`, the reported
+//! sections and passes are included as well. Reports of a build can be
+//! merged into a Chrome trace with `//tools/build_profile`.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::{Child, ExitStatus};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tinyjson::JsonValue;
+
+use crate::json::write_sorted;
+use crate::rustc::RustcArgs;
+
+/// The resources used by a child process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ResourceUsage {
+    pub(crate) user_time: Duration,
+    pub(crate) system_time: Duration,
+    pub(crate) max_rss_bytes: u64,
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+mod sys {
+    use std::os::raw::{c_int, c_long};
+
+    #[cfg(target_os = "macos")]
+    type SuSeconds = i32;
+    #[cfg(not(target_os = "macos"))]
+    type SuSeconds = c_long;
+
+    #[repr(C)]
+    pub(super) struct Timeval {
+        pub(super) tv_sec: c_long,
+        pub(super) tv_usec: SuSeconds,
+    }
+
+    #[repr(C)]
+    pub(super) struct Rusage {
+        pub(super) ru_utime: Timeval,
+        pub(super) ru_stime: Timeval,
+        pub(super) ru_maxrss: c_long,
+        // The remaining fields aren't used.
+        _ru_rest: [c_long; 13],
+    }
+
+    extern "C" {
+        pub(super) fn wait4(
+            pid: c_int,
+            status: *mut c_int,
+            options: c_int,
+            rusage: *mut Rusage,
+        ) -> c_int;
+    }
+
+    impl Timeval {
+        pub(super) fn to_duration(&self) -> std::time::Duration {
+            std::time::Duration::from_secs(self.tv_sec.max(0) as u64)
+                + std::time::Duration::from_micros(self.tv_usec.max(0) as u64)
+        }
+    }
+
+    // ru_maxrss is in kilobytes on Linux and in bytes on macOS.
+    #[cfg(target_os = "macos")]
+    pub(super) const MAX_RSS_UNIT: u64 = 1;
+    #[cfg(not(target_os = "macos"))]
+    pub(super) const MAX_RSS_UNIT: u64 = 1024;
+}
+
+/// Waits for `child` to exit and returns its exit status together with its
+/// resource usage, if the platform reports it.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(crate) fn wait_with_usage(
+    child: &mut Child,
+) -> io::Result<(ExitStatus, Option<ResourceUsage>)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    loop {
+        let mut status = 0;
+        // SAFETY: `Rusage` only consists of integers, for which all zeroes is a
+        // valid value.
+        let mut usage: sys::Rusage = unsafe { std::mem::zeroed() };
+        // SAFETY: both pointers are valid for writes for the duration of the
+        // call. `Child` doesn't reap the process on its own, so the pid can't
+        // have been reused.
+        let pid = unsafe { sys::wait4(child.id() as i32, &mut status, 0, &mut usage) };
+        if pid == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err);
+        }
+        return Ok((
+            ExitStatus::from_raw(status),
+            Some(ResourceUsage {
+                user_time: usage.ru_utime.to_duration(),
+                system_time: usage.ru_stime.to_duration(),
+                max_rss_bytes: usage.ru_maxrss.max(0) as u64 * sys::MAX_RSS_UNIT,
+            }),
+        ));
+    }
+}
+
+/// Waits for `child` to exit and returns its exit status together with its
+/// resource usage, if the platform reports it.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub(crate) fn wait_with_usage(
+    child: &mut Child,
+) -> io::Result<(ExitStatus, Option<ResourceUsage>)> {
+    child.wait().map(|status| (status, None))
+}
+
+/// A section of the compilation reported by rustc's `--json=timings`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Section {
+    pub(crate) name: String,
+    // Microseconds since rustc started.
+    pub(crate) start_us: u64,
+    pub(crate) end_us: u64,
+}
+
+/// Resource usage and timings of a single child process.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResourceReport {
+    executable: String,
+    crate_name: Option<String>,
+    crate_types: Vec<String>,
+    emit: Option<String>,
+    start_time: SystemTime,
+    started: Instant,
+    wall_time: Duration,
+    exit_code: Option<i32>,
+    usage: Option<ResourceUsage>,
+    sections: Vec<Section>,
+    // Sections which were started but didn't end yet.
+    open_sections: HashMap<String, u64>,
+    // The passes reported by `-Z time-passes`, with their duration in seconds.
+    passes: Vec<(String, f64)>,
+}
+
+impl ResourceReport {
+    /// Starts the report of a child process that is about to be spawned.
+    pub(crate) fn start(executable: &str, args: &[String]) -> Self {
+        let args = RustcArgs::from_args(args);
+        Self {
+            executable: Path::new(executable).file_stem().map_or_else(
+                || executable.to_owned(),
+                |s| s.to_string_lossy().into_owned(),
+            ),
+            crate_name: args.crate_name,
+            crate_types: args.crate_types,
+            emit: args.emit,
+            start_time: SystemTime::now(),
+            started: Instant::now(),
+            wall_time: Duration::default(),
+            exit_code: None,
+            usage: None,
+            sections: Vec::new(),
+            open_sections: HashMap::new(),
+            passes: Vec::new(),
+        }
+    }
+
+    /// Records the timings reported in a line of the child's stderr.
+    pub(crate) fn collect_line(&mut self, line: &str) {
+        let line = line.trim_end();
+        if let Some(pass) = line.strip_prefix("time:") {
+            // -Z time-passes prints lines like
+            // `time:   0.012; rss:   35MB ->   38MB (   +3MB)\tmacro_expand_crate`.
+            let seconds = pass.split(';').next().and_then(|s| s.trim().parse().ok());
+            let name = pass.rsplit(['\t', ')']).next();
+            if let (Some(seconds), Some(name)) = (seconds, name) {
+                self.passes.push((name.trim().to_owned(), seconds));
+            }
+            return;
+        }
+        if !line.starts_with('{') {
+            return;
+        }
+        let Ok(JsonValue::Object(map)) = line.parse::<JsonValue>() else {
+            return;
+        };
+        if !matches!(map.get("$message_type"), Some(JsonValue::String(t)) if t == "section_timing")
+        {
+            return;
+        }
+        let (
+            Some(JsonValue::String(name)),
+            Some(JsonValue::String(event)),
+            Some(JsonValue::Number(timestamp)),
+        ) = (map.get("name"), map.get("event"), map.get("timestamp"))
+        else {
+            return;
+        };
+        let timestamp = timestamp.max(0.0) as u64;
+        match event.as_str() {
+            "start" => {
+                self.open_sections.insert(name.clone(), timestamp);
+            }
+            "end" => {
+                if let Some(start_us) = self.open_sections.remove(name) {
+                    self.sections.push(Section {
+                        name: name.clone(),
+                        start_us,
+                        end_us: timestamp,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Records all timings in the raw output of a child process.
+    pub(crate) fn collect_raw_output(&mut self, raw_output: &[u8]) {
+        for line in String::from_utf8_lossy(raw_output).lines() {
+            self.collect_line(line);
+        }
+    }
+
+    /// Completes the report once the child exited, or once the request was
+    /// answered for a child which keeps running in the background.
+    pub(crate) fn finish(&mut self, exit_code: i32, usage: Option<ResourceUsage>) {
+        self.wall_time = self.started.elapsed();
+        self.exit_code = Some(exit_code);
+        self.usage = usage;
+    }
+
+    fn to_json(&self) -> JsonValue {
+        fn string(s: &str) -> JsonValue {
+            JsonValue::String(s.to_owned())
+        }
+        fn micros(d: Duration) -> JsonValue {
+            JsonValue::Number(d.as_micros() as f64)
+        }
+        let start_time = self
+            .start_time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let usage =
+            |f: fn(&ResourceUsage) -> JsonValue| self.usage.as_ref().map_or(JsonValue::Null, f);
+        JsonValue::Object(HashMap::from([
+            ("executable".to_owned(), string(&self.executable)),
+            (
+                "crate_name".to_owned(),
+                self.crate_name.as_deref().map_or(JsonValue::Null, string),
+            ),
+            (
+                "crate_types".to_owned(),
+                JsonValue::Array(self.crate_types.iter().map(|t| string(t)).collect()),
+            ),
+            (
+                "emit".to_owned(),
+                self.emit.as_deref().map_or(JsonValue::Null, string),
+            ),
+            ("start_time_us".to_owned(), micros(start_time)),
+            ("wall_time_us".to_owned(), micros(self.wall_time)),
+            (
+                "exit_code".to_owned(),
+                self.exit_code
+                    .map_or(JsonValue::Null, |c| JsonValue::Number(c as f64)),
+            ),
+            ("user_time_us".to_owned(), usage(|u| micros(u.user_time))),
+            (
+                "system_time_us".to_owned(),
+                usage(|u| micros(u.system_time)),
+            ),
+            (
+                "max_rss_bytes".to_owned(),
+                usage(|u| JsonValue::Number(u.max_rss_bytes as f64)),
+            ),
+            (
+                "sections".to_owned(),
+                JsonValue::Array(
+                    self.sections
+                        .iter()
+                        .map(|s| {
+                            JsonValue::Object(HashMap::from([
+                                ("name".to_owned(), string(&s.name)),
+                                ("start_us".to_owned(), JsonValue::Number(s.start_us as f64)),
+                                ("end_us".to_owned(), JsonValue::Number(s.end_us as f64)),
+                            ]))
+                        })
+                        .collect(),
+                ),
+            ),
+            (
+                "passes".to_owned(),
+                JsonValue::Array(
+                    self.passes
+                        .iter()
+                        .map(|(name, seconds)| {
+                            JsonValue::Object(HashMap::from([
+                                ("name".to_owned(), string(name)),
+                                ("seconds".to_owned(), JsonValue::Number(*seconds)),
+                            ]))
+                        })
+                        .collect(),
+                ),
+            ),
+        ]))
+    }
+
+    /// Writes the report as json to the file at `path`.
+    pub(crate) fn write_file(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        write_sorted(&self.to_json(), &mut out)?;
+        writeln!(out)?;
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| (*a).to_owned()).collect()
+    }
+
+    #[test]
+    fn test_report_from_rustc_args() {
+        let mut report = ResourceReport::start(
+            "/path/to/bin/rustc",
+            &args(&[
+                "src/lib.rs",
+                "--crate-name",
+                "foo",
+                "--crate-type=rlib",
+                "--emit=dep-info,metadata,link",
+                "--json=timings",
+            ]),
+        );
+        report.collect_line("{\"$message_type\":\"section_timing\",\"event\":\"start\",\"name\":\"codegen\",\"timestamp\":1200}\n");
+        report.collect_line("{\"$message_type\":\"diagnostic\",\"rendered\":\"warning\"}\n");
+        report.collect_line("{\"$message_type\":\"section_timing\",\"event\":\"end\",\"name\":\"codegen\",\"timestamp\":3400}\n");
+        report.collect_line("time:   0.012; rss:   35MB ->   38MB (   +3MB)\tmacro_expand_crate\n");
+        report.finish(
+            0,
+            Some(ResourceUsage {
+                user_time: Duration::from_millis(3),
+                system_time: Duration::from_micros(1500),
+                max_rss_bytes: 4096,
+            }),
+        );
+
+        let JsonValue::Object(json) = report.to_json() else {
+            panic!("Expected an object");
+        };
+        assert_eq!(json["executable"], JsonValue::String("rustc".to_owned()));
+        assert_eq!(json["crate_name"], JsonValue::String("foo".to_owned()));
+        assert_eq!(
+            json["crate_types"],
+            JsonValue::Array(vec![JsonValue::String("rlib".to_owned())])
+        );
+        assert_eq!(
+            json["emit"],
+            JsonValue::String("dep-info,metadata,link".to_owned())
+        );
+        assert_eq!(json["exit_code"], JsonValue::Number(0.0));
+        assert_eq!(json["user_time_us"], JsonValue::Number(3000.0));
+        assert_eq!(json["system_time_us"], JsonValue::Number(1500.0));
+        assert_eq!(json["max_rss_bytes"], JsonValue::Number(4096.0));
+        assert_eq!(
+            report.sections,
+            vec![Section {
+                name: "codegen".to_owned(),
+                start_us: 1200,
+                end_us: 3400,
+            }]
+        );
+        assert_eq!(
+            report.passes,
+            vec![("macro_expand_crate".to_owned(), 0.012)]
+        );
+    }
+
+    #[test]
+    fn test_report_without_usage() {
+        let mut report = ResourceReport::start("build_script", &[]);
+        report.finish(1, None);
+        let JsonValue::Object(json) = report.to_json() else {
+            panic!("Expected an object");
+        };
+        assert_eq!(json["crate_name"], JsonValue::Null);
+        assert_eq!(json["user_time_us"], JsonValue::Null);
+        assert_eq!(json["exit_code"], JsonValue::Number(1.0));
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    #[test]
+    fn test_wait_with_usage() {
+        let mut child = std::process::Command::new("sh")
+            .args(["-c", "exit 3"])
+            .spawn()
+            .unwrap();
+        let (status, usage) = wait_with_usage(&mut child).unwrap();
+        assert_eq!(status.code(), Some(3));
+        assert!(usage.unwrap().max_rss_bytes > 0);
+    }
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::convert::{TryFrom, TryInto};
+use std::fs;
 
 use tinyjson::JsonValue;
 
@@ -61,16 +62,23 @@ impl TryFrom<JsonValue> for RustcMessage {
     }
 }
 
-/// process_rustc_json takes an output line from rustc configured with
+/// process_json takes an output line from rustc configured with
 /// --error-format=json, parses the json and returns the appropriate output
 /// according to the original --error-format supplied.
-/// Only messages are returned, emits are ignored.
-/// Retuns an errors if parsing json fails.
-pub(crate) fn process_json(line: String, error_format: ErrorFormat) -> LineResult {
+/// Messages are returned, emits are passed to `on_emit` which decides what
+/// should happen with them. This is used to implement pipelining in rules_rust,
+/// please see
+/// https://internals.rust-lang.org/t/evaluating-pipelined-rustc-compilation/10199
+/// Retuns an error if parsing json fails.
+pub(crate) fn process_json<F>(line: String, error_format: ErrorFormat, on_emit: F) -> LineResult
+where
+    F: FnOnce(&str) -> LineOutput,
+{
     let parsed: JsonValue = line
         .parse()
         .map_err(|_| "error parsing rustc output as json".to_owned())?;
     Ok(match parsed.try_into() {
+        Ok(RustcMessage::Emit(emit)) => on_emit(&emit),
         Ok(RustcMessage::Message(rendered)) => {
             output_based_on_error_format(line, rendered, error_format)
         }
@@ -78,33 +86,64 @@ pub(crate) fn process_json(line: String, error_format: ErrorFormat) -> LineResul
     })
 }
 
-/// stop_on_rmeta_completion parses the json output of rustc in the same way
-/// process_rustc_json does. In addition, it will signal to stop when metadata
-/// is emitted so the compiler can be terminated.
-/// This is used to implement pipelining in rules_rust, please see
-/// https://internals.rust-lang.org/t/evaluating-pipelined-rustc-compilation/10199
-/// Retuns an error if parsing json fails.
-/// TODO: pass a function to handle the emit event and merge with process_json
-pub(crate) fn stop_on_rmeta_completion(
-    line: String,
-    error_format: ErrorFormat,
-    kill: &mut bool,
-) -> LineResult {
-    let parsed: JsonValue = line
-        .parse()
-        .map_err(|_| "error parsing rustc output as json".to_owned())?;
-    Ok(match parsed.try_into() {
-        Ok(RustcMessage::Emit(emit)) if emit == "metadata" => {
-            *kill = true;
-            LineOutput::Terminate
-        }
-        Ok(RustcMessage::Message(rendered)) => {
-            output_based_on_error_format(line, rendered, error_format)
+/// The flags of a rustc command line that describe the crate being built.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct RustcArgs {
+    pub(crate) crate_name: Option<String>,
+    pub(crate) crate_types: Vec<String>,
+    pub(crate) emit: Option<String>,
+    // The path of the crate root source file.
+    pub(crate) crate_root: Option<String>,
+}
+
+/// Returns the values of `flag` in `args`, which may be passed either as
+/// `flag value` or as `flag=value`.
+fn flag_values<'a>(args: &'a [String], flag: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    args.iter().enumerate().filter_map(move |(index, arg)| {
+        if arg == flag {
+            args.get(index + 1).map(String::as_str)
+        } else {
+            arg.strip_prefix(flag)?.strip_prefix('=')
         }
-        _ => LineOutput::Skip,
     })
 }
 
+impl RustcArgs {
+    /// Extracts the crate description from the arguments of a rustc process.
+    /// Param files are expanded, but ones that can't be read are skipped as
+    /// rustc will report them anyway.
+    pub(crate) fn from_args(args: &[String]) -> Self {
+        let mut expanded = Vec::new();
+        for arg in args {
+            match arg.strip_prefix('@') {
+                Some(param_file) => {
+                    if let Ok(content) = fs::read_to_string(param_file) {
+                        expanded.extend(content.lines().map(str::to_owned));
+                    }
+                }
+                None => expanded.push(arg.clone()),
+            }
+        }
+        let crate_name = flag_values(&expanded, "--crate-name")
+            .next()
+            .map(str::to_owned);
+        let crate_types = flag_values(&expanded, "--crate-type")
+            .map(str::to_owned)
+            .collect();
+        let emit = flag_values(&expanded, "--emit").last().map(str::to_owned);
+        let crate_root = expanded
+            .iter()
+            .find(|arg| arg.ends_with(".rs") && !arg.starts_with('-'))
+            .cloned();
+        Self {
+            crate_name,
+            crate_types,
+            emit,
+            crate_root,
+        }
+    }
+}
+
 fn output_based_on_error_format(
     line: String,
     rendered: String,
@@ -118,3 +157,34 @@ fn output_based_on_error_format(
         ErrorFormat::Rendered => LineOutput::Message(rendered),
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rustc_args() {
+        let args: Vec<String> = [
+            "external/serde/src/lib.rs",
+            "--crate-name",
+            "serde",
+            "--crate-type=rlib",
+            "--emit=dep-info,metadata,link",
+            "--cfg",
+            "feature=\"std\"",
+            "-Copt-level=3",
+        ]
+        .iter()
+        .map(|a| (*a).to_owned())
+        .collect();
+        assert_eq!(
+            RustcArgs::from_args(&args),
+            RustcArgs {
+                crate_name: Some("serde".to_owned()),
+                crate_types: vec!["rlib".to_owned()],
+                emit: Some("dep-info,metadata,link".to_owned()),
+                crate_root: Some("external/serde/src/lib.rs".to_owned()),
+            }
+        );
+    }
+}
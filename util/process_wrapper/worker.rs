@@ -0,0 +1,819 @@
+// Copyright 2026 The Bazel Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//    http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Support for running process_wrapper as a Bazel persistent worker.
+//!
+//! Bazel starts the worker with `--persistent_worker` and then sends
+//! `WorkRequest` messages on stdin, expecting one `WorkResponse` per request
+//! on stdout. Both the default protobuf protocol (varint length-delimited
+//! messages) and the JSON protocol (one object per line, selected with
+//! `requires-worker-protocol: json`) are supported.
+//! See https://bazel.build/remote/creating for details.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Mutex;
+use std::thread::{self, ScopedJoinHandle};
+
+use tinyjson::JsonValue;
+
+use crate::flags::{FlagParseError, Flags, ParseOutcome};
+
+/// The flag Bazel appends to the startup arguments of a persistent worker.
+pub(crate) const PERSISTENT_WORKER_FLAG: &str = "--persistent_worker";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum WorkerProtocol {
+    Json,
+    Proto,
+}
+
+#[derive(Debug)]
+pub(crate) enum WorkerError {
+    FlagError(FlagParseError),
+    IO(io::Error),
+    Protocol(String),
+}
+
+impl fmt::Display for WorkerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::FlagError(e) => write!(f, "error parsing worker flags: {e}"),
+            Self::IO(e) => write!(f, "{e}"),
+            Self::Protocol(s) => write!(f, "worker protocol error: {s}"),
+        }
+    }
+}
+
+impl From<io::Error> for WorkerError {
+    fn from(err: io::Error) -> Self {
+        Self::IO(err)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct WorkRequest {
+    pub(crate) arguments: Vec<String>,
+    pub(crate) request_id: i32,
+    pub(crate) cancel: bool,
+    pub(crate) verbosity: i32,
+    pub(crate) sandbox_dir: String,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct WorkResponse {
+    pub(crate) exit_code: i32,
+    pub(crate) output: String,
+    pub(crate) request_id: i32,
+    pub(crate) was_cancelled: bool,
+}
+
+/// Returns true if process_wrapper was started by Bazel as a persistent worker.
+pub(crate) fn is_persistent_worker(argv: &[String]) -> bool {
+    argv.iter().skip(1).any(|arg| arg == PERSISTENT_WORKER_FLAG)
+}
+
+/// Parses the worker startup arguments, returning the protocol to speak.
+pub(crate) fn worker_protocol(argv: Vec<String>) -> Result<WorkerProtocol, WorkerError> {
+    let argv: Vec<String> = argv
+        .into_iter()
+        .filter(|arg| arg != PERSISTENT_WORKER_FLAG)
+        .collect();
+    let mut protocol_raw = None;
+    let mut flags = Flags::new();
+    flags.define_flag(
+        "--worker-protocol",
+        "The persistent worker protocol to speak, either 'proto' or 'json'.\n\
+        Default: `proto`",
+        &mut protocol_raw,
+    );
+    match flags.parse(argv).map_err(WorkerError::FlagError)? {
+        ParseOutcome::Help(help) => {
+            eprintln!("{help}");
+            std::process::exit(0);
+        }
+        ParseOutcome::Parsed(extra) if !extra.is_empty() => {
+            return Err(WorkerError::Protocol(format!(
+                "unexpected worker startup arguments: {extra:?}"
+            )))
+        }
+        ParseOutcome::Parsed(_) => {}
+    }
+    match protocol_raw.as_deref() {
+        None | Some("proto") => Ok(WorkerProtocol::Proto),
+        Some("json") => Ok(WorkerProtocol::Json),
+        Some(v) => Err(WorkerError::Protocol(format!(
+            "invalid --worker-protocol '{v}'"
+        ))),
+    }
+}
+
+/// Reads work requests from `input` until it is closed and writes a response
+/// for each of them to `output`.
+///
+/// `handler` receives the arguments and the id of a request and returns the
+/// exit code and the output that should be reported to Bazel. Requests with a
+/// non-zero id are multiplex requests and are processed concurrently, all
+/// others are processed one at a time in the order they are received.
+///
+/// Requests with a `sandbox_dir` are failed: a pipelined rustc process serves
+/// two actions, which can't share a sandbox. The `Rustc` actions don't set
+/// `supports-multiplex-sandboxing`, so Bazel doesn't send such requests.
+///
+/// A multiplex request which is cancelled while it is processed is answered
+/// with `was_cancelled` once its handler returns, the handler isn't
+/// interrupted. Cancel requests for requests which were already answered are
+/// ignored. A handler which panics fails its request.
+pub(crate) fn run_worker<R, W, F>(
+    mut input: R,
+    output: W,
+    protocol: WorkerProtocol,
+    handler: F,
+) -> Result<(), WorkerError>
+where
+    R: BufRead,
+    W: Write + Send,
+    F: Fn(Vec<String>, i32) -> (i32, String) + Sync,
+{
+    let output = Mutex::new(output);
+    let respond = |response: WorkResponse| -> Result<(), WorkerError> {
+        let mut output = output
+            .lock()
+            .map_err(|_| WorkerError::Protocol("worker output lock poisoned".to_owned()))?;
+        write_response(&mut *output, &response, protocol)?;
+        Ok(())
+    };
+    // Whether each multiplex request being processed was cancelled, by id.
+    let in_flight = Mutex::new(HashMap::new());
+    let lock_in_flight = || in_flight.lock().unwrap_or_else(|e| e.into_inner());
+    let handle = |request: WorkRequest| -> Result<(), WorkerError> {
+        let WorkRequest {
+            arguments,
+            request_id,
+            sandbox_dir,
+            ..
+        } = request;
+        let (exit_code, output) = if sandbox_dir.is_empty() {
+            panic::catch_unwind(AssertUnwindSafe(|| handler(arguments, request_id))).unwrap_or_else(
+                |panic| {
+                    let message = panic
+                        .downcast_ref::<&str>()
+                        .map(|s| s.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    (
+                        1,
+                        format!("process wrapper error: request handler panicked: {message}\n"),
+                    )
+                },
+            )
+        } else {
+            (
+                1,
+                format!(
+                    "process wrapper error: multiplex sandboxing is not supported, the action must not set supports-multiplex-sandboxing (sandbox_dir: {})\n",
+                    sandbox_dir
+                ),
+            )
+        };
+        // Hold the lock while responding so that a cancel request can't
+        // arrive between checking for cancellation and responding.
+        let mut in_flight = lock_in_flight();
+        let was_cancelled = in_flight.remove(&request_id).unwrap_or(false);
+        let response = if was_cancelled {
+            WorkResponse {
+                request_id,
+                was_cancelled,
+                ..Default::default()
+            }
+        } else {
+            WorkResponse {
+                exit_code,
+                output,
+                request_id,
+                was_cancelled,
+            }
+        };
+        respond(response)?;
+        drop(in_flight);
+        Ok(())
+    };
+
+    thread::scope(|scope| -> Result<(), WorkerError> {
+        let mut handlers: Vec<ScopedJoinHandle<'_, Result<(), WorkerError>>> = Vec::new();
+        while let Some(request) = read_request(&mut input, protocol)? {
+            // Join the handlers which are done so that they don't accumulate
+            // and their errors surface right away.
+            let (finished, running) = handlers.into_iter().partition(|h| h.is_finished());
+            handlers = running;
+            for handler in finished {
+                join_handler(handler)?;
+            }
+
+            if request.cancel {
+                if let Some(cancelled) = lock_in_flight().get_mut(&request.request_id) {
+                    *cancelled = true;
+                }
+            } else if request.request_id == 0 {
+                handle(request)?;
+            } else {
+                lock_in_flight().insert(request.request_id, false);
+                handlers.push(scope.spawn(|| handle(request)));
+            }
+        }
+        for handler in handlers {
+            join_handler(handler)?;
+        }
+        Ok(())
+    })
+}
+
+fn join_handler(handler: ScopedJoinHandle<'_, Result<(), WorkerError>>) -> Result<(), WorkerError> {
+    handler
+        .join()
+        .map_err(|_| WorkerError::Protocol("request handler panicked".to_owned()))?
+}
+
+/// Reads the next request from `input`, returning `None` once it is closed.
+pub(crate) fn read_request(
+    input: &mut impl BufRead,
+    protocol: WorkerProtocol,
+) -> Result<Option<WorkRequest>, WorkerError> {
+    match protocol {
+        WorkerProtocol::Json => loop {
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            return parse_json_request(&line).map(Some);
+        },
+        WorkerProtocol::Proto => {
+            let Some(len) = read_varint(input)? else {
+                return Ok(None);
+            };
+            let mut buf = vec![0; len as usize];
+            input.read_exact(&mut buf)?;
+            decode_request(&buf).map(Some)
+        }
+    }
+}
+
+/// Writes `response` to `output` using the given protocol and flushes it.
+pub(crate) fn write_response(
+    output: &mut impl Write,
+    response: &WorkResponse,
+    protocol: WorkerProtocol,
+) -> io::Result<()> {
+    match protocol {
+        WorkerProtocol::Json => {
+            let json = response_to_json(response)
+                .stringify()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            writeln!(output, "{json}")?;
+        }
+        WorkerProtocol::Proto => {
+            let buf = encode_response(response);
+            let mut framed = Vec::with_capacity(buf.len() + 5);
+            encode_varint(buf.len() as u64, &mut framed);
+            framed.extend_from_slice(&buf);
+            output.write_all(&framed)?;
+        }
+    }
+    output.flush()
+}
+
+fn parse_json_request(line: &str) -> Result<WorkRequest, WorkerError> {
+    let parsed: JsonValue = line
+        .parse()
+        .map_err(|e| WorkerError::Protocol(format!("invalid json work request: {e}")))?;
+    let JsonValue::Object(map) = parsed else {
+        return Err(WorkerError::Protocol(
+            "json work request is not an object".to_owned(),
+        ));
+    };
+    let mut request = WorkRequest::default();
+    if let Some(JsonValue::Array(arguments)) = map.get("arguments") {
+        for arg in arguments {
+            let JsonValue::String(arg) = arg else {
+                return Err(WorkerError::Protocol(format!(
+                    "non-string argument in json work request: {arg:?}"
+                )));
+            };
+            request.arguments.push(arg.clone());
+        }
+    }
+    if let Some(JsonValue::Number(n)) = map.get("requestId") {
+        request.request_id = *n as i32;
+    }
+    if let Some(JsonValue::Boolean(b)) = map.get("cancel") {
+        request.cancel = *b;
+    }
+    if let Some(JsonValue::Number(n)) = map.get("verbosity") {
+        request.verbosity = *n as i32;
+    }
+    if let Some(JsonValue::String(s)) = map.get("sandboxDir") {
+        request.sandbox_dir = s.clone();
+    }
+    Ok(request)
+}
+
+fn response_to_json(response: &WorkResponse) -> JsonValue {
+    JsonValue::Object(HashMap::from([
+        (
+            "exitCode".to_string(),
+            JsonValue::Number(response.exit_code as f64),
+        ),
+        (
+            "output".to_string(),
+            JsonValue::String(response.output.clone()),
+        ),
+        (
+            "requestId".to_string(),
+            JsonValue::Number(response.request_id as f64),
+        ),
+        (
+            "wasCancelled".to_string(),
+            JsonValue::Boolean(response.was_cancelled),
+        ),
+    ]))
+}
+
+const WIRE_TYPE_VARINT: u64 = 0;
+const WIRE_TYPE_I64: u64 = 1;
+const WIRE_TYPE_LEN: u64 = 2;
+const WIRE_TYPE_I32: u64 = 5;
+
+/// Reads a varint from `input`, returning `None` if `input` is at EOF.
+fn read_varint(input: &mut impl BufRead) -> Result<Option<u64>, WorkerError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = match input.fill_buf()?.first() {
+            Some(&b) => b,
+            None if shift == 0 => return Ok(None),
+            None => {
+                return Err(WorkerError::Protocol(
+                    "unexpected end of input in varint".to_owned(),
+                ))
+            }
+        };
+        input.consume(1);
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(WorkerError::Protocol("varint is too long".to_owned()))
+}
+
+fn decode_varint(buf: &mut &[u8]) -> Result<u64, WorkerError> {
+    read_varint(buf)?.ok_or_else(|| WorkerError::Protocol("unexpected end of message".to_owned()))
+}
+
+fn encode_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn decode_bytes<'a>(buf: &mut &'a [u8]) -> Result<&'a [u8], WorkerError> {
+    let len = decode_varint(buf)? as usize;
+    if buf.len() < len {
+        return Err(WorkerError::Protocol(
+            "length-delimited field exceeds message".to_owned(),
+        ));
+    }
+    let (bytes, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(bytes)
+}
+
+fn decode_string(buf: &mut &[u8]) -> Result<String, WorkerError> {
+    String::from_utf8(decode_bytes(buf)?.to_vec())
+        .map_err(|e| WorkerError::Protocol(format!("invalid utf-8 in string field: {e}")))
+}
+
+fn skip_field(buf: &mut &[u8], wire_type: u64) -> Result<(), WorkerError> {
+    let len = match wire_type {
+        WIRE_TYPE_VARINT => {
+            decode_varint(buf)?;
+            return Ok(());
+        }
+        WIRE_TYPE_LEN => {
+            decode_bytes(buf)?;
+            return Ok(());
+        }
+        WIRE_TYPE_I64 => 8,
+        WIRE_TYPE_I32 => 4,
+        _ => {
+            return Err(WorkerError::Protocol(format!(
+                "unsupported wire type {wire_type}"
+            )))
+        }
+    };
+    if buf.len() < len {
+        return Err(WorkerError::Protocol(
+            "fixed field exceeds message".to_owned(),
+        ));
+    }
+    *buf = &buf[len..];
+    Ok(())
+}
+
+fn decode_request(mut buf: &[u8]) -> Result<WorkRequest, WorkerError> {
+    let buf = &mut buf;
+    let mut request = WorkRequest::default();
+    while !buf.is_empty() {
+        let key = decode_varint(buf)?;
+        match (key >> 3, key & 0x7) {
+            (1, WIRE_TYPE_LEN) => request.arguments.push(decode_string(buf)?),
+            (3, WIRE_TYPE_VARINT) => request.request_id = decode_varint(buf)? as i32,
+            (4, WIRE_TYPE_VARINT) => request.cancel = decode_varint(buf)? != 0,
+            (5, WIRE_TYPE_VARINT) => request.verbosity = decode_varint(buf)? as i32,
+            (6, WIRE_TYPE_LEN) => request.sandbox_dir = decode_string(buf)?,
+            // Field 2 holds the inputs and their digests, which aren't needed.
+            (_, wire_type) => skip_field(buf, wire_type)?,
+        }
+    }
+    Ok(request)
+}
+
+fn encode_response(response: &WorkResponse) -> Vec<u8> {
+    let mut buf = Vec::new();
+    if response.exit_code != 0 {
+        encode_varint(1 << 3 | WIRE_TYPE_VARINT, &mut buf);
+        // Negative int32 values are sign extended to 64 bits.
+        encode_varint(response.exit_code as i64 as u64, &mut buf);
+    }
+    if !response.output.is_empty() {
+        encode_varint(2 << 3 | WIRE_TYPE_LEN, &mut buf);
+        encode_varint(response.output.len() as u64, &mut buf);
+        buf.extend_from_slice(response.output.as_bytes());
+    }
+    if response.request_id != 0 {
+        encode_varint(3 << 3 | WIRE_TYPE_VARINT, &mut buf);
+        encode_varint(response.request_id as i64 as u64, &mut buf);
+    }
+    if response.was_cancelled {
+        encode_varint(4 << 3 | WIRE_TYPE_VARINT, &mut buf);
+        encode_varint(1, &mut buf);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn encode_request(request: &WorkRequest) -> Vec<u8> {
+        let mut buf = Vec::new();
+        for arg in &request.arguments {
+            encode_varint(1 << 3 | WIRE_TYPE_LEN, &mut buf);
+            encode_varint(arg.len() as u64, &mut buf);
+            buf.extend_from_slice(arg.as_bytes());
+        }
+        // An input with a path and a digest, which must be skipped.
+        encode_varint(2 << 3 | WIRE_TYPE_LEN, &mut buf);
+        encode_varint(8, &mut buf);
+        buf.extend_from_slice(&[0x0a, 0x02, b'a', b'b', 0x12, 0x02, 0x01, 0x02]);
+        if request.request_id != 0 {
+            encode_varint(3 << 3 | WIRE_TYPE_VARINT, &mut buf);
+            encode_varint(request.request_id as u64, &mut buf);
+        }
+        if request.cancel {
+            encode_varint(4 << 3 | WIRE_TYPE_VARINT, &mut buf);
+            encode_varint(1, &mut buf);
+        }
+        let mut framed = Vec::new();
+        encode_varint(buf.len() as u64, &mut framed);
+        framed.extend_from_slice(&buf);
+        framed
+    }
+
+    fn decode_response(mut buf: &[u8]) -> WorkResponse {
+        let buf = &mut buf;
+        let mut response = WorkResponse::default();
+        while !buf.is_empty() {
+            let key = decode_varint(buf).unwrap();
+            match key >> 3 {
+                1 => response.exit_code = decode_varint(buf).unwrap() as i32,
+                2 => response.output = decode_string(buf).unwrap(),
+                3 => response.request_id = decode_varint(buf).unwrap() as i32,
+                4 => response.was_cancelled = decode_varint(buf).unwrap() != 0,
+                _ => panic!("unexpected field in response: {}", key),
+            }
+        }
+        response
+    }
+
+    fn read_proto_responses(mut output: &[u8]) -> Vec<WorkResponse> {
+        let mut responses = Vec::new();
+        while let Some(len) = read_varint(&mut output).unwrap() {
+            let (message, rest) = output.split_at(len as usize);
+            responses.push(decode_response(message));
+            output = rest;
+        }
+        responses
+    }
+
+    fn echo_handler(args: Vec<String>, _request_id: i32) -> (i32, String) {
+        let code = if args.iter().any(|a| a == "fail") {
+            1
+        } else {
+            0
+        };
+        (code, args.join(" "))
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|&s| s.to_owned()).collect()
+    }
+
+    #[test]
+    fn test_is_persistent_worker() {
+        assert!(is_persistent_worker(&args(&[
+            "process_wrapper",
+            "--persistent_worker"
+        ])));
+        assert!(!is_persistent_worker(&args(&[
+            "process_wrapper",
+            "--",
+            "rustc"
+        ])));
+    }
+
+    #[test]
+    fn test_worker_protocol() {
+        assert_eq!(
+            worker_protocol(args(&["process_wrapper", "--persistent_worker"])).unwrap(),
+            WorkerProtocol::Proto
+        );
+        assert_eq!(
+            worker_protocol(args(&[
+                "process_wrapper",
+                "--worker-protocol",
+                "json",
+                "--persistent_worker"
+            ]))
+            .unwrap(),
+            WorkerProtocol::Json
+        );
+        assert!(worker_protocol(args(&[
+            "process_wrapper",
+            "--persistent_worker",
+            "--worker-protocol",
+            "xml"
+        ]))
+        .is_err());
+    }
+
+    #[test]
+    fn test_varint_round_trip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut buf = Vec::new();
+            encode_varint(value, &mut buf);
+            assert_eq!(decode_varint(&mut buf.as_slice()).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_proto_singleplex() {
+        let mut input = encode_request(&WorkRequest {
+            arguments: args(&["--", "rustc", "lib.rs"]),
+            ..Default::default()
+        });
+        input.extend(encode_request(&WorkRequest {
+            arguments: args(&["fail"]),
+            ..Default::default()
+        }));
+        let mut output = Vec::new();
+        run_worker(
+            input.as_slice(),
+            &mut output,
+            WorkerProtocol::Proto,
+            echo_handler,
+        )
+        .unwrap();
+        assert_eq!(
+            read_proto_responses(&output),
+            vec![
+                WorkResponse {
+                    output: "-- rustc lib.rs".to_owned(),
+                    ..Default::default()
+                },
+                WorkResponse {
+                    exit_code: 1,
+                    output: "fail".to_owned(),
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_proto_multiplex() {
+        let mut input = Vec::new();
+        for request_id in 1..=8 {
+            input.extend(encode_request(&WorkRequest {
+                arguments: args(&[&format!("request{request_id}")]),
+                request_id,
+                ..Default::default()
+            }));
+        }
+        let mut output = Vec::new();
+        run_worker(
+            input.as_slice(),
+            &mut output,
+            WorkerProtocol::Proto,
+            echo_handler,
+        )
+        .unwrap();
+        let mut responses = read_proto_responses(&output);
+        responses.sort_by_key(|r| r.request_id);
+        assert_eq!(responses.len(), 8);
+        for (response, request_id) in responses.iter().zip(1..) {
+            assert_eq!(response.request_id, request_id);
+            assert_eq!(response.output, format!("request{request_id}"));
+        }
+    }
+
+    #[test]
+    fn test_proto_multiplex_cancel() {
+        let mut input = Vec::new();
+        for (request_id, arg, cancel) in [
+            (1, "block", false),
+            (1, "", true),
+            (2, "release", false),
+            // Request 3 was never sent.
+            (3, "", true),
+        ] {
+            input.extend(encode_request(&WorkRequest {
+                arguments: args(&[arg]),
+                request_id,
+                cancel,
+                ..Default::default()
+            }));
+        }
+        let (release, released) = std::sync::mpsc::channel();
+        let (release, released) = (Mutex::new(release), Mutex::new(released));
+        let mut output = Vec::new();
+        run_worker(
+            input.as_slice(),
+            &mut output,
+            WorkerProtocol::Proto,
+            |args: Vec<String>, request_id| {
+                // Request 1 is only released once its cancellation was read.
+                match args[0].as_str() {
+                    "block" => released.lock().unwrap().recv().unwrap(),
+                    _ => release.lock().unwrap().send(()).unwrap(),
+                }
+                echo_handler(args, request_id)
+            },
+        )
+        .unwrap();
+        let mut responses = read_proto_responses(&output);
+        responses.sort_by_key(|r| r.request_id);
+        assert_eq!(
+            responses,
+            vec![
+                WorkResponse {
+                    request_id: 1,
+                    was_cancelled: true,
+                    ..Default::default()
+                },
+                WorkResponse {
+                    output: "release".to_owned(),
+                    request_id: 2,
+                    ..Default::default()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_handler_panic_fails_request() {
+        let input = concat!(
+            r#"{"arguments": ["panic"], "requestId": 1}"#,
+            "\n",
+            r#"{"arguments": ["ok"], "requestId": 2}"#,
+            "\n"
+        );
+        let mut output = Vec::new();
+        run_worker(
+            input.as_bytes(),
+            &mut output,
+            WorkerProtocol::Json,
+            |args: Vec<String>, request_id| {
+                if args[0] == "panic" {
+                    panic!("boom");
+                }
+                echo_handler(args, request_id)
+            },
+        )
+        .unwrap();
+        let mut responses: Vec<JsonValue> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| l.parse().unwrap())
+            .collect();
+        responses.sort_by_key(|r| match r["requestId"] {
+            JsonValue::Number(n) => n as i32,
+            _ => 0,
+        });
+        assert_eq!(responses[0]["exitCode"], JsonValue::Number(1.0));
+        assert_eq!(
+            responses[0]["output"],
+            JsonValue::String("process wrapper error: request handler panicked: boom\n".to_owned())
+        );
+        assert_eq!(responses[1]["exitCode"], JsonValue::Number(0.0));
+    }
+
+    #[test]
+    fn test_proto_negative_exit_code() {
+        let buf = encode_response(&WorkResponse {
+            exit_code: -1,
+            ..Default::default()
+        });
+        assert_eq!(decode_response(&buf).exit_code, -1);
+    }
+
+    #[test]
+    fn test_proto_truncated_request() {
+        let input = encode_request(&WorkRequest {
+            arguments: args(&["--", "rustc"]),
+            ..Default::default()
+        });
+        let mut truncated = &input[..input.len() - 1];
+        assert!(read_request(&mut truncated, WorkerProtocol::Proto).is_err());
+    }
+
+    #[test]
+    fn test_json_worker() {
+        let input = concat!(
+            r#"{"arguments": ["--", "rustc", "lib.rs"], "inputs": [{"path": "lib.rs", "digest": "abc"}]}"#,
+            "\n\n",
+            r#"{"arguments": ["fail"], "requestId": 0}"#,
+            "\n",
+        );
+        let mut output = Vec::new();
+        run_worker(
+            input.as_bytes(),
+            &mut output,
+            WorkerProtocol::Json,
+            echo_handler,
+        )
+        .unwrap();
+        let responses: Vec<JsonValue> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|l| l.parse().unwrap())
+            .collect();
+        assert_eq!(
+            responses,
+            vec![
+                r#"{"exitCode": 0, "output": "-- rustc lib.rs", "requestId": 0, "wasCancelled": false}"#
+                    .parse::<JsonValue>()
+                    .unwrap(),
+                r#"{"exitCode": 1, "output": "fail", "requestId": 0, "wasCancelled": false}"#
+                    .parse::<JsonValue>()
+                    .unwrap(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sandboxed_request_rejected() {
+        let input = concat!(
+            r#"{"arguments": ["--", "rustc"], "requestId": 3, "sandboxDir": "sandbox/3"}"#,
+            "\n"
+        );
+        let mut output = Vec::new();
+        run_worker(
+            input.as_bytes(),
+            &mut output,
+            WorkerProtocol::Json,
+            echo_handler,
+        )
+        .unwrap();
+        let response: JsonValue = String::from_utf8(output).unwrap().trim().parse().unwrap();
+        assert_eq!(response["exitCode"], JsonValue::Number(1.0));
+        assert_eq!(response["requestId"], JsonValue::Number(3.0));
+    }
+}
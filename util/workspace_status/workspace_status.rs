@@ -1,17 +1,46 @@
 //! Utilities for parsing [workspace status stamps](https://bazel.build/docs/user-manual#workspace-status).
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
 /// The error type of workspace status parsing.
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub enum WorkspaceStatusError {
     /// The workspace status data is malformed and cannot be parsed.
     InvalidFormat(String),
+    /// A workspace status file could not be read.
+    Io(String),
+    /// A stamp key isn't a valid key, see [is_valid_key].
+    InvalidKey(String),
+    /// A placeholder refers to a key which isn't in the workspace status.
+    MissingKey(String),
 }
 
+impl fmt::Display for WorkspaceStatusError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WorkspaceStatusError::InvalidFormat(message) | WorkspaceStatusError::Io(message) => {
+                f.write_str(message)
+            }
+            WorkspaceStatusError::InvalidKey(key) => {
+                write!(f, "Invalid workspace status key: {:?}", key)
+            }
+            WorkspaceStatusError::MissingKey(key) => {
+                write!(f, "Workspace status key not found: {}", key)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WorkspaceStatusError {}
+
 /// Returns an iterator of workspace status stamp values parsed from the given text.
 pub fn parse_workspace_status_stamps(
     text: &'_ str,
 ) -> impl Iterator<Item = Result<(&'_ str, &'_ str), WorkspaceStatusError>> {
-    text.lines().map(|l| {
+    text.lines().filter(|l| !l.is_empty()).map(|l| {
         let pair = l.split_once(' ');
         pair.ok_or_else(|| {
             WorkspaceStatusError::InvalidFormat(format!(
@@ -22,6 +51,228 @@ pub fn parse_workspace_status_stamps(
     })
 }
 
+/// Returns whether `key` is a valid workspace status key: ASCII letters,
+/// digits and underscores, not starting with a digit.
+pub fn is_valid_key(key: &str) -> bool {
+    let mut chars = key.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// A workspace status stamp value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stamp {
+    pub value: String,
+    /// Whether the stamp comes from the volatile status file. Changes to
+    /// volatile stamps don't invalidate actions by themselves, but outputs
+    /// which depend on them aren't reproducible.
+    pub volatile: bool,
+}
+
+/// The stamps of the stable and volatile workspace status files.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WorkspaceStatus {
+    stamps: BTreeMap<String, Stamp>,
+}
+
+impl WorkspaceStatus {
+    /// Parses the contents of the stable and volatile status files.
+    ///
+    /// Keys must be valid and may only appear once across both files.
+    pub fn parse(stable: &str, volatile: &str) -> Result<Self, WorkspaceStatusError> {
+        let mut stamps = BTreeMap::new();
+        for (text, volatile) in [(stable, false), (volatile, true)] {
+            for pair in parse_workspace_status_stamps(text) {
+                let (key, value) = pair?;
+                if !is_valid_key(key) {
+                    return Err(WorkspaceStatusError::InvalidKey(key.to_owned()));
+                }
+                let stamp = Stamp {
+                    value: value.to_owned(),
+                    volatile,
+                };
+                if stamps.insert(key.to_owned(), stamp).is_some() {
+                    return Err(WorkspaceStatusError::InvalidFormat(format!(
+                        "Duplicate workspace status key: {}",
+                        key
+                    )));
+                }
+            }
+        }
+        Ok(WorkspaceStatus { stamps })
+    }
+
+    /// Creates a workspace status from stamps which were already parsed.
+    ///
+    /// Unlike [WorkspaceStatus::parse], keys aren't validated and duplicates
+    /// aren't errors: the last value of a key wins, so volatile stamps take
+    /// precedence over stable ones.
+    pub fn from_stamps<S, V>(stable: S, volatile: V) -> Self
+    where
+        S: IntoIterator<Item = (String, String)>,
+        V: IntoIterator<Item = (String, String)>,
+    {
+        let stable = stable.into_iter().map(|(key, value)| (key, value, false));
+        let volatile = volatile.into_iter().map(|(key, value)| (key, value, true));
+        let stamps = stable
+            .chain(volatile)
+            .map(|(key, value, volatile)| (key, Stamp { value, volatile }))
+            .collect();
+        WorkspaceStatus { stamps }
+    }
+
+    /// Reads and parses the stable and volatile status files, either of which
+    /// may be omitted.
+    pub fn load(
+        stable_file: Option<&Path>,
+        volatile_file: Option<&Path>,
+    ) -> Result<Self, WorkspaceStatusError> {
+        let read = |path: Option<&Path>| {
+            path.map_or_else(
+                || Ok(String::new()),
+                |path| {
+                    fs::read_to_string(path).map_err(|e| {
+                        WorkspaceStatusError::Io(format!(
+                            "Failed to read workspace status file {}: {}",
+                            path.display(),
+                            e
+                        ))
+                    })
+                },
+            )
+        };
+        Self::parse(&read(stable_file)?, &read(volatile_file)?)
+    }
+
+    /// Returns the stamp of `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&Stamp> {
+        self.stamps.get(key)
+    }
+
+    /// Returns whether `key` is a volatile stamp.
+    pub fn is_volatile(&self, key: &str) -> bool {
+        self.get(key).is_some_and(|stamp| stamp.volatile)
+    }
+
+    /// Returns an iterator over the keys and stamps, sorted by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Stamp)> {
+        self.stamps.iter().map(|(key, stamp)| (key.as_str(), stamp))
+    }
+
+    /// Returns a [Substitution] of this status' stamps into `{KEY}` placeholders.
+    pub fn substitution(&self, missing_keys: MissingKeys) -> Substitution<'_> {
+        Substitution {
+            status: self,
+            missing_keys,
+            used_volatile_keys: BTreeSet::new(),
+        }
+    }
+}
+
+/// What to do with placeholders whose key isn't in the workspace status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingKeys {
+    /// Leave the placeholder as it is.
+    Keep,
+    /// Replace the placeholder with an empty string.
+    Empty,
+    /// Fail with [WorkspaceStatusError::MissingKey].
+    Error,
+}
+
+/// Substitutes stamps into `{KEY}` placeholders, recording the volatile keys
+/// which were used.
+///
+/// Braces which don't enclose a valid key, or a key of the status, aren't
+/// placeholders, and neither are `${KEY}` which are left to other
+/// substitutions.
+#[derive(Debug)]
+pub struct Substitution<'s> {
+    status: &'s WorkspaceStatus,
+    missing_keys: MissingKeys,
+    used_volatile_keys: BTreeSet<&'s str>,
+}
+
+impl<'s> Substitution<'s> {
+    /// Returns `text` with its placeholders substituted.
+    pub fn substitute(&mut self, text: &str) -> Result<String, WorkspaceStatusError> {
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(open) = rest.find('{') {
+            let (before, from_open) = rest.split_at(open);
+            result.push_str(before);
+            let key = from_open[1..]
+                .find('}')
+                .map(|close| &from_open[1..close + 1])
+                .filter(|key| {
+                    (is_valid_key(key) || self.status.stamps.contains_key(*key))
+                        && !before.ends_with('$')
+                });
+            let key = match key {
+                Some(key) => key,
+                None => {
+                    result.push('{');
+                    rest = &from_open[1..];
+                    continue;
+                }
+            };
+            match self.status.stamps.get_key_value(key) {
+                Some((key, stamp)) => {
+                    if stamp.volatile {
+                        self.used_volatile_keys.insert(key.as_str());
+                    }
+                    result.push_str(&stamp.value);
+                }
+                None => match self.missing_keys {
+                    MissingKeys::Keep => {
+                        result.push_str(&from_open[..key.len() + 2]);
+                    }
+                    MissingKeys::Empty => {}
+                    MissingKeys::Error => {
+                        return Err(WorkspaceStatusError::MissingKey(key.to_owned()))
+                    }
+                },
+            }
+            rest = &from_open[key.len() + 2..];
+        }
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Substitutes the placeholders of each argument.
+    pub fn substitute_args<I>(&mut self, args: I) -> Result<Vec<String>, WorkspaceStatusError>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        args.into_iter()
+            .map(|arg| self.substitute(arg.as_ref()))
+            .collect()
+    }
+
+    /// Substitutes the placeholders of each environment variable value.
+    pub fn substitute_env<I, K>(
+        &mut self,
+        env: I,
+    ) -> Result<BTreeMap<K, String>, WorkspaceStatusError>
+    where
+        I: IntoIterator<Item = (K, String)>,
+        K: Ord,
+    {
+        env.into_iter()
+            .map(|(key, value)| Ok((key, self.substitute(&value)?)))
+            .collect()
+    }
+
+    /// The volatile keys substituted so far. Outputs of actions which use
+    /// them change with every build, defeating caching.
+    pub fn used_volatile_keys(&self) -> impl Iterator<Item = &'s str> + '_ {
+        self.used_volatile_keys.iter().copied()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -118,4 +369,105 @@ mod test {
             error
         );
     }
+
+    #[test]
+    fn test_parse_stable_and_volatile() {
+        let status = WorkspaceStatus::parse(
+            "STABLE_GIT_COMMIT abc123\nBUILD_USER user name\n",
+            "BUILD_TIMESTAMP 1730574875\n\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            status.get("BUILD_USER"),
+            Some(&Stamp {
+                value: "user name".to_owned(),
+                volatile: false,
+            })
+        );
+        assert!(status.is_volatile("BUILD_TIMESTAMP"));
+        assert!(!status.is_volatile("STABLE_GIT_COMMIT"));
+        assert!(!status.is_volatile("UNKNOWN"));
+        assert_eq!(
+            status.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+            ["BUILD_TIMESTAMP", "BUILD_USER", "STABLE_GIT_COMMIT"]
+        );
+    }
+
+    #[test]
+    fn test_parse_invalid_keys() {
+        assert_eq!(
+            WorkspaceStatus::parse("STABLE-VALUE x", ""),
+            Err(WorkspaceStatusError::InvalidKey("STABLE-VALUE".to_owned()))
+        );
+        assert_eq!(
+            WorkspaceStatus::parse("BUILD_USER a", "BUILD_USER b"),
+            Err(WorkspaceStatusError::InvalidFormat(
+                "Duplicate workspace status key: BUILD_USER".to_owned()
+            ))
+        );
+        assert!(is_valid_key("_KEY_1"));
+        assert!(!is_valid_key("1KEY"));
+        assert!(!is_valid_key(""));
+    }
+
+    #[test]
+    fn test_substitute() {
+        let status =
+            WorkspaceStatus::parse("STABLE_VERSION 1.2.3", "BUILD_TIMESTAMP 1730574875").unwrap();
+
+        let mut substitution = status.substitution(MissingKeys::Keep);
+        assert_eq!(
+            substitution
+                .substitute("v{STABLE_VERSION} {UNKNOWN} ${pwd} {not a key} {}")
+                .unwrap(),
+            "v1.2.3 {UNKNOWN} ${pwd} {not a key} {}"
+        );
+        assert_eq!(substitution.used_volatile_keys().count(), 0);
+        assert_eq!(
+            substitution
+                .substitute_args(["--stamp={BUILD_TIMESTAMP}", "{"])
+                .unwrap(),
+            ["--stamp=1730574875", "{"]
+        );
+        assert_eq!(
+            substitution.used_volatile_keys().collect::<Vec<_>>(),
+            ["BUILD_TIMESTAMP"]
+        );
+
+        let mut substitution = status.substitution(MissingKeys::Empty);
+        assert_eq!(
+            substitution
+                .substitute_env([("VERSION", "{STABLE_VERSION}{UNKNOWN}".to_owned())])
+                .unwrap(),
+            BTreeMap::from([("VERSION", "1.2.3".to_owned())])
+        );
+
+        let mut substitution = status.substitution(MissingKeys::Error);
+        assert_eq!(
+            substitution.substitute("{STABLE_VERSION} {UNKNOWN}"),
+            Err(WorkspaceStatusError::MissingKey("UNKNOWN".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_from_stamps() {
+        let status = WorkspaceStatus::from_stamps(
+            [
+                ("git.commit".to_owned(), "abc123".to_owned()),
+                ("BUILD_USER".to_owned(), "stable".to_owned()),
+            ],
+            [("BUILD_USER".to_owned(), "volatile".to_owned())],
+        );
+        assert!(status.is_volatile("BUILD_USER"));
+        assert_eq!(status.get("BUILD_USER").unwrap().value, "volatile");
+
+        let mut substitution = status.substitution(MissingKeys::Keep);
+        assert_eq!(
+            substitution
+                .substitute("{git.commit} {BUILD_USER} {other.key}")
+                .unwrap(),
+            "abc123 volatile {other.key}"
+        );
+    }
 }